@@ -0,0 +1,6 @@
+//! Passes the target triple to `--version --verbose`
+
+fn main() {
+    let target = std::env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=JJ_STARSHIP_TARGET={target}");
+}
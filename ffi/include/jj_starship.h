@@ -0,0 +1,26 @@
+/* C bindings for the jj-starship collector (libjj_starship_ffi) */
+
+#ifndef JJ_STARSHIP_H
+#define JJ_STARSHIP_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Info for the repo containing path, as the JSON document `jj-starship info`
+ * prints; NULL outside a repo or on error. Release with js_free. */
+char *js_collect(const char *path);
+
+/* Render an info document (from js_collect or hand-written) as a prompt.
+ * config is config file contents (TOML) used in place of the config file, or
+ * NULL to read the config file; NULL on invalid input. Release with js_free. */
+char *js_render(const char *json, const char *config);
+
+/* Release a string returned by js_collect or js_render; NULL is ignored. */
+void js_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,89 @@
+//! C ABI for linking the collector in-process, built as a cdylib
+//! Strings cross the boundary as NUL-terminated UTF-8; every returned string
+//! is owned by the caller and released with `js_free`
+
+use std::ffi::{CStr, CString, c_char};
+use std::path::Path;
+use std::ptr;
+
+/// Collect info for the repo containing `path`, as the JSON document
+/// `jj-starship info` prints; null outside a repo or on error
+///
+/// # Safety
+/// `path` must be null or a valid NUL-terminated string
+#[must_use]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn js_collect(path: *const c_char) -> *mut c_char {
+    // SAFETY: upheld by the caller
+    let Some(path) = (unsafe { read_str(path) }) else {
+        return ptr::null_mut();
+    };
+    jj_starship::collect_json(Path::new(path)).map_or(ptr::null_mut(), into_raw)
+}
+
+/// Render an info document (from `js_collect` or hand-written) as a prompt
+/// `config` is config file contents (TOML) used in place of the config file,
+/// or null to read it as the binary does; null on invalid input
+///
+/// # Safety
+/// `json` and `config` must each be null or a valid NUL-terminated string
+#[must_use]
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn js_render(json: *const c_char, config: *const c_char) -> *mut c_char {
+    // SAFETY: upheld by the caller
+    let (json, config_toml) = unsafe { (read_str(json), read_str(config)) };
+    let Some(json) = json else {
+        return ptr::null_mut();
+    };
+    jj_starship::render_json(json, config_toml).map_or(ptr::null_mut(), into_raw)
+}
+
+/// Release a string returned by `js_collect` or `js_render`; null is ignored
+///
+/// # Safety
+/// `s` must be null or a pointer returned by this library, not yet freed
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn js_free(s: *mut c_char) {
+    if !s.is_null() {
+        // SAFETY: the pointer came from `CString::into_raw` in `into_raw`
+        drop(unsafe { CString::from_raw(s) });
+    }
+}
+
+/// Borrow a C string; None for null or invalid UTF-8
+unsafe fn read_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    // SAFETY: non-null and NUL-terminated per the callers' contracts
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+/// Hand a string to C; interior NULs (never produced by the renderer) give null
+fn into_raw(s: String) -> *mut c_char {
+    CString::new(s).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_roundtrip() {
+        let json = CString::new(r#"{"change_id":"kxqpmtzv","bookmark":"main"}"#).unwrap();
+        let config = CString::new("no_symbol = true\n[jj]\ncolor = false\n").unwrap();
+        unsafe {
+            let out = js_render(json.as_ptr(), config.as_ptr());
+            assert!(!out.is_null());
+            let rendered = CStr::from_ptr(out).to_str().unwrap().to_string();
+            js_free(out);
+            assert!(rendered.contains("main"), "{rendered}");
+            assert!(!rendered.contains('\x1b'), "{rendered}");
+
+            let bad = CString::new("{}").unwrap();
+            assert!(js_render(bad.as_ptr(), ptr::null()).is_null());
+            assert!(js_collect(ptr::null()).is_null());
+            js_free(ptr::null_mut());
+        }
+    }
+}
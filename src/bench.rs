@@ -0,0 +1,91 @@
+//! `bench`: repeated detection, collection and rendering with per-phase statistics
+//! Phases are the ones `--timings` reports, so a slow phase shows up by name
+
+use crate::timing::Timings;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Run `prompt` `iterations` times, each with fresh timings, and summarize
+/// every phase (summed within an iteration) plus the whole run
+pub fn run(iterations: usize, mut prompt: impl FnMut(&Timings)) -> String {
+    let mut phases: Vec<(&'static str, Vec<Duration>)> = Vec::new();
+    let mut totals = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let timings = Timings::new(true);
+        let start = Instant::now();
+        prompt(&timings);
+        totals.push(start.elapsed());
+
+        let mut this_run: Vec<(&'static str, Duration)> = Vec::new();
+        for (phase, duration) in timings.phases() {
+            match this_run.iter_mut().find(|(p, _)| *p == phase) {
+                Some((_, sum)) => *sum += duration,
+                None => this_run.push((phase, duration)),
+            }
+        }
+        for (phase, duration) in this_run {
+            match phases.iter_mut().find(|(p, _)| *p == phase) {
+                Some((_, samples)) => samples.push(duration),
+                None => phases.push((phase, vec![duration])),
+            }
+        }
+    }
+
+    let mut out = format!(
+        "{iterations} iterations\n  {:<14}{:>10}{:>10}{:>10}\n",
+        "phase (ms)", "min", "median", "p95"
+    );
+    for (phase, samples) in phases.iter_mut().chain([("total", totals)].iter_mut()) {
+        let (min, median, p95) = summarize(samples);
+        let _ = writeln!(
+            out,
+            "  {phase:<14}{:>10.3}{:>10.3}{:>10.3}",
+            millis(min),
+            millis(median),
+            millis(p95)
+        );
+    }
+    out
+}
+
+/// Min, median and 95th percentile (nearest rank) of the samples
+fn summarize(samples: &mut [Duration]) -> (Duration, Duration, Duration) {
+    if samples.is_empty() {
+        return Default::default();
+    }
+    samples.sort_unstable();
+    let rank = |p: usize| samples[(samples.len() * p).div_ceil(100).max(1) - 1];
+    (samples[0], rank(50), rank(95))
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let mut samples: Vec<Duration> = (1..=20).rev().map(Duration::from_millis).collect();
+        assert_eq!(
+            summarize(&mut samples),
+            (
+                Duration::from_millis(1),
+                Duration::from_millis(10),
+                Duration::from_millis(19)
+            )
+        );
+        let mut one = [Duration::from_millis(3)];
+        assert_eq!(summarize(&mut one).2, Duration::from_millis(3));
+    }
+
+    #[test]
+    fn test_run_reports_phases() {
+        let report = run(3, |timings| timings.time("detect", || ()));
+        assert!(report.starts_with("3 iterations\n"));
+        assert!(report.contains("\n  detect "));
+        assert!(report.contains("\n  total "));
+    }
+}
@@ -0,0 +1,262 @@
+//! On-disk cache of collected JJ info
+//! JJ state only changes through operations, so an entry stays exact for as long
+//! as the repo's op heads (and the collection options) are unchanged; the
+//! working-copy state, which the sparse patterns live in, is checked too
+//! A hit reads no more than that; a miss loads the repo through jj-lib, refs-only
+//! prompts included, as there is no hand-written reader for the op store
+//! Each repo has its own entry file, replaced atomically on write, so any number
+//! of shells can read without locking and never see a half-written entry
+
+use crate::config::{self, Config};
+use crate::error::Result;
+use crate::jj::{self, JjInfo};
+use crate::json;
+use crate::log;
+use crate::timing::Timings;
+use jj_lib::config::StackedConfig;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Entry layout version; binaries of another layout sharing the directory
+/// read each other's entries as misses
+const FORMAT: usize = 1;
+
+/// Collections quicker than this aren't stored: reading the entry back saves
+/// next to nothing, and most prompts after a jj command would rewrite it
+const MIN_COLLECT_TIME: Duration = Duration::from_millis(10);
+
+/// Collect JJ info, served from the cache while the op heads are unchanged
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(root = %repo_root.display())))]
+pub fn collect_jj(
+    repo_root: &Path,
+    config: &Config,
+    user_config: Option<&StackedConfig>,
+    timings: &Timings,
+) -> Result<JjInfo> {
+    // One-off lookups of other revisions would evict the prompt's entry, and
+    // new files don't record an operation
+    if config.rev.is_some() || config.jj_large_files || config.no_cache {
+        return jj::collect(repo_root, config, user_config, timings);
+    }
+    let fingerprint = jj_fingerprint(repo_root, config, user_config);
+    let cached = timings.time("cache", || {
+        fingerprint
+            .as_deref()
+            .and_then(|fp| load_jj(repo_root, config.refs_only, Some(fp)))
+    });
+    if let Some(info) = cached {
+        log::debug!("cache hit");
+        return Ok(info);
+    }
+    if fingerprint.is_none() {
+        log::debug!("no op heads found, bypassing cache");
+    } else {
+        log::debug!("cache miss");
+    }
+
+    let started = Instant::now();
+    let info = match jj::collect(repo_root, config, user_config, timings) {
+        Ok(info) => info,
+        // The last entry, marked stale, beats no prompt while the repo can't be
+        // read (a concurrent operation holding it, a stale workspace)
+        Err(e) => match load_jj(repo_root, config.refs_only, None) {
+            Some(info) => {
+                log::warning!("{e}; showing outdated cached info");
+                return Ok(JjInfo {
+                    stale: true,
+                    ..info
+                });
+            }
+            None => return Err(e),
+        },
+    };
+    // Details dropped for time would be served as the full picture later
+    if timings.dropped() {
+        log::debug!("incomplete info, not cached");
+        // The invalidated entry for the same change still has the dropped
+        // counts, which beat none as long as they're marked
+        if let Some(last) = load_jj(repo_root, config.refs_only, None)
+            .filter(|last| last.change_id == info.change_id && !last.status_skipped)
+        {
+            log::debug!("out of time, showing outdated cached info");
+            return Ok(JjInfo {
+                stale: true,
+                ..last
+            });
+        }
+    } else if started.elapsed() < MIN_COLLECT_TIME {
+        log::debug!("quick collection, not cached");
+    } else if let Some(fp) = &fingerprint {
+        store_jj(repo_root, config.refs_only, fp, &info);
+    }
+    Ok(info)
+}
+
+/// Cache validity key: op heads plus every option that changes what gets collected
+fn jj_fingerprint(
+    repo_root: &Path,
+    config: &Config,
+    user_config: Option<&StackedConfig>,
+) -> Option<String> {
+    let jj_dir = repo_root.join(".jj");
+    let repo_path = jj_dir.join("repo");
+    // Secondary workspaces store the repo location in a file
+    let repo_dir = if repo_path.is_file() {
+        jj_dir.join(fs::read_to_string(&repo_path).ok()?.trim())
+    } else {
+        repo_path
+    };
+
+    let mut heads: Vec<String> = fs::read_dir(repo_dir.join("op_heads").join("heads"))
+        .ok()?
+        .filter_map(|entry| Some(entry.ok()?.file_name().to_string_lossy().into_owned()))
+        .collect();
+    if heads.is_empty() {
+        return None;
+    }
+    heads.sort_unstable();
+
+    Some(format!(
+        "{}|{}|id={}|tracking={}|slow={}|refs_only={}|state={}|wip={}|author={}|default={}|jj={:016x}|wc={}",
+        env!("CARGO_PKG_VERSION"),
+        heads.join(","),
+        config.id_length,
+        config.jj_tracking,
+        config.is_slow_path(repo_root),
+        config.refs_only,
+        config.state_colors,
+        config.wip_prefixes.join(","),
+        config.foreign_author,
+        config.default_branch,
+        user_config.map_or(0, config_digest),
+        working_copy_state(&jj_dir),
+    ))
+}
+
+/// Modification time and size of the working-copy state, which `jj sparse set`
+/// rewrites; empty when it can't be read
+fn working_copy_state(jj_dir: &Path) -> String {
+    let Ok(metadata) = fs::metadata(jj_dir.join("working_copy").join("tree_state")) else {
+        return String::new();
+    };
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_nanos());
+    format!("{mtime}:{}", metadata.len())
+}
+
+/// Digest of the user's jj config, which jj-lib loads the repo with
+fn config_digest(config: &StackedConfig) -> u64 {
+    config.layers().iter().fold(FNV_OFFSET, |hash, layer| {
+        fnv1a(fnv1a(hash, layer.data.to_string().as_bytes()), b"\0")
+    })
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// 64-bit FNV-1a, continuing from `hash`; unlike `DefaultHasher` its output
+/// is the same in every build, so entries outlive upgrades of the binary
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `$XDG_CACHE_HOME/jj-starship`, falling back to `~/.cache/jj-starship`
+fn cache_dir() -> Option<PathBuf> {
+    Some(config::xdg_dir("XDG_CACHE_HOME", ".cache")?.join("jj-starship"))
+}
+
+/// Refs-only entries (`--instant`, `--transient`) live beside the full one, so
+/// the two phases of an async prompt don't evict each other
+fn entry_path(repo_root: &Path, refs_only: bool) -> Option<PathBuf> {
+    let hash = fnv1a(FNV_OFFSET, repo_root.as_os_str().as_encoded_bytes());
+    let kind = if refs_only { "-refs" } else { "" };
+    Some(cache_dir()?.join(format!("{hash:016x}{kind}.json")))
+}
+
+/// The entry for `fingerprint`, or with None whatever entry the repo has
+fn load_jj(repo_root: &Path, refs_only: bool, fingerprint: Option<&str>) -> Option<JjInfo> {
+    let text = fs::read_to_string(entry_path(repo_root, refs_only)?).ok()?;
+    let entry = json::parse(&text).ok()?;
+    if entry.get_usize("format") != FORMAT {
+        return None;
+    }
+    // Root guards against hash collisions
+    if fingerprint.is_some_and(|fp| entry.get_str("fingerprint").as_deref() != Some(fp))
+        || entry.get_str("root")? != repo_root.to_string_lossy()
+    {
+        return None;
+    }
+    JjInfo::from_json(entry.get("info")?)
+}
+
+/// Best effort: a failed write only costs the next prompt a cache miss
+fn store_jj(repo_root: &Path, refs_only: bool, fingerprint: &str, info: &JjInfo) {
+    let Some(path) = entry_path(repo_root, refs_only) else {
+        return;
+    };
+    let entry = json::Value::Object(vec![
+        ("format".into(), FORMAT.into()),
+        ("fingerprint".into(), fingerprint.into()),
+        (
+            "root".into(),
+            repo_root.to_string_lossy().into_owned().into(),
+        ),
+        ("info".into(), info.to_json()),
+    ]);
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Err(e) = write_atomic(&path, &entry.to_string()) {
+        log::debug!("cache write to {} failed: {e}", path.display());
+    }
+}
+
+/// Write to a temporary file beside `path`, then rename it over `path`
+/// Readers see the old entry or the new one, and concurrent writers each
+/// replace it whole (the last rename wins)
+fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(".{}.tmp", std::process::id()));
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, contents)
+        .and_then(|()| fs::rename(&tmp, path))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp);
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fnv1a() {
+        // Reference values of 64-bit FNV-1a
+        assert_eq!(fnv1a(FNV_OFFSET, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET, b"foobar"), 0x8594_4171_f739_67e8);
+        assert_eq!(
+            fnv1a(fnv1a(FNV_OFFSET, b"foo"), b"bar"),
+            fnv1a(FNV_OFFSET, b"foobar")
+        );
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir().join(format!("jj-starship-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("entry.json");
+        write_atomic(&path, "{\"a\":1}").unwrap();
+        write_atomic(&path, "{\"a\":2}").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\":2}");
+        // No temporary files left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
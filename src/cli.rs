@@ -0,0 +1,1325 @@
+//! Command-line entry point: argument parsing, subcommands and the prompt
+
+#[cfg(feature = "git")]
+use crate::config::Colocated;
+#[cfg(feature = "cli")]
+use crate::config::{ColorFlags, DisplayFlags};
+use crate::config::{Config, ConfigArgs};
+use crate::detect::{DetectResult, RepoType};
+use crate::exports::Exports;
+#[cfg(feature = "git")]
+use crate::git;
+use crate::timing::Timings;
+#[cfg(feature = "cli")]
+use crate::{
+    bench, config, demo, html, import, info, init, json, migrate, porcelain, schema, serve,
+    validate, version,
+};
+use crate::{cache, custom, detect, hook, jj, log, osc, output, term};
+#[cfg(feature = "cli")]
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::env;
+#[cfg(feature = "cli")]
+use std::fs;
+#[cfg(feature = "cli")]
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[cfg(feature = "cli")]
+#[derive(Parser, Default)]
+#[command(name = "jj-starship")]
+#[command(about = "Unified Git/JJ Starship prompt module")]
+#[allow(clippy::struct_excessive_bools)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Print version
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// With --version, also print features, libgit2, jj and target for bug reports
+    #[arg(long, requires = "version")]
+    verbose: bool,
+
+    /// Override working directory
+    #[arg(long, global = true)]
+    cwd: Option<PathBuf>,
+
+    /// Backend to run: auto detects, jj or git skip the other kind of repo
+    #[arg(long, global = true, value_enum, default_value_t = RepoTypeArg::Auto)]
+    repo_type: RepoTypeArg,
+
+    /// Backend for colocated repos: jj, git, or merged (JJ with Git's file status)
+    #[arg(long, global = true, value_name = "SOURCE", value_parser = ["jj", "git", "merged"])]
+    colocated: Option<String>,
+
+    /// Max length for branch/bookmark name (0 = unlimited)
+    #[arg(long, global = true)]
+    truncate_name: Option<usize>,
+
+    /// Length of `change_id/commit` hash to display (default: 8)
+    #[arg(long, global = true)]
+    id_length: Option<usize>,
+
+    /// Symbol prefix for JJ repos (default: "󱗆")
+    #[arg(long, global = true)]
+    jj_symbol: Option<String>,
+
+    /// Disable symbol prefix entirely
+    #[arg(long, global = true)]
+    no_symbol: bool,
+
+    /// Nerd Font generation for the default symbols: v3, v2, or none (default: detected)
+    #[arg(long, global = true, value_name = "VERSION", value_parser = ["v3", "v2", "none"])]
+    nerd_font: Option<String>,
+
+    /// Color tier: truecolor, 256, or 16 (default: detected from COLORTERM/TERM)
+    #[arg(long, global = true, value_name = "TIER", value_parser = ["truecolor", "256", "16"])]
+    colors: Option<String>,
+
+    /// Word before the symbol (default: "on ", "" to drop it)
+    #[arg(long, global = true, value_name = "TEXT", allow_hyphen_values = true)]
+    prefix_text: Option<String>,
+
+    /// Text appended after the prompt (default: "")
+    #[arg(long, global = true, value_name = "TEXT", allow_hyphen_values = true)]
+    suffix: Option<String>,
+
+    /// Appended when the prompt shows outdated cached info (default: "~", "" to drop it)
+    #[arg(long, global = true, value_name = "TEXT", allow_hyphen_values = true)]
+    stale_marker: Option<String>,
+
+    /// Status indicators to leave out, comma-separated (e.g. untracked,ahead)
+    #[arg(long, global = true, value_name = "INDICATORS", value_delimiter = ',')]
+    hide: Vec<String>,
+
+    /// Style of the repo symbol, e.g. "bold blue" (overrides `symbol_style`)
+    #[arg(long, global = true, value_name = "STYLE")]
+    symbol_color: Option<String>,
+
+    /// Style of the bookmark/branch name (overrides `name_style`)
+    #[arg(long, global = true, value_name = "STYLE")]
+    name_color: Option<String>,
+
+    /// Style of the change ID/commit hash (overrides `id_style`)
+    #[arg(long, global = true, value_name = "STYLE")]
+    id_color: Option<String>,
+
+    /// Style of the [status] brackets and symbols (overrides `status_style`)
+    #[arg(long, global = true, value_name = "STYLE")]
+    status_color: Option<String>,
+
+    /// Terminal width; narrow terminals drop segments to fit (default: $COLUMNS)
+    #[arg(long, global = true, value_name = "N")]
+    columns: Option<usize>,
+
+    /// Milliseconds before details are dropped by `priority` (0 = unlimited)
+    #[arg(long, global = true, value_name = "MS")]
+    time_budget_ms: Option<usize>,
+
+    /// Disable output styling
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Compact prompt: symbol, name and conflict marker only, without color (the `minimal` preset)
+    #[arg(short, long, global = true, conflicts_with = "preset")]
+    minimal: bool,
+
+    /// Every optional segment and detail at once (the `full` preset)
+    #[arg(long, global = true, conflicts_with_all = ["preset", "minimal"])]
+    full: bool,
+
+    /// Base options on a preset: minimal, full, nerd, ascii, or one from the config file
+    #[arg(long, global = true, value_name = "NAME")]
+    preset: Option<String>,
+
+    /// Output format: ansi for terminals, starship for a custom module, html for documentation previews
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Ansi)]
+    output: OutputFormat,
+
+    /// Print per-phase durations to stderr
+    #[arg(long, global = true)]
+    timings: bool,
+
+    // JJ display flags
+    /// Hide "on {symbol}" prefix for JJ repos
+    #[arg(long, global = true)]
+    no_jj_prefix: bool,
+    /// Hide bookmark name for JJ repos
+    #[arg(long, global = true)]
+    no_jj_name: bool,
+    /// Hide `change_id` for JJ repos
+    #[arg(long, global = true)]
+    no_jj_id: bool,
+    /// Hide [status] for JJ repos
+    #[arg(long, global = true)]
+    no_jj_status: bool,
+    /// Show the tracked remote (`→origin`) and per-remote ahead/behind for JJ bookmarks
+    #[arg(long, global = true)]
+    jj_tracking: bool,
+    /// Warn about new files too large for jj to snapshot (walks the working copy)
+    #[arg(long, global = true)]
+    jj_large_files: bool,
+    /// Mark commits authored by someone other than the configured user (`≠`)
+    #[arg(long, global = true)]
+    foreign_author: bool,
+    /// Mark the repo's default branch (`⚑`): `origin/HEAD`, or jj's `trunk()`
+    #[arg(long, global = true)]
+    default_branch: bool,
+    /// Mark a repo nested inside another repo (`▣`)
+    #[arg(long, global = true)]
+    nested: bool,
+    /// Don't read or write the JJ info cache
+    #[arg(long, global = true)]
+    no_cache: bool,
+    /// Read colors from the user's jj config (`ui.color`, `[colors]`)
+    #[arg(long, global = true)]
+    jj_config: bool,
+    /// Color the name by repo state: green clean, yellow dirty, red conflicted
+    #[arg(long, global = true)]
+    state_colors: bool,
+    /// Append counts to status symbols (`+2!5?1`)
+    #[arg(long, global = true)]
+    counts: bool,
+    /// Render status as words (`conflicted, 2 ahead`) instead of glyphs
+    #[arg(long, global = true)]
+    plain_words: bool,
+    /// Put the status on a second line, for multi-line prompts
+    #[arg(long, global = true)]
+    block: bool,
+    /// Report the directory (OSC 7) and repo user vars (OSC 1337) to the terminal
+    #[arg(long, global = true)]
+    osc: bool,
+    /// Escape `[`, `]`, `$` and `\` for starship's format parser (as `--output starship`)
+    #[arg(long, global = true)]
+    escape: bool,
+    /// Trim whitespace from the end of the prompt
+    #[arg(long, global = true)]
+    no_trailing_space: bool,
+    /// End the prompt with a newline
+    #[arg(long, global = true)]
+    newline: bool,
+
+    /// Skip file status (shown as `≋`) for repos under this path (repeatable)
+    #[arg(long, global = true, value_name = "PATH")]
+    slow_path: Vec<PathBuf>,
+    /// Scan file status on NFS/SMB/FUSE mounts too (skipped by default, shown as `≋`)
+    #[arg(long, global = true)]
+    network_status: bool,
+    /// Detect from the shell's `$PWD`, keeping symlinked paths, instead of the resolved directory
+    #[arg(long, global = true)]
+    logical_pwd: bool,
+
+    #[cfg(feature = "git")]
+    #[command(flatten)]
+    git: GitArgs,
+}
+
+#[cfg(all(feature = "cli", feature = "git"))]
+#[derive(Args, Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct GitArgs {
+    /// Symbol prefix for Git repos (default: "")
+    #[arg(long, global = true)]
+    git_symbol: Option<String>,
+    /// Hide "on {symbol}" prefix for Git repos
+    #[arg(long, global = true)]
+    no_git_prefix: bool,
+    /// Hide branch name for Git repos
+    #[arg(long, global = true)]
+    no_git_name: bool,
+    /// Hide (commit) for Git repos
+    #[arg(long, global = true)]
+    no_git_id: bool,
+    /// Hide [status] for Git repos
+    #[arg(long, global = true)]
+    no_git_status: bool,
+    /// Stop counting untracked files past N, shown as `?N+` (default: 1000, 0 = unlimited)
+    #[arg(long, global = true)]
+    max_untracked: Option<usize>,
+    /// Skip file status (shown as `≋`) when the index has more than N entries (0 = never)
+    #[arg(long, global = true)]
+    large_repo_threshold: Option<usize>,
+    /// Show a single `±N` count of changed files instead of per-category symbols
+    #[arg(long, global = true)]
+    git_dirty_count: bool,
+    /// Show a branch both ahead and behind as `⇕N/M` instead of `⇡N⇣M`
+    #[arg(long, global = true)]
+    git_diverged: bool,
+    /// Count every file inside untracked directories (slow with large new directories)
+    #[arg(long, global = true)]
+    git_recurse_untracked: bool,
+    /// Count submodules with changes or new commits as modified
+    #[arg(long, global = true)]
+    git_submodules: bool,
+    /// Only show whether anything changed (`*`), skipping per-category file counts
+    #[arg(long, global = true)]
+    git_simple_dirty: bool,
+    /// Show Git LFS locks you hold (`⊠`) and lockable files modified without one (`⊘`)
+    #[arg(long, global = true)]
+    git_lfs_locks: bool,
+}
+
+#[cfg(feature = "cli")]
+impl Cli {
+    /// Options for `Config::new` from the parsed flags
+    fn config_args(&self) -> ConfigArgs {
+        let jj_flags = DisplayFlags {
+            no_prefix: self.no_jj_prefix,
+            no_name: self.no_jj_name,
+            no_id: self.no_jj_id,
+            no_status: self.no_jj_status,
+            no_color: self.no_color,
+        };
+
+        #[allow(unused_mut)]
+        let mut args = ConfigArgs {
+            truncate_name: self.truncate_name,
+            id_length: self.id_length,
+            jj_symbol: self.jj_symbol.clone(),
+            no_symbol: self.no_symbol,
+            nerd_font: self.nerd_font.clone(),
+            colors: self.colors.clone(),
+            prefix_text: self.prefix_text.clone(),
+            suffix: self.suffix.clone(),
+            stale_marker: self.stale_marker.clone(),
+            jj_tracking: self.jj_tracking,
+            jj_large_files: self.jj_large_files,
+            foreign_author: self.foreign_author,
+            default_branch: self.default_branch,
+            nested: self.nested,
+            no_cache: self.no_cache,
+            jj_config: self.jj_config,
+            state_colors: self.state_colors,
+            counts: self.counts,
+            plain_words: self.plain_words,
+            block: self.block,
+            osc: self.osc,
+            escape: self.escape,
+            no_trailing_space: self.no_trailing_space,
+            newline: self.newline,
+            slow_paths: self.slow_path.clone(),
+            network_status: self.network_status,
+            logical_pwd: self.logical_pwd,
+            colocated: self.colocated.clone(),
+            columns: self.columns,
+            time_budget_ms: self.time_budget_ms,
+            preset: self
+                .preset
+                .clone()
+                .or_else(|| self.minimal.then(|| "minimal".to_string()))
+                .or_else(|| self.full.then(|| "full".to_string())),
+            jj_flags,
+            hide: self.hide.clone(),
+            color_flags: ColorFlags {
+                symbol: self.symbol_color.clone(),
+                name: self.name_color.clone(),
+                id: self.id_color.clone(),
+                status: self.status_color.clone(),
+            },
+            ..ConfigArgs::default()
+        };
+
+        #[cfg(feature = "git")]
+        {
+            args.git_symbol.clone_from(&self.git.git_symbol);
+            args.max_untracked = self.git.max_untracked;
+            args.large_repo_threshold = self.git.large_repo_threshold;
+            args.git_dirty_count = self.git.git_dirty_count;
+            args.git_diverged = self.git.git_diverged;
+            args.git_recurse_untracked = self.git.git_recurse_untracked;
+            args.git_submodules = self.git.git_submodules;
+            args.git_simple_dirty = self.git.git_simple_dirty;
+            args.git_lfs_locks = self.git.git_lfs_locks;
+            args.git_flags = DisplayFlags {
+                no_prefix: self.git.no_git_prefix,
+                no_name: self.git.no_git_name,
+                no_id: self.git.no_git_id,
+                no_status: self.git.no_git_status,
+                no_color: self.no_color,
+            };
+        }
+        args
+    }
+}
+
+/// Backend choice for `--repo-type`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+enum RepoTypeArg {
+    #[default]
+    Auto,
+    Jj,
+    Git,
+}
+
+#[cfg(feature = "cli")]
+impl RepoTypeArg {
+    /// Repo type detection is restricted to, if any
+    const fn forced(self) -> Option<RepoType> {
+        match self {
+            Self::Auto => None,
+            Self::Jj => Some(RepoType::Jj),
+            Self::Git => Some(RepoType::Git),
+        }
+    }
+}
+
+/// How rendered prompts are written
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+enum OutputFormat {
+    #[default]
+    Ansi,
+    /// ANSI with starship's format characters escaped
+    Starship,
+    Html,
+    /// Versioned `key<TAB>value` lines of the collected info (prompt and info)
+    Porcelain,
+}
+
+#[cfg(feature = "cli")]
+impl OutputFormat {
+    /// Encode an ANSI-styled render in this format
+    fn encode(self, rendered: String) -> String {
+        match self {
+            // Porcelain is written from collected info, never from a render
+            Self::Ansi | Self::Porcelain => rendered,
+            Self::Starship => output::escape_starship(&rendered),
+            Self::Html => html::from_ansi(&rendered),
+        }
+    }
+}
+
+/// Where `--report-width` sends the prompt's width
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum WidthReport {
+    /// A line on file descriptor 3, beside the prompt on stdout
+    Fd3,
+    /// `{"prompt": ..., "width": ...}` on stdout instead of the bare prompt
+    Json,
+}
+
+/// A rendered prompt with its trailing whitespace trimmed unless `trailing_space`
+fn trimmed(config: &Config, output: String) -> String {
+    if config.trailing_space {
+        output
+    } else {
+        output::trim_trailing_space(&output)
+    }
+}
+
+/// Write the prompt's width to fd 3, which the caller opened (`3>&1`, `3>file`)
+#[cfg(feature = "cli")]
+fn report_width_fd3(width: usize) {
+    let written = fs::OpenOptions::new()
+        .append(true)
+        .open("/dev/fd/3")
+        .and_then(|mut fd| writeln!(fd, "{width}"));
+    if let Err(e) = written {
+        log::warning!("--report-width: can't write to fd 3: {e}");
+    }
+}
+
+#[cfg(feature = "cli")]
+#[derive(Subcommand)]
+enum Command {
+    /// Output prompt string (default)
+    Prompt(PromptArgs),
+    /// Exit 0 if in repo, 1 otherwise (for starship "when" condition)
+    Detect(DetectArgs),
+    /// Exit 0 if the working copy is clean, 1 if dirty, 2 if unknown or outside a repo
+    Status(StatusArgs),
+    /// Print `export JJ_STARSHIP_*=...` lines with the collected repo data, for `eval`
+    Env,
+    /// Run a long-lived server speaking another prompt tool's protocol
+    Serve(ServeArgs),
+    /// Warm caches for the directory without printing anything
+    Prefetch,
+    /// Print the collected repo info as a JSON document (the input `render --stdin-json` reads)
+    Info,
+    /// Render collected info from a JSON document (for theme development)
+    Render(RenderArgs),
+    /// Print the JSON Schema of the info document or the config file
+    Schema {
+        #[arg(value_enum)]
+        of: SchemaKind,
+    },
+    /// Print a gallery of sample prompt states with the active options
+    Demo,
+    /// Time repeated prompts in the current directory: min/median/p95 per phase
+    Bench(BenchArgs),
+    /// Inspect the effective configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Write a config file from starship's `[git_branch]`, `[git_commit]` and `[git_status]` settings
+    ImportStarship {
+        /// starship config to read (default: `$STARSHIP_CONFIG` or ~/.config/starship.toml)
+        path: Option<PathBuf>,
+        /// Merge into an existing jj-starship config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print shell hooks that prefetch on directory change
+    Init {
+        #[arg(value_enum)]
+        shell: init::Shell,
+    },
+}
+
+/// Document described by `schema`
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SchemaKind {
+    /// `info` output, `render --stdin-json` input and porcelain keys
+    Info,
+    /// The config file
+    Config,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print effective options and where each value came from
+    Show,
+    /// Check a config file (default: the active one) for errors
+    Validate {
+        /// Config file to check
+        path: Option<PathBuf>,
+    },
+    /// Print config file lines for the set `JJ_STARSHIP_*` variables and the jj-starship flags in a starship.toml
+    Migrate {
+        /// starship config whose `[custom.*]` command runs jj-starship
+        path: Option<PathBuf>,
+    },
+}
+
+#[cfg(feature = "cli")]
+#[derive(Args, Default)]
+struct PromptArgs {
+    /// Print a refs-only prompt immediately, then continuation data for `--complete` on a second line
+    #[arg(long, conflicts_with = "complete")]
+    instant: bool,
+    /// Finish a prompt started with `--instant`; pass its continuation data to skip detection
+    #[arg(long, value_name = "DATA", num_args = 0..=1, default_missing_value = "")]
+    complete: Option<String>,
+    /// Print only `{symbol}{name}`, without status, for transient prompts in scrollback
+    #[arg(long, conflicts_with = "instant")]
+    transient: bool,
+    /// JJ revision to render instead of the working copy: bookmark, tag, or change/commit ID
+    #[arg(long, value_name = "REV")]
+    rev: Option<String>,
+    /// Git ref or revision to render instead of HEAD (no file status)
+    #[arg(long = "ref", value_name = "REF")]
+    git_ref: Option<String>,
+    /// Also report the prompt's width in terminal cells: on fd 3 (default), or as JSON
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TARGET",
+        num_args = 0..=1,
+        default_missing_value = "fd3",
+        conflicts_with = "instant"
+    )]
+    report_width: Option<WidthReport>,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Args)]
+struct DetectArgs {
+    /// Exit with the repo kind: 10 jj, 11 jj-colocated, 12 git, 1 none
+    #[arg(long)]
+    kind: bool,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Args)]
+struct StatusArgs {
+    /// Print nothing; only set the exit status
+    #[arg(long, short)]
+    quiet: bool,
+    /// Count untracked files as dirty (Git; JJ tracks new files itself)
+    #[arg(long)]
+    include_untracked: bool,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Args)]
+struct RenderArgs {
+    /// Read a `JjInfo` or `GitInfo` JSON object from stdin
+    #[arg(long)]
+    stdin_json: bool,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Args)]
+struct BenchArgs {
+    /// Prompts to run
+    #[arg(long, default_value_t = 20)]
+    iterations: usize,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Args)]
+struct ServeArgs {
+    /// Speak the gitstatusd protocol on stdin/stdout (for powerlevel10k)
+    #[arg(long)]
+    gitstatus: bool,
+    /// gitstatusd options passed by launchers (ignored)
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, hide = true)]
+    _gitstatusd_args: Vec<String>,
+}
+
+/// Entry point without the `cli` feature: no arguments, the prompt for the
+/// current directory, configured through the config file and `JJ_STARSHIP_*`
+#[cfg(not(feature = "cli"))]
+pub fn run() -> ExitCode {
+    log::init();
+    term::enable_vt();
+    let config = Config::new(ConfigArgs::default());
+    let Some(cwd) = config
+        .logical_pwd
+        .then(detect::logical_cwd)
+        .flatten()
+        .or_else(|| env::current_dir().ok())
+    else {
+        return ExitCode::FAILURE;
+    };
+    let timings = Timings::new(false);
+    let result = detect::detect_enabled(&cwd, &config, None);
+    if let Some(output) = render(
+        result,
+        &config,
+        &timings,
+        config.osc.then_some(cwd.as_path()),
+    ) {
+        let output = trimmed(&config, output);
+        let output = if config.escape {
+            output::escape_starship(&output)
+        } else {
+            output
+        };
+        if config.newline {
+            println!("{output}");
+        } else {
+            print!("{output}");
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Entry point: parse the arguments and run the subcommand (the prompt by default)
+#[cfg(feature = "cli")]
+pub fn run() -> ExitCode {
+    log::init();
+    term::enable_vt();
+    let cli = parse_cli();
+    if cli.version {
+        if cli.verbose {
+            print!("{}", version::verbose());
+        } else {
+            println!("{}", version::short());
+        }
+        return ExitCode::SUCCESS;
+    }
+    let config_args = cli.config_args();
+    let config = Config::new(config_args.clone());
+    let Some(cwd) = cli
+        .cwd
+        .or_else(|| config.logical_pwd.then(detect::logical_cwd).flatten())
+        .or_else(|| env::current_dir().ok())
+    else {
+        return ExitCode::FAILURE;
+    };
+    let timings = Timings::new(cli.timings);
+    let format = match cli.output {
+        OutputFormat::Ansi if config.escape => OutputFormat::Starship,
+        format => format,
+    };
+    let forced = cli.repo_type.forced();
+    let command = cli
+        .command
+        .unwrap_or_else(|| Command::Prompt(PromptArgs::default()));
+    if format == OutputFormat::Porcelain && !matches!(command, Command::Prompt(_) | Command::Info) {
+        eprintln!("jj-starship: --output porcelain only applies to prompt and info");
+        return ExitCode::FAILURE;
+    }
+
+    match command {
+        Command::Prompt(args) if format == OutputFormat::Porcelain => {
+            let config = Config {
+                rev: args.rev,
+                git_ref: args.git_ref,
+                ..config
+            };
+            print_porcelain(&cwd, &config, forced, &timings)
+        }
+        Command::Info if format == OutputFormat::Porcelain => {
+            print_porcelain(&cwd, &config, forced, &timings)
+        }
+        Command::Prompt(args) if args.instant => {
+            prompt_instant(&cwd, config, args, format, forced, &timings)
+        }
+        Command::Prompt(args) => prompt(&cwd, config, args, format, forced, &timings),
+        Command::Serve(args) => run_serve(&args, config, &config_args),
+        Command::Prefetch => prefetch(&cwd, config, forced, &timings),
+        Command::Render(args) => render_stdin(&args, &cwd, &config, format),
+        Command::Config {
+            command: ConfigCommand::Show,
+        } => {
+            print!("{}", config.describe());
+            ExitCode::SUCCESS
+        }
+        Command::Config {
+            command: ConfigCommand::Validate { path },
+        } => config_validate(path),
+        Command::Config {
+            command: ConfigCommand::Migrate { path },
+        } => config_migrate(path),
+        Command::Bench(args) => run_bench(&args, &cwd, config, forced),
+        Command::Schema { of } => {
+            let schema = match of {
+                SchemaKind::Info => schema::info(),
+                SchemaKind::Config => schema::config(),
+            };
+            println!("{schema}");
+            ExitCode::SUCCESS
+        }
+        Command::Demo => {
+            print!("{}", format.encode(demo::gallery(&config)));
+            ExitCode::SUCCESS
+        }
+        Command::ImportStarship { path, force } => import_starship(path, force),
+        Command::Init { shell } => print_init(shell),
+        Command::Detect(args) if args.kind => {
+            let result = detect::detect_enabled(&cwd, &config, forced);
+            ExitCode::from(result.repo_type.exit_code())
+        }
+        Command::Status(args) => status(&cwd, config, &args, forced, &timings),
+        Command::Env => print_env(&cwd, config, forced, &timings),
+        Command::Info => print_info(&cwd, &config, forced, &timings),
+        Command::Detect(_) => {
+            if detect::in_repo(&cwd, &config, forced) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+    }
+}
+
+/// `prompt --instant`: the refs-only prompt, then its continuation on line 2
+#[cfg(feature = "cli")]
+fn prompt_instant(
+    cwd: &Path,
+    config: Config,
+    args: PromptArgs,
+    format: OutputFormat,
+    forced: Option<RepoType>,
+    timings: &Timings,
+) -> ExitCode {
+    let result = timings.time("detect", || detect::detect_enabled(cwd, &config, forced));
+    let continuation = result.continuation();
+    // The continuation must stay on line 2, so no block layout here
+    let config = Config {
+        refs_only: true,
+        block: false,
+        rev: args.rev,
+        git_ref: args.git_ref,
+        ..config
+    };
+    if let (Some(output), Some(continuation)) = (
+        render(result, &config, timings, config.osc.then_some(cwd)),
+        continuation,
+    ) {
+        println!("{}", format.encode(trimmed(&config, output)));
+        println!("{continuation}");
+    }
+    timings.report();
+    ExitCode::SUCCESS
+}
+
+/// `prompt`: the prompt for `cwd`, or for the repo an instant prompt found
+#[cfg(feature = "cli")]
+fn prompt(
+    cwd: &Path,
+    config: Config,
+    args: PromptArgs,
+    format: OutputFormat,
+    forced: Option<RepoType>,
+    timings: &Timings,
+) -> ExitCode {
+    let config = Config {
+        rev: args.rev,
+        git_ref: args.git_ref,
+        ..config
+    };
+    let config = if args.transient {
+        config.transient()
+    } else {
+        config
+    };
+    let result = timings.time("detect", || {
+        args.complete
+            .as_deref()
+            .and_then(|data| DetectResult::from_continuation(data, cwd))
+            .unwrap_or_else(|| detect::detect_enabled(cwd, &config, forced))
+    });
+    if let Some(output) = render(result, &config, timings, config.osc.then_some(cwd)) {
+        let output = trimmed(&config, output);
+        let width = output::visible_width(&output);
+        let output = format.encode(output);
+        match args.report_width {
+            Some(WidthReport::Json) => {
+                let report = json::Value::Object(vec![
+                    ("prompt".into(), output.into()),
+                    ("width".into(), width.into()),
+                ]);
+                println!("{report}");
+            }
+            report => {
+                if config.newline {
+                    println!("{output}");
+                } else {
+                    print!("{output}");
+                }
+                if report == Some(WidthReport::Fd3) {
+                    io::stdout().flush().ok();
+                    report_width_fd3(width);
+                }
+            }
+        }
+    }
+    timings.report();
+    ExitCode::SUCCESS
+}
+
+/// `prefetch`: collect without printing, to populate the caches
+#[cfg(feature = "cli")]
+fn prefetch(cwd: &Path, config: Config, forced: Option<RepoType>, timings: &Timings) -> ExitCode {
+    // Collection populates the JJ cache and warms OS caches for Git
+    let result = timings.time("detect", || detect::detect_enabled(cwd, &config, forced));
+    // Prefetching has no prompt waiting on it, so nothing is dropped
+    let config = Config {
+        time_budget_ms: 0,
+        ..config
+    };
+    let _ = render(result, &config, timings, None);
+    timings.report();
+    ExitCode::SUCCESS
+}
+
+/// `render --stdin-json`: the prompt for an info object read from stdin
+#[cfg(feature = "cli")]
+fn render_stdin(args: &RenderArgs, cwd: &Path, config: &Config, format: OutputFormat) -> ExitCode {
+    if !args.stdin_json {
+        eprintln!("jj-starship render: no input selected (use --stdin-json)");
+        return ExitCode::FAILURE;
+    }
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        eprintln!("jj-starship render: {e}");
+        return ExitCode::FAILURE;
+    }
+    match info::render(&input, cwd, config) {
+        Ok(output) => {
+            println!("{}", format.encode(trimmed(config, output)));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("jj-starship render: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `config validate`: report problems in the config file, failing if any
+#[cfg(feature = "cli")]
+fn config_validate(path: Option<PathBuf>) -> ExitCode {
+    let Some(path) = path.or_else(config::file_path) else {
+        eprintln!("jj-starship config validate: no config file path");
+        return ExitCode::FAILURE;
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let diagnostics = validate::validate(&text);
+    for diagnostic in &diagnostics {
+        eprintln!("{}:{diagnostic}", path.display());
+    }
+    if diagnostics.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// `serve`: answer prompt requests over a long-running protocol
+#[cfg(feature = "cli")]
+fn run_serve(args: &ServeArgs, config: Config, config_args: &ConfigArgs) -> ExitCode {
+    if !args.gitstatus {
+        eprintln!("jj-starship serve: no protocol selected (use --gitstatus)");
+        return ExitCode::FAILURE;
+    }
+    match serve::gitstatus(config, || Config::new(config_args.clone())) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(_) => ExitCode::FAILURE,
+    }
+}
+
+/// `bench`: time repeated prompts for `cwd`
+#[cfg(feature = "cli")]
+fn run_bench(args: &BenchArgs, cwd: &Path, config: Config, forced: Option<RepoType>) -> ExitCode {
+    // Every iteration after the first would otherwise time a cache hit
+    let config = Config {
+        no_cache: true,
+        ..config
+    };
+    let report = bench::run(args.iterations, |timings| {
+        let result = timings.time("detect", || detect::detect_enabled(cwd, &config, forced));
+        render(result, &config, timings, None);
+    });
+    print!("{report}");
+    ExitCode::SUCCESS
+}
+
+/// `init`: the shell integration script, calling this executable
+#[cfg(feature = "cli")]
+fn print_init(shell: init::Shell) -> ExitCode {
+    let exe = env::current_exe().ok().map_or_else(
+        || "jj-starship".into(),
+        |p| p.to_string_lossy().into_owned(),
+    );
+    print!("{}", init::script(shell, &exe));
+    ExitCode::SUCCESS
+}
+
+/// `status`: exit 0 when clean, 1 when dirty, 2 outside a repo
+#[cfg(feature = "cli")]
+fn status(
+    cwd: &Path,
+    config: Config,
+    args: &StatusArgs,
+    forced: Option<RepoType>,
+    timings: &Timings,
+) -> ExitCode {
+    // `has_changes` is only collected for state colors; a cached answer may
+    // predate the latest snapshot
+    let config = Config {
+        state_colors: true,
+        no_cache: true,
+        ..config
+    };
+    let result = timings.time("detect", || detect::detect_enabled(cwd, &config, forced));
+    let dirty = is_dirty(result, &config, args.include_untracked, timings);
+    timings.report();
+    match dirty {
+        Some(dirty) => {
+            if !args.quiet {
+                println!("{}", if dirty { "dirty" } else { "clean" });
+            }
+            ExitCode::from(u8::from(dirty))
+        }
+        None => ExitCode::from(2),
+    }
+}
+
+/// `env`: shell exports describing the repo
+#[cfg(feature = "cli")]
+fn print_env(cwd: &Path, config: Config, forced: Option<RepoType>, timings: &Timings) -> ExitCode {
+    // `has_changes` is only collected for state colors
+    let config = Config {
+        state_colors: true,
+        ..config
+    };
+    let result = timings.time("detect", || detect::detect_enabled(cwd, &config, forced));
+    print!("{}", collect_exports(result, &config, timings).script());
+    timings.report();
+    ExitCode::SUCCESS
+}
+
+/// `info`: the collected info as JSON
+#[cfg(feature = "cli")]
+fn print_info(
+    cwd: &Path,
+    config: &Config,
+    forced: Option<RepoType>,
+    timings: &Timings,
+) -> ExitCode {
+    let result = timings.time("detect", || detect::detect_enabled(cwd, config, forced));
+    let info = info::collect(result, config, timings);
+    timings.report();
+    match info {
+        Some(info) => {
+            println!("{info}");
+            ExitCode::SUCCESS
+        }
+        None => ExitCode::FAILURE,
+    }
+}
+
+/// Porcelain lines for `cwd`; fails only when a repo's info can't be collected
+#[cfg(feature = "cli")]
+fn print_porcelain(
+    cwd: &Path,
+    config: &Config,
+    forced: Option<RepoType>,
+    timings: &Timings,
+) -> ExitCode {
+    let result = timings.time("detect", || detect::detect_enabled(cwd, config, forced));
+    let (repo_type, repo_root) = (result.repo_type, result.repo_root.as_deref());
+    let fields = match (config.backend(repo_type), repo_root) {
+        (RepoType::Jj | RepoType::JjColocated, Some(root)) => {
+            cache::collect_jj(root, config, None, timings)
+                .inspect_err(|e| log::warning!("{e}"))
+                .ok()
+                .map(|info| porcelain::jj_fields(&info))
+        }
+        #[cfg(feature = "git")]
+        (RepoType::Git, Some(root)) => git::collect(root, config, timings)
+            .inspect_err(|e| log::warning!("{e}"))
+            .ok()
+            .map(|info| porcelain::git_fields(&info)),
+        _ => None,
+    };
+    timings.report();
+    print!(
+        "{}",
+        porcelain::format(repo_type, repo_root, fields.as_deref())
+    );
+    if fields.is_none() && repo_type != RepoType::None {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Resolve the environment and the flags of the starship.toml command (if
+/// any) without the config file, printing what they set as config file lines
+#[cfg(feature = "cli")]
+fn config_migrate(path: Option<PathBuf>) -> ExitCode {
+    let cli = match path {
+        None => Cli::default(),
+        Some(path) => match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| migrate::command_args(&text))
+            .and_then(|args| Cli::try_parse_from(args).map_err(|e| e.to_string()))
+        {
+            Ok(cli) => cli,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e.trim_end());
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    let config = Config::new(ConfigArgs {
+        config_toml: Some(String::new()),
+        ..cli.config_args()
+    });
+    print!("{}", config.migrated());
+    ExitCode::SUCCESS
+}
+
+/// Convert starship's config and write it to the config file path, leaving an
+/// existing file alone unless `force`
+#[cfg(feature = "cli")]
+fn import_starship(path: Option<PathBuf>, force: bool) -> ExitCode {
+    let (Some(path), Some(target)) = (path.or_else(import::starship_path), config::file_path())
+    else {
+        eprintln!("jj-starship import-starship: no config file path");
+        return ExitCode::FAILURE;
+    };
+    let import = match fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| import::import(&text))
+    {
+        Ok(import) => import,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    let config = match fs::read_to_string(&target) {
+        Ok(_) if !force => {
+            eprintln!(
+                "jj-starship import-starship: {} exists (--force merges into it)",
+                target.display()
+            );
+            return ExitCode::FAILURE;
+        }
+        Ok(existing) => match import::merge(&existing, &import.config) {
+            Ok(merged) => merged,
+            Err(e) => {
+                eprintln!("{}: {e}", target.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        Err(_) => import.config,
+    };
+    let written = target
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| fs::write(&target, &config));
+    if let Err(e) = written {
+        eprintln!("{}: {e}", target.display());
+        return ExitCode::FAILURE;
+    }
+    for key in &import.skipped {
+        eprintln!("jj-starship import-starship: skipped {key}");
+    }
+    eprintln!("jj-starship import-starship: wrote {}", target.display());
+    ExitCode::SUCCESS
+}
+
+/// Starship runs `jj-starship` (or `jj-starship prompt`) bare on every prompt;
+/// those skip building the clap command, anything else goes through clap
+#[cfg(feature = "cli")]
+fn parse_cli() -> Cli {
+    let mut args = env::args_os().skip(1);
+    match (args.next(), args.next()) {
+        (None, _) => Cli::default(),
+        (Some(command), None) if command == "prompt" => Cli {
+            command: Some(Command::Prompt(PromptArgs::default())),
+            ..Cli::default()
+        },
+        _ => Cli::parse(),
+    }
+}
+
+/// Run prompt generation, returning None on error (silent fail for prompts)
+/// With `osc_cwd`, the directory and repo data are also reported to the terminal
+#[allow(unreachable_patterns)]
+#[cfg_attr(not(feature = "git"), allow(unused_variables))]
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(repo_type = result.repo_type.as_str())))]
+fn render(
+    result: DetectResult,
+    config: &Config,
+    timings: &Timings,
+    osc_cwd: Option<&Path>,
+) -> Option<String> {
+    // Repo data describes the working tree, not the metadata directory
+    if let Some(dir) = result.internals {
+        if let Some(cwd) = osc_cwd {
+            osc::emit(cwd, &Exports::none());
+        }
+        return Some(output::format_internals(dir, config));
+    }
+    let nested = config.nested
+        && result
+            .repo_root
+            .as_deref()
+            .is_some_and(|root| detect::is_nested(root, detect::Limits::from_config(config)));
+    let unaliased = config;
+    let aliased = result
+        .repo_root
+        .as_deref()
+        .and_then(|root| config.repo_alias(root))
+        .map(|alias| config.aliased(alias));
+    let config = aliased.as_ref().unwrap_or(config);
+    let custom_values = result
+        .repo_root
+        .as_deref()
+        .and_then(|root| timings.time("custom", || custom::run(config, root)));
+    let customized = custom_values.map(|custom_values| Config {
+        custom_values,
+        ..config.clone()
+    });
+    let config = customized.as_ref().unwrap_or(config);
+    match config.backend(result.repo_type) {
+        RepoType::Jj | RepoType::JjColocated => {
+            let repo_root = result.repo_root?;
+            let repo_type = result.repo_type;
+            render_jj(
+                repo_type, repo_root, nested, config, unaliased, timings, osc_cwd,
+            )
+        }
+        #[cfg(feature = "git")]
+        RepoType::Git => {
+            let repo_root = result.repo_root?;
+            let mut info = git::collect(&repo_root, config, timings)
+                .inspect_err(|e| log::warning!("{e}"))
+                .ok()?;
+            info.nested = nested;
+            if let Some(cwd) = osc_cwd {
+                osc::emit(cwd, &Exports::git(&repo_root, &info));
+            }
+            let budgeted = config.budgeted(timings);
+            let config = budgeted.as_ref().unwrap_or(config);
+            let draw = || timings.time("render", || output::format_git(&info, config));
+            hook::wrap(config, &repo_root, || info.to_json(), draw)
+        }
+        RepoType::None => {
+            // Clears the repo user vars left by the previous prompt
+            if let Some(cwd) = osc_cwd {
+                osc::emit(cwd, &Exports::none());
+            }
+            None
+        }
+        // Catch disabled variants
+        _ => None,
+    }
+}
+
+/// Prompt for a JJ repo: collect (through the cache), report to the terminal
+/// and draw, falling back to Git's view of a colocated repo jj-lib can't read
+#[cfg_attr(not(feature = "git"), allow(unused_variables))]
+fn render_jj(
+    repo_type: RepoType,
+    repo_root: PathBuf,
+    nested: bool,
+    config: &Config,
+    unaliased: &Config,
+    timings: &Timings,
+    osc_cwd: Option<&Path>,
+) -> Option<String> {
+    // `foreign_author` needs the user's `user.email`, `default_branch` the
+    // `trunk()` alias and `--rev` the revset aliases
+    let wanted = config.jj_config || config.foreign_author || config.default_branch;
+    let user_config = (wanted || config.rev.is_some())
+        .then(|| timings.time("jj config", || jj::load_user_config(&repo_root)));
+    let collected = cache::collect_jj(&repo_root, config, user_config.as_ref(), timings);
+    let mut info = match collected {
+        Ok(info) => info,
+        Err(e) => {
+            log::warning!("{e}");
+            // A bad `--rev` is the caller's error, not an unreadable repo
+            if config.rev.is_some() {
+                return None;
+            }
+            // Likely a repo format this jj-lib doesn't support: fall back to
+            // Git's view of a colocated repo, else a placeholder
+            #[cfg(feature = "git")]
+            if repo_type == RepoType::JjColocated {
+                let result = DetectResult {
+                    repo_type: RepoType::Git,
+                    repo_root: Some(repo_root),
+                    internals: None,
+                };
+                return render(result, unaliased, timings, osc_cwd);
+            }
+            jj::JjInfo::unreadable()
+        }
+    };
+    info.nested = nested;
+    if let Some(cwd) = osc_cwd {
+        osc::emit(cwd, &Exports::jj(repo_type, &repo_root, &info));
+    }
+    // Opens .git again: jj-lib's gix handle can't stand in for a git2::Repository
+    #[cfg(feature = "git")]
+    let git_info = (repo_type == RepoType::JjColocated
+        && config.colocated == Colocated::Merged
+        && config.rev.is_none())
+    .then(|| {
+        git::collect(&repo_root, config, timings)
+            .inspect_err(|e| log::warning!("{e}"))
+            .ok()
+    })
+    .flatten();
+    let budgeted = config.budgeted(timings);
+    let config = budgeted.as_ref().unwrap_or(config);
+    let format = |config: &Config| {
+        #[cfg(feature = "git")]
+        if let Some(git_info) = &git_info {
+            return output::format_merged(&info, git_info, config);
+        }
+        output::format_jj(&info, config)
+    };
+    let draw = || match user_config.filter(|_| config.jj_config) {
+        Some(user_config) => {
+            let mut config = config.clone();
+            jj::apply_user_config(&user_config, &mut config);
+            timings.time("render", || format(&config))
+        }
+        None => timings.time("render", || format(config)),
+    };
+    hook::wrap(config, &repo_root, || info.to_json(), draw)
+}
+
+/// Working-copy state for `status`; None outside a repo, on error, or when the
+/// large-repo policy skipped the scan
+#[cfg(feature = "cli")]
+#[allow(unreachable_patterns)]
+#[cfg_attr(not(feature = "git"), allow(unused_variables))]
+fn is_dirty(
+    result: DetectResult,
+    config: &Config,
+    include_untracked: bool,
+    timings: &Timings,
+) -> Option<bool> {
+    match config.backend(result.repo_type) {
+        RepoType::Jj | RepoType::JjColocated => {
+            let info = cache::collect_jj(&result.repo_root?, config, None, timings)
+                .inspect_err(|e| log::warning!("{e}"))
+                .ok()?;
+            (!info.status_skipped).then_some(info.has_changes || info.conflict)
+        }
+        #[cfg(feature = "git")]
+        RepoType::Git => {
+            let info = git::collect(&result.repo_root?, config, timings)
+                .inspect_err(|e| log::warning!("{e}"))
+                .ok()?;
+            (!info.status_skipped).then_some(info.is_dirty(include_untracked))
+        }
+        _ => None,
+    }
+}
+
+/// Collected data for `env`; outside a repo or on error everything but the type is empty
+#[cfg(feature = "cli")]
+#[allow(unreachable_patterns)]
+fn collect_exports(result: DetectResult, config: &Config, timings: &Timings) -> Exports {
+    match (config.backend(result.repo_type), result.repo_root) {
+        (repo_type @ (RepoType::Jj | RepoType::JjColocated), Some(root)) => {
+            cache::collect_jj(&root, config, None, timings)
+                .inspect_err(|e| log::warning!("{e}"))
+                .map_or_else(
+                    |_| Exports::none(),
+                    |info| Exports::jj(repo_type, &root, &info),
+                )
+        }
+        #[cfg(feature = "git")]
+        (RepoType::Git, Some(root)) => git::collect(&root, config, timings)
+            .inspect_err(|e| log::warning!("{e}"))
+            .map_or_else(|_| Exports::none(), |info| Exports::git(&root, &info)),
+        _ => Exports::none(),
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+
+    /// `preset` passed on to `Config::new` for the given arguments
+    fn preset(args: &[&str]) -> Option<String> {
+        let cli = Cli::try_parse_from(std::iter::once("jj-starship").chain(args.iter().copied()))
+            .unwrap();
+        cli.config_args().preset
+    }
+
+    #[test]
+    fn test_minimal_flag() {
+        assert_eq!(preset(&["-m"]).as_deref(), Some("minimal"));
+        assert_eq!(
+            preset(&["--minimal", "--hide", "ahead"]).as_deref(),
+            Some("minimal")
+        );
+        assert_eq!(preset(&[]), None);
+        assert!(Cli::try_parse_from(["jj-starship", "-m", "--preset", "nerd"]).is_err());
+    }
+
+    #[test]
+    fn test_full_flag() {
+        assert_eq!(preset(&["--full"]).as_deref(), Some("full"));
+        assert!(Cli::try_parse_from(["jj-starship", "--full", "-m"]).is_err());
+        assert!(Cli::try_parse_from(["jj-starship", "--full", "--preset", "nerd"]).is_err());
+    }
+}
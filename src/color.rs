@@ -1,8 +1,417 @@
 //! ANSI color codes for terminal output
 //! Uses standard ANSI colors (0-15) so they adapt to terminal theme
 
+use crate::term::ColorTier;
+use std::borrow::Cow;
+
 pub const RESET: &str = "\x1b[0m";
 pub const PURPLE: &str = "\x1b[35m"; // Color 5: Magenta
 pub const GREEN: &str = "\x1b[32m"; // Color 2: Green
 pub const RED: &str = "\x1b[31m"; // Color 1: Red
 pub const BLUE: &str = "\x1b[34m"; // Color 4: Blue
+pub const YELLOW: &str = "\x1b[33m"; // Color 3: Yellow
+
+/// Base color names in ANSI order (0-7)
+const NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// ANSI index (0-7) of a base color name, with `purple` for `magenta`
+fn base_index(name: &str) -> Option<u8> {
+    let name = if name == "purple" { "magenta" } else { name };
+    let idx = NAMES.iter().position(|n| *n == name)?;
+    u8::try_from(idx).ok()
+}
+
+/// xterm's default RGB values for the 16 ANSI colors
+#[cfg(feature = "color-tables")]
+const ANSI_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Channel levels of the 256-color palette's 6x6x6 cube (indices 16-231)
+#[cfg(feature = "color-tables")]
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// A single color in jj's color syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    /// Terminal default
+    Default,
+    /// Base (0-7) or bright (8-15) ANSI color
+    Ansi(u8),
+    /// 256-color palette index
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Accepts `red`, `bright red`, `ansi-color-N`, `#rrggbb`, `default`
+    /// (`purple` is an alias for `magenta`)
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_ascii_lowercase();
+
+        if name == "default" {
+            return Some(Self::Default);
+        }
+        if let Some(base) = name.strip_prefix("bright ") {
+            return Some(Self::Ansi(8 + base_index(base.trim())?));
+        }
+        if let Some(idx) = base_index(&name) {
+            return Some(Self::Ansi(idx));
+        }
+        if let Some(n) = name.strip_prefix("ansi-color-") {
+            return Some(Self::Indexed(n.parse().ok()?));
+        }
+
+        let hex = name.strip_prefix('#')?;
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        Some(Self::Rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// Closest color the terminal tier can show; without the `color-tables`
+    /// feature, RGB colors and palette indices above 15 are left as they are
+    pub fn downgrade(self, tier: ColorTier) -> Self {
+        match (self, tier) {
+            #[cfg(feature = "color-tables")]
+            (Self::Rgb(r, g, b), ColorTier::Ansi256) => Self::Indexed(nearest_indexed((r, g, b))),
+            #[cfg(feature = "color-tables")]
+            (Self::Rgb(r, g, b), ColorTier::Ansi16) => Self::Ansi(nearest_ansi((r, g, b))),
+            (Self::Indexed(n), ColorTier::Ansi16) if n < 16 => Self::Ansi(n),
+            #[cfg(feature = "color-tables")]
+            (Self::Indexed(n), ColorTier::Ansi16) => Self::Ansi(nearest_ansi(indexed_rgb(n))),
+            _ => self,
+        }
+    }
+
+    /// RGB shown for this color, using xterm's palette; `None` for the terminal default
+    #[cfg(feature = "cli")]
+    pub fn rgb(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Self::Default => None,
+            Self::Ansi(n) => ANSI_RGB.get(usize::from(n)).copied(),
+            Self::Indexed(n) if n < 16 => ANSI_RGB.get(usize::from(n)).copied(),
+            Self::Indexed(n) => Some(indexed_rgb(n)),
+            Self::Rgb(r, g, b) => Some((r, g, b)),
+        }
+    }
+
+    /// SGR parameters selecting this color as foreground or background
+    fn sgr(self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+        match self {
+            Self::Default => format!("{}", base + 9),
+            Self::Ansi(n) if n < 8 => format!("{}", base + n),
+            Self::Ansi(n) => format!("{}", base + 60 + (n - 8)),
+            Self::Indexed(n) => format!("{};5;{n}", base + 8),
+            Self::Rgb(r, g, b) => format!("{};2;{r};{g};{b}", base + 8),
+        }
+    }
+}
+
+#[cfg(feature = "color-tables")]
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| u32::from(x.abs_diff(y)).pow(2);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+/// Index of the closest entry in `colors`
+#[cfg(feature = "color-tables")]
+fn nearest(rgb: (u8, u8, u8), colors: impl Iterator<Item = (u8, u8, u8)>) -> u8 {
+    let (index, _) = colors
+        .enumerate()
+        .min_by_key(|(_, c)| distance(rgb, *c))
+        .unwrap_or_default();
+    u8::try_from(index).unwrap_or_default()
+}
+
+#[cfg(feature = "color-tables")]
+fn nearest_ansi(rgb: (u8, u8, u8)) -> u8 {
+    nearest(rgb, ANSI_RGB.into_iter())
+}
+
+/// Closest 256-palette index, from the color cube or the grayscale ramp
+#[cfg(feature = "color-tables")]
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    16 + nearest(rgb, (16..=255).map(indexed_rgb))
+}
+
+/// RGB of a 256-palette index above 15
+#[cfg(feature = "color-tables")]
+fn indexed_rgb(n: u8) -> (u8, u8, u8) {
+    if n >= 232 {
+        let level = 8 + 10 * (n - 232);
+        return (level, level, level);
+    }
+    let i = usize::from(n.saturating_sub(16));
+    (CUBE[i / 36], CUBE[i / 6 % 6], CUBE[i % 6])
+}
+
+/// Foreground/background colors plus text attributes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    /// Parse a style like `bold purple on black` or `underline #ff8000`
+    /// Colors use `Color::parse` syntax; `on <color>` sets the background
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut style = Self::default();
+        let mut words = spec.split_whitespace().peekable();
+        while let Some(word) = words.next() {
+            match word.to_ascii_lowercase().as_str() {
+                "bold" => style.bold = true,
+                "dim" => style.dim = true,
+                "italic" => style.italic = true,
+                "underline" => style.underline = true,
+                "on" => {
+                    let color = next_color(words.next()?, &mut words)?;
+                    style.bg = Some(color);
+                }
+                _ if style.fg.is_none() => style.fg = Some(next_color(word, &mut words)?),
+                _ => return None,
+            }
+        }
+        (style != Self::default()).then_some(style)
+    }
+
+    /// Colors downgraded to what the terminal tier can show
+    #[must_use]
+    pub fn downgrade(self, tier: ColorTier) -> Self {
+        Self {
+            fg: self.fg.map(|c| c.downgrade(tier)),
+            bg: self.bg.map(|c| c.downgrade(tier)),
+            ..self
+        }
+    }
+
+    /// SGR escape sequence enabling this style
+    pub fn escape(&self) -> Cow<'static, str> {
+        let mut params: Vec<String> = [
+            (self.bold, "1"),
+            (self.dim, "2"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, code)| (*code).to_string())
+        .collect();
+        params.extend(self.fg.map(|c| c.sgr(false)));
+        params.extend(self.bg.map(|c| c.sgr(true)));
+        if params.is_empty() {
+            Cow::Borrowed("")
+        } else {
+            Cow::Owned(format!("\x1b[{}m", params.join(";")))
+        }
+    }
+
+    /// Style after applying an SGR sequence's parameters (`1;35`); `0` or none resets
+    #[cfg(feature = "cli")]
+    #[must_use]
+    pub fn apply_sgr(mut self, params: &str) -> Self {
+        let mut codes = params.split(';').map(|p| p.parse::<u8>().unwrap_or(0));
+        while let Some(code) = codes.next() {
+            match code {
+                0 => self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => {
+                    self.bold = false;
+                    self.dim = false;
+                }
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.fg = Some(Color::Ansi(code - 30)),
+                90..=97 => self.fg = Some(Color::Ansi(code - 82)),
+                39 => self.fg = None,
+                40..=47 => self.bg = Some(Color::Ansi(code - 40)),
+                100..=107 => self.bg = Some(Color::Ansi(code - 92)),
+                49 => self.bg = None,
+                38 | 48 => {
+                    let color = match codes.next() {
+                        Some(5) => codes.next().map(Color::Indexed),
+                        Some(2) => match (codes.next(), codes.next(), codes.next()) {
+                            (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r, g, b)),
+                            _ => None,
+                        },
+                        _ => None,
+                    };
+                    if code == 38 {
+                        self.fg = color;
+                    } else {
+                        self.bg = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// Inline CSS declarations for this style (`color:#cd00cd;font-weight:bold`)
+    #[cfg(feature = "cli")]
+    pub fn css(&self) -> String {
+        let hex = |c: Color| c.rgb().map(|(r, g, b)| format!("#{r:02x}{g:02x}{b:02x}"));
+        let mut decls: Vec<String> = Vec::new();
+        decls.extend(self.fg.and_then(hex).map(|c| format!("color:{c}")));
+        decls.extend(
+            self.bg
+                .and_then(hex)
+                .map(|c| format!("background-color:{c}")),
+        );
+        for (on, decl) in [
+            (self.bold, "font-weight:bold"),
+            (self.dim, "opacity:0.5"),
+            (self.italic, "font-style:italic"),
+            (self.underline, "text-decoration:underline"),
+        ] {
+            if on {
+                decls.push(decl.to_string());
+            }
+        }
+        decls.join(";")
+    }
+}
+
+/// A color starting at `word`, taking the following word for `bright <name>`
+fn next_color<'a>(
+    word: &'a str,
+    words: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>,
+) -> Option<Color> {
+    if word.eq_ignore_ascii_case("bright") {
+        return Color::parse(&format!("bright {}", words.next()?));
+    }
+    Color::parse(word)
+}
+
+/// Resolve a color name to its escape sequence for the terminal tier
+/// Accepts jj's color syntax: `red`, `bright red`, `ansi-color-N`, `#rrggbb`, `default`
+pub fn parse(name: &str, tier: ColorTier) -> Option<Cow<'static, str>> {
+    Some(
+        Style {
+            fg: Some(Color::parse(name)?),
+            ..Style::default()
+        }
+        .downgrade(tier)
+        .escape(),
+    )
+}
+
+/// Resolve a style spec (colors plus `bold`/`dim`/`italic`/`underline`, `on <bg>`)
+/// to its escape sequence for the terminal tier
+pub fn parse_style(spec: &str, tier: ColorTier) -> Option<Cow<'static, str>> {
+    Some(Style::parse(spec)?.downgrade(tier).escape())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ColorTier::TrueColor;
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(parse("magenta", TrueColor).as_deref(), Some(PURPLE));
+        assert_eq!(parse("Bright Red", TrueColor).as_deref(), Some("\x1b[91m"));
+        assert_eq!(
+            parse("ansi-color-208", TrueColor).as_deref(),
+            Some("\x1b[38;5;208m")
+        );
+        assert_eq!(
+            parse("#ff8000", TrueColor).as_deref(),
+            Some("\x1b[38;2;255;128;0m")
+        );
+        assert_eq!(parse("octarine", TrueColor), None);
+        assert_eq!(parse("purple", TrueColor).as_deref(), Some(PURPLE));
+        assert_eq!(
+            parse("bright purple", TrueColor).as_deref(),
+            Some("\x1b[95m")
+        );
+        assert_eq!(parse("bright_purple_x", TrueColor), None);
+        assert_eq!(parse("purplish", TrueColor), None);
+        assert_eq!(parse("#ff80", TrueColor), None);
+    }
+
+    #[test]
+    fn test_parse_styles() {
+        assert_eq!(parse_style("red", TrueColor).as_deref(), Some(RED));
+        assert_eq!(
+            parse_style("bold purple on black", TrueColor).as_deref(),
+            Some("\x1b[1;35;40m")
+        );
+        assert_eq!(
+            parse_style("underline italic bright cyan on bright black", TrueColor).as_deref(),
+            Some("\x1b[3;4;96;100m")
+        );
+        assert_eq!(parse_style("dim", TrueColor).as_deref(), Some("\x1b[2m"));
+        assert_eq!(
+            parse_style("on #102030", TrueColor).as_deref(),
+            Some("\x1b[48;2;16;32;48m")
+        );
+        assert_eq!(parse_style("red blue", TrueColor), None);
+        assert_eq!(parse_style("bold on", TrueColor), None);
+        assert_eq!(parse_style("", TrueColor), None);
+    }
+
+    #[cfg(feature = "color-tables")]
+    #[test]
+    fn test_downgrade() {
+        use ColorTier::{Ansi16, Ansi256};
+        assert_eq!(parse("#ff8000", Ansi256).as_deref(), Some("\x1b[38;5;208m"));
+        assert_eq!(parse("#808080", Ansi256).as_deref(), Some("\x1b[38;5;244m"));
+        assert_eq!(parse("#ff0000", Ansi16).as_deref(), Some("\x1b[91m"));
+        assert_eq!(parse("ansi-color-1", Ansi16).as_deref(), Some(RED));
+        assert_eq!(parse("ansi-color-21", Ansi16).as_deref(), Some(BLUE));
+        assert_eq!(parse("red", Ansi16).as_deref(), Some(RED));
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn test_apply_sgr() {
+        for spec in [
+            "bold purple on #102030",
+            "underline ansi-color-208",
+            "bright cyan",
+        ] {
+            let style = Style::parse(spec).unwrap();
+            let escape = style.escape();
+            let params = escape.trim_start_matches("\x1b[").trim_end_matches('m');
+            assert_eq!(Style::default().apply_sgr(params), style, "{spec}");
+        }
+        assert_eq!(
+            Style::parse("bold red").unwrap().apply_sgr("0"),
+            Style::default()
+        );
+        assert_eq!(
+            Style::parse("bold red on black").unwrap().css(),
+            "color:#cd0000;background-color:#000000;font-weight:bold"
+        );
+    }
+}
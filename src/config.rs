@@ -1,12 +1,57 @@
 //! Configuration for jj-starship
 
+use crate::color::{self, BLUE, GREEN, PURPLE, RED};
+use crate::detect::{self, RepoType};
+use crate::glob;
+use crate::json;
+use crate::log;
+use crate::netfs;
+use crate::symbols::NerdFont;
+use crate::term::{ColorTier, Term};
+use crate::timing::Timings;
+use jj_lib::config::{ConfigLayer, ConfigNamePathBuf, ConfigSource, StackedConfig};
 use std::borrow::Cow;
+use std::cell::OnceCell;
 use std::env;
+use std::fmt;
+#[cfg(feature = "cli")]
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
+/// Default word before the symbol
+pub const DEFAULT_PREFIX_TEXT: &str = "on ";
 /// Default symbol for JJ repos
-pub const DEFAULT_JJ_SYMBOL: &str = "󱗆 ";
+pub const DEFAULT_JJ_SYMBOL: &str = NerdFont::V3.symbols().jj;
 /// Default symbol for Git repos
-pub const DEFAULT_GIT_SYMBOL: &str = " ";
+pub const DEFAULT_GIT_SYMBOL: &str = NerdFont::V3.symbols().git;
+/// Default symbol for a Git branch both ahead of and behind its upstream
+pub const DEFAULT_DIVERGED_SYMBOL: &str = "⇕";
+/// Default marker after a prompt drawn from an outdated cache entry
+pub const DEFAULT_STALE_MARKER: &str = "~";
+/// Default cap on untracked files counted
+pub const DEFAULT_MAX_UNTRACKED: usize = 1000;
+/// Default share of `$COLUMNS` the prompt may take before segments are dropped
+pub const DEFAULT_MAX_WIDTH_PERCENT: usize = 50;
+/// Default order segments are dropped in on narrow terminals; the name always stays
+pub const DEFAULT_DROP_ORDER: [Segment; 3] = [Segment::Id, Segment::Prefix, Segment::Status];
+/// Default description prefixes that mark a draft commit
+pub const DEFAULT_WIP_PREFIXES: [&str; 3] = ["wip:", "private:", "[draft]"];
+/// Default conventional-commit type icons for the `type` segment
+pub const DEFAULT_COMMIT_TYPES: [(&str, &str); 11] = [
+    ("feat", "✨"),
+    ("fix", "🐛"),
+    ("docs", "📝"),
+    ("style", "💄"),
+    ("refactor", "♻️"),
+    ("perf", "⚡"),
+    ("test", "✅"),
+    ("build", "📦"),
+    ("ci", "👷"),
+    ("chore", "🔧"),
+    ("revert", "⏪"),
+];
 
 /// Display options for a repo type
 #[derive(Debug, Clone, Copy, Default)]
@@ -31,23 +76,515 @@ impl DisplayConfig {
     }
 }
 
+/// Status indicators that can be colored individually
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indicator {
+    /// `≋` large-repo skip
+    Skipped,
+    /// `!` (jj) / `=` (git)
+    Conflict,
+    /// `↯` conflicted ancestor down to the nearest bookmark (jj)
+    ConflictBelow,
+    /// `⇔`
+    Divergent,
+    /// `?` empty description (jj)
+    Undescribed,
+    /// `✎` description marks a draft (`wip_prefixes`)
+    Wip,
+    /// `≠` commit authored by someone other than the configured user
+    ForeignAuthor,
+    /// `⚑` on the repo's default branch (`origin/HEAD`, jj's `trunk()`)
+    DefaultBranch,
+    /// `⊂` sparse workspace (jj)
+    Sparse,
+    /// `⚠` new files too large to snapshot (jj)
+    LargeFile,
+    /// `⚠` paths the status scan couldn't read (git)
+    Unreadable,
+    /// `⊠` files with a Git LFS lock held by the user (git)
+    LfsLocked,
+    /// `⊘` modified lockable files without the user's Git LFS lock (git)
+    LfsUnlocked,
+    /// `+`
+    Staged,
+    /// `!` (git)
+    Modified,
+    /// `?` (git)
+    Untracked,
+    /// `✘`
+    Deleted,
+    /// `»` (git)
+    Renamed,
+    /// `~` (git)
+    TypeChanged,
+    /// `⇡`
+    Ahead,
+    /// `⇣`
+    Behind,
+}
+
+impl Indicator {
+    pub const ALL: [Self; 21] = [
+        Self::Skipped,
+        Self::Conflict,
+        Self::ConflictBelow,
+        Self::Divergent,
+        Self::Undescribed,
+        Self::Wip,
+        Self::ForeignAuthor,
+        Self::DefaultBranch,
+        Self::Sparse,
+        Self::LargeFile,
+        Self::Unreadable,
+        Self::LfsLocked,
+        Self::LfsUnlocked,
+        Self::Staged,
+        Self::Modified,
+        Self::Untracked,
+        Self::Deleted,
+        Self::Renamed,
+        Self::TypeChanged,
+        Self::Ahead,
+        Self::Behind,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|i| i.name() == name)
+    }
+
+    /// Key under `[jj.status_styles]` / `[git.status_styles]`
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Skipped => "skipped",
+            Self::Conflict => "conflict",
+            Self::ConflictBelow => "conflict_below",
+            Self::Divergent => "divergent",
+            Self::Undescribed => "undescribed",
+            Self::Wip => "wip",
+            Self::ForeignAuthor => "foreign_author",
+            Self::DefaultBranch => "default_branch",
+            Self::Sparse => "sparse",
+            Self::LargeFile => "large_file",
+            Self::Unreadable => "unreadable",
+            Self::LfsLocked => "lfs_locked",
+            Self::LfsUnlocked => "lfs_unlocked",
+            Self::Staged => "staged",
+            Self::Modified => "modified",
+            Self::Untracked => "untracked",
+            Self::Deleted => "deleted",
+            Self::Renamed => "renamed",
+            Self::TypeChanged => "typechanged",
+            Self::Ahead => "ahead",
+            Self::Behind => "behind",
+        }
+    }
+}
+
+/// Minimum counts before count-based status indicators show (default 1)
+#[derive(Debug, Clone, Default)]
+pub struct Thresholds(pub Vec<(Indicator, usize)>);
+
+impl Thresholds {
+    /// Whether an indicator with this count should show
+    pub fn shows(&self, indicator: Indicator, count: usize) -> bool {
+        let min = self
+            .0
+            .iter()
+            .find(|(i, _)| *i == indicator)
+            .map_or(1, |(_, min)| *min);
+        count > 0 && count >= min
+    }
+}
+
+/// Parts of the prompt line, rendered in the order `segments` lists them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Segment {
+    /// `on {symbol}`
+    Prefix,
+    /// Bookmark/branch name
+    Name,
+    /// `({id})`
+    Id,
+    /// In-progress Git operation (`REBASING 3/7`)
+    State,
+    /// `[{status}]`
+    Status,
+    /// Conventional-commit type icon (not shown by default)
+    Type,
+    /// Time since the last jj operation (`op 5m`, not shown by default)
+    OpAge,
+    /// Git commits not yet on the default branch (`main+3`, not shown by default)
+    Base,
+    /// Last jj operation or Git reflog action and its age (`rebase 1h`, not shown by default)
+    Action,
+    /// First line of the commit description (not shown by default)
+    Description,
+    /// Non-default jj workspace or linked Git worktree (not shown by default)
+    Workspace,
+    /// Short ID of the current jj operation (`op:3b9f0e1a`, not shown by default)
+    OpId,
+}
+
+impl Segment {
+    pub const DEFAULT: [Self; 5] = [
+        Self::Prefix,
+        Self::Name,
+        Self::Id,
+        Self::State,
+        Self::Status,
+    ];
+    pub const ALL: [Self; 12] = [
+        Self::Prefix,
+        Self::Name,
+        Self::Id,
+        Self::State,
+        Self::Status,
+        Self::Type,
+        Self::OpAge,
+        Self::Base,
+        Self::Action,
+        Self::Description,
+        Self::Workspace,
+        Self::OpId,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.name() == name)
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Prefix => "prefix",
+            Self::Name => "name",
+            Self::Id => "id",
+            Self::State => "state",
+            Self::Status => "status",
+            Self::Type => "type",
+            Self::OpAge => "op",
+            Self::Base => "base",
+            Self::Action => "action",
+            Self::Description => "description",
+            Self::Workspace => "workspace",
+            Self::OpId => "op_id",
+        }
+    }
+}
+
+/// Prompt details `priority` ranks, dropped least important first when
+/// collection runs short of `time_budget_ms`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Detail {
+    /// Bookmark/branch name (never dropped)
+    Name,
+    /// JJ's conflict check (Git's conflicts come from the status scan)
+    Conflict,
+    /// `({id})`, and the ID prefixes of divergent commits
+    Id,
+    /// File status scan (`≋` when dropped)
+    Status,
+    /// Ahead/behind counts against the upstream
+    AheadBehind,
+}
+
+impl Detail {
+    /// Default priority: ahead/behind goes first, then the status, then the ID
+    pub const ALL: [Self; 5] = [
+        Self::Name,
+        Self::Conflict,
+        Self::Id,
+        Self::Status,
+        Self::AheadBehind,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.name() == name)
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Conflict => "conflict",
+            Self::Id => "id",
+            Self::Status => "status",
+            Self::AheadBehind => "ahead_behind",
+        }
+    }
+}
+
+/// Backend for colocated repos (`.jj` and `.git` side by side)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colocated {
+    #[default]
+    Jj,
+    /// Branch, ahead/behind and file status from Git
+    Git,
+    /// JJ prompt with Git's file status added to the status block
+    Merged,
+}
+
+impl Colocated {
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    pub const NAMES: &[&str] = &["jj", "git", "merged"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "jj" => Some(Self::Jj),
+            "git" => Some(Self::Git),
+            "merged" => Some(Self::Merged),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Jj => "jj",
+            Self::Git => "git",
+            Self::Merged => "merged",
+        }
+    }
+}
+
+/// Colors for each part of the prompt (ANSI escape sequences)
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub symbol: Cow<'static, str>,
+    pub name: Cow<'static, str>,
+    pub id: Cow<'static, str>,
+    /// Brackets and any indicator without its own color
+    pub status: Cow<'static, str>,
+    /// Per-indicator overrides
+    pub indicators: Vec<(Indicator, Cow<'static, str>)>,
+}
+
+impl Palette {
+    /// Color for a status indicator
+    pub fn indicator(&self, indicator: Indicator) -> &str {
+        self.indicators
+            .iter()
+            .find(|(i, _)| *i == indicator)
+            .map_or(&self.status, |(_, color)| color)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            symbol: Cow::Borrowed(BLUE),
+            name: Cow::Borrowed(PURPLE),
+            id: Cow::Borrowed(GREEN),
+            status: Cow::Borrowed(RED),
+            indicators: Vec::new(),
+        }
+    }
+}
+
+/// Style override for branch/bookmark names matching a pattern
+#[derive(Debug, Clone)]
+pub struct BranchRule {
+    /// `|`-separated globs matched against the full name
+    pub pattern: String,
+    /// Name color
+    pub color: Option<Cow<'static, str>>,
+    /// Replaces the repo symbol
+    pub symbol: Option<String>,
+}
+
+impl BranchRule {
+    pub fn matches(&self, name: &str) -> bool {
+        self.pattern
+            .split('|')
+            .any(|alt| glob::matches(alt.trim(), name))
+    }
+}
+
+/// Display alias and symbol for repos matching a root path or remote URL
+#[derive(Debug, Clone)]
+pub struct RepoAlias {
+    /// `|`-separated globs matched against the repo root (a leading `~` is
+    /// `$HOME`) or the `origin` remote URL
+    pub pattern: String,
+    /// Shown after the symbol
+    pub alias: Option<String>,
+    /// Replaces the repo symbol
+    pub symbol: Option<String>,
+}
+
+impl RepoAlias {
+    pub fn matches(&self, repo_root: &str, url: impl Fn() -> Option<String>) -> bool {
+        let home = home_dir();
+        self.pattern.split('|').map(str::trim).any(|alt| {
+            let alt = match (alt.strip_prefix('~'), &home) {
+                (Some(rest), Some(home)) => Cow::Owned(format!("{}{rest}", home.display())),
+                _ => Cow::Borrowed(alt),
+            };
+            glob::matches(&alt, repo_root) || url().is_some_and(|url| glob::matches(&alt, &url))
+        })
+    }
+}
+
 /// Configuration options
 #[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools, clippy::struct_field_names)]
 pub struct Config {
     /// Max length for branch/bookmark name (0 = unlimited)
     pub truncate_name: usize,
     /// Length of `change_id/commit` hash to display
     pub id_length: usize,
+    /// Stop counting untracked files past this many, shown as `?N+` (0 = unlimited)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub max_untracked: usize,
+    /// Skip file status when the git index has more entries than this (0 = never)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub large_repo_threshold: usize,
+    /// Repos under these paths always skip file status
+    pub slow_paths: Vec<PathBuf>,
+    /// Git repos under these paths are read even when owned by another user
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub trusted_paths: Vec<PathBuf>,
+    /// Scan file status on network filesystems too (otherwise treated as slow paths)
+    pub network_status: bool,
+    /// Detect from `$PWD` rather than the symlink-resolved working directory
+    pub logical_pwd: bool,
+    /// Backend for colocated repos
+    pub colocated: Colocated,
+    /// Globs of repo paths where jj-starship is disabled entirely
+    pub disable_in: Vec<String>,
+    /// Parent directories repo detection walks above the cwd (0 = unlimited)
+    pub detect_max_depth: usize,
+    /// Filesystem checks repo detection may make per prompt (0 = unlimited)
+    pub detect_fs_budget: usize,
+    /// Prompt segments in display order; unlisted ones are hidden
+    pub segments: Vec<Segment>,
+    /// Widest the prompt may get in columns before segments are dropped (0 = unlimited)
+    pub max_width: usize,
+    /// Segments dropped, in this order, while the prompt is wider than `max_width`
+    pub drop_order: Vec<Segment>,
+    /// Milliseconds a prompt may spend before details are dropped (0 = unlimited)
+    pub time_budget_ms: usize,
+    /// Details by importance; the last go first as `time_budget_ms` runs out
+    pub priority: Vec<Detail>,
+    /// Description prefixes that mark a draft commit (`✎`), matched case-insensitively
+    pub wip_prefixes: Vec<String>,
+    /// Conventional-commit type to icon for the `type` segment; empty icons hide a type
+    pub commit_types: Vec<(String, String)>,
+    /// Colors the terminal can show; richer colors are downgraded
+    pub colors: ColorTier,
+    /// Text before the symbol (`on `)
+    pub prefix_text: String,
+    /// Text after the prompt
+    pub suffix: String,
+    /// Status indicators left out for both repo types (`hide`)
+    pub hide: Vec<Indicator>,
+    /// Appended to a prompt drawn from an outdated cache entry (`~`)
+    pub stale_marker: String,
     /// Symbol prefix for JJ repos
     pub jj_symbol: Cow<'static, str>,
     /// Symbol prefix for Git repos
     #[cfg_attr(not(feature = "git"), allow(dead_code))]
     pub git_symbol: Cow<'static, str>,
+    /// Template laying out JJ prompts, e.g. `$prefix$name $status` (empty = segment order)
+    pub jj_format: String,
+    /// Template laying out Git prompts (empty = segment order)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_format: String,
+    /// `[custom]` commands by name, shown by format templates as `${custom.<name>}`
+    pub custom: Vec<(String, String)>,
+    /// How long the `[custom]` commands may run, in milliseconds
+    pub custom_timeout_ms: usize,
+    /// Output of the `[custom]` commands, filled in for the repo being rendered
+    pub custom_values: Vec<(String, String)>,
+    /// Command that may veto the prompt before it is rendered (`hooks.pre`)
+    pub pre_hook: String,
+    /// Command that may veto or replace the rendered prompt (`hooks.post`)
+    pub post_hook: String,
+    /// How long each hook may run, in milliseconds
+    pub hook_timeout_ms: usize,
     /// JJ display options
     pub jj_display: DisplayConfig,
     /// Git display options
     #[cfg_attr(not(feature = "git"), allow(dead_code))]
     pub git_display: DisplayConfig,
+    /// JJ colors
+    pub jj_palette: Palette,
+    /// Git colors
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_palette: Palette,
+    /// Per-branch styles, first match wins
+    pub branch_rules: Vec<BranchRule>,
+    /// Per-repo aliases and symbols, first match wins
+    pub repo_aliases: Vec<RepoAlias>,
+    /// JJ ahead/behind thresholds
+    pub jj_thresholds: Thresholds,
+    /// Git file-count and ahead/behind thresholds
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_thresholds: Thresholds,
+    /// Append the tracked remote (`→origin`) and per-remote ahead/behind for JJ bookmarks
+    pub jj_tracking: bool,
+    /// Flag new files over jj's `snapshot.max-new-file-size` (walks the working copy)
+    pub jj_large_files: bool,
+    /// Mark commits whose author email isn't the configured user's
+    pub foreign_author: bool,
+    /// Mark the repo's default branch, to guard against committing to it
+    pub default_branch: bool,
+    /// Mark repos nested inside another repo (`▣`)
+    pub nested: bool,
+    /// Collect JJ info fresh on every run, never touching the on-disk cache
+    pub no_cache: bool,
+    /// Read the user's jj config for colors
+    pub jj_config: bool,
+    /// Color the name by repo state: green clean, yellow dirty, red conflicted
+    pub state_colors: bool,
+    /// Append counts to status symbols (`+2!5?1`, `⇔2`)
+    pub counts: bool,
+    /// Render status as words (`2 ahead`) instead of glyphs
+    pub plain_words: bool,
+    /// Put the status on a second line below repo, name and ID
+    pub block: bool,
+    /// Report cwd and repo data to the terminal with OSC 7 / OSC 1337 user vars
+    pub osc: bool,
+    /// Backslash-escape starship's format characters (`[`, `]`, `$`, `\`) in the prompt
+    pub escape: bool,
+    /// Keep whitespace at the end of the prompt; off trims it
+    pub trailing_space: bool,
+    /// End the prompt with a newline
+    pub newline: bool,
+    /// Show a single `±N` total instead of per-category Git file status
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_dirty_count: bool,
+    /// Collapse Git ahead and behind into one `git_diverged_symbol` (`⇕2/1`) when both show
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_diverged: bool,
+    /// Symbol for a diverged branch (`⇕`)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_diverged_symbol: String,
+    /// Count each file in untracked directories rather than the directory once
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_recurse_untracked: bool,
+    /// Count submodules with changes or new commits as modified
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_submodules: bool,
+    /// Only check whether anything changed (`*`), skipping the per-category Git file counts
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_simple_dirty: bool,
+    /// Show Git LFS locks held (`⊠`) and lockable files modified without one (`⊘`)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_lfs_locks: bool,
+    /// Remotes to show Git ahead/behind against (`origin⇡2 upstream⇣14`) instead of the upstream
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_compare_remotes: Vec<String>,
+    /// Skip status scans and render name/id from refs only (`prompt --instant`)
+    pub refs_only: bool,
+    /// JJ revision to render instead of the working copy (`prompt --rev`)
+    pub rev: Option<String>,
+    /// Git ref to render instead of HEAD (`prompt --ref`)
+    #[cfg_attr(not(feature = "git"), allow(dead_code))]
+    pub git_ref: Option<String>,
+    /// Config file the options were read from, if any
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    pub config_file: Option<PathBuf>,
+    /// Where each option's value came from
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    pub origins: Vec<Origin>,
 }
 
 impl Default for Config {
@@ -55,14 +592,85 @@ impl Default for Config {
         Self {
             truncate_name: 0, // unlimited
             id_length: 8,
+            max_untracked: DEFAULT_MAX_UNTRACKED,
+            large_repo_threshold: 0,
+            slow_paths: Vec::new(),
+            trusted_paths: Vec::new(),
+            network_status: false,
+            logical_pwd: false,
+            colocated: Colocated::default(),
+            disable_in: Vec::new(),
+            detect_max_depth: 0,
+            detect_fs_budget: 0,
+            segments: Segment::DEFAULT.to_vec(),
+            max_width: 0,
+            drop_order: DEFAULT_DROP_ORDER.to_vec(),
+            time_budget_ms: 0,
+            priority: Detail::ALL.to_vec(),
+            wip_prefixes: DEFAULT_WIP_PREFIXES.map(String::from).to_vec(),
+            commit_types: default_commit_types(),
+            colors: ColorTier::TrueColor,
+            prefix_text: DEFAULT_PREFIX_TEXT.to_string(),
+            suffix: String::new(),
+            hide: Vec::new(),
+            stale_marker: DEFAULT_STALE_MARKER.to_string(),
             jj_symbol: Cow::Borrowed(DEFAULT_JJ_SYMBOL),
             git_symbol: Cow::Borrowed(DEFAULT_GIT_SYMBOL),
+            jj_format: String::new(),
+            git_format: String::new(),
+            custom: Vec::new(),
+            custom_timeout_ms: 500,
+            custom_values: Vec::new(),
+            pre_hook: String::new(),
+            post_hook: String::new(),
+            hook_timeout_ms: 500,
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            jj_palette: Palette::default(),
+            git_palette: Palette::default(),
+            branch_rules: Vec::new(),
+            repo_aliases: Vec::new(),
+            jj_thresholds: Thresholds::default(),
+            git_thresholds: Thresholds::default(),
+            jj_tracking: false,
+            jj_large_files: false,
+            foreign_author: false,
+            default_branch: false,
+            nested: false,
+            no_cache: false,
+            jj_config: false,
+            state_colors: false,
+            counts: false,
+            plain_words: false,
+            block: false,
+            osc: false,
+            escape: false,
+            trailing_space: true,
+            newline: false,
+            git_dirty_count: false,
+            git_diverged: false,
+            git_diverged_symbol: DEFAULT_DIVERGED_SYMBOL.to_string(),
+            git_recurse_untracked: false,
+            git_submodules: false,
+            git_simple_dirty: false,
+            git_lfs_locks: false,
+            git_compare_remotes: Vec::new(),
+            refs_only: false,
+            rev: None,
+            git_ref: None,
+            config_file: None,
+            origins: Vec::new(),
         }
     }
 }
 
+fn default_commit_types() -> Vec<(String, String)> {
+    DEFAULT_COMMIT_TYPES
+        .iter()
+        .map(|&(name, icon)| (name.to_string(), icon.to_string()))
+        .collect()
+}
+
 /// CLI display flags for a repo type
 #[derive(Debug, Clone, Copy, Default)]
 #[allow(clippy::struct_excessive_bools)]
@@ -74,61 +682,479 @@ pub struct DisplayFlags {
     pub no_color: bool,
 }
 
-impl DisplayFlags {
-    fn into_config(self, env_prefix: &str) -> DisplayConfig {
-        DisplayConfig {
-            show_prefix: !self.no_prefix && env::var(format!("{env_prefix}_PREFIX")).is_err(),
-            show_name: !self.no_name && env::var(format!("{env_prefix}_NAME")).is_err(),
-            show_id: !self.no_id && env::var(format!("{env_prefix}_ID")).is_err(),
-            show_status: !self.no_status && env::var(format!("{env_prefix}_STATUS")).is_err(),
-            show_color: !self.no_color && env::var(format!("{env_prefix}_COLOR")).is_err(),
-        }
-    }
+/// CLI style overrides for prompt parts, applied to both repo types
+#[derive(Debug, Clone, Default)]
+pub struct ColorFlags {
+    pub symbol: Option<String>,
+    pub name: Option<String>,
+    pub id: Option<String>,
+    pub status: Option<String>,
+}
+
+/// Raw option values collected from the CLI
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ConfigArgs {
+    pub truncate_name: Option<usize>,
+    pub id_length: Option<usize>,
+    pub max_untracked: Option<usize>,
+    pub large_repo_threshold: Option<usize>,
+    pub slow_paths: Vec<PathBuf>,
+    pub network_status: bool,
+    pub logical_pwd: bool,
+    pub colocated: Option<String>,
+    pub columns: Option<usize>,
+    pub time_budget_ms: Option<usize>,
+    pub jj_symbol: Option<String>,
+    pub git_symbol: Option<String>,
+    pub no_symbol: bool,
+    pub nerd_font: Option<String>,
+    pub colors: Option<String>,
+    pub prefix_text: Option<String>,
+    pub suffix: Option<String>,
+    pub stale_marker: Option<String>,
+    pub jj_tracking: bool,
+    pub jj_large_files: bool,
+    pub foreign_author: bool,
+    pub default_branch: bool,
+    pub nested: bool,
+    pub no_cache: bool,
+    pub jj_config: bool,
+    pub state_colors: bool,
+    pub counts: bool,
+    pub plain_words: bool,
+    pub block: bool,
+    pub osc: bool,
+    pub escape: bool,
+    pub no_trailing_space: bool,
+    pub newline: bool,
+    pub git_dirty_count: bool,
+    pub git_diverged: bool,
+    pub git_recurse_untracked: bool,
+    pub git_submodules: bool,
+    pub git_simple_dirty: bool,
+    pub git_lfs_locks: bool,
+    pub jj_flags: DisplayFlags,
+    pub git_flags: DisplayFlags,
+    pub color_flags: ColorFlags,
+    pub hide: Vec<String>,
+    pub preset: Option<String>,
+    /// Config file contents to use in place of reading the config file
+    pub config_toml: Option<String>,
 }
 
 impl Config {
-    /// Create config from CLI args and environment variables
-    /// CLI args take precedence over env vars
-    #[allow(clippy::fn_params_excessive_bools)]
-    pub fn new(
-        truncate_name: Option<usize>,
-        id_length: Option<usize>,
-        jj_symbol: Option<String>,
-        git_symbol: Option<String>,
-        no_symbol: bool,
-        jj_flags: DisplayFlags,
-        git_flags: DisplayFlags,
-    ) -> Self {
-        let truncate_name = truncate_name
-            .or_else(|| env::var("JJ_STARSHIP_TRUNCATE_NAME").ok()?.parse().ok())
-            .unwrap_or(0);
+    /// Create config from CLI args, environment variables, the config file and a preset
+    /// CLI args take precedence over env vars, then the file, then the preset
+    pub fn new(args: ConfigArgs) -> Self {
+        let config_file = file_path().filter(|path| args.config_toml.is_none() && path.is_file());
+        let file = match &args.config_toml {
+            Some(text) => parse_text(text),
+            None => config_file.as_deref().and_then(load_file),
+        };
+        let mut r = Resolver::new(file, args.preset.clone());
+
+        let mut config = Self {
+            config_file,
+            ..Self::default()
+        };
+        config.resolve_scope(&mut r, &args);
+        config.resolve_text(&mut r, &args);
+        config.resolve_flags(&mut r, &args);
+        config.resolve_git(&mut r, &args);
+        config.commit_types = r.commit_types();
+        config.custom = r.custom();
+        config.jj_palette = r.palette("jj", config.colors, &args.color_flags);
+        config.git_palette = r.palette("git", config.colors, &args.color_flags);
+        config.branch_rules = r.branch_rules(config.colors);
+        config.repo_aliases = r.repo_aliases();
+        config.jj_thresholds = r.thresholds("jj");
+        config.git_thresholds = r.thresholds("git");
+        config.origins = r.origins;
+        config
+    }
 
-        let id_length = id_length
-            .or_else(|| env::var("JJ_STARSHIP_ID_LENGTH").ok()?.parse().ok())
-            .unwrap_or(8);
+    /// Which repos to render and how much of them to show
+    fn resolve_scope(&mut self, r: &mut Resolver, args: &ConfigArgs) {
+        self.truncate_name = r.usize(
+            "truncate_name",
+            args.truncate_name,
+            "JJ_STARSHIP_TRUNCATE_NAME",
+            0,
+        );
+        self.id_length = r.usize("id_length", args.id_length, "JJ_STARSHIP_ID_LENGTH", 8);
+        self.max_untracked = r.usize(
+            "max_untracked",
+            args.max_untracked,
+            "JJ_STARSHIP_MAX_UNTRACKED",
+            DEFAULT_MAX_UNTRACKED,
+        );
+        self.large_repo_threshold = r.usize(
+            "large_repo_threshold",
+            args.large_repo_threshold,
+            "JJ_STARSHIP_LARGE_REPO_THRESHOLD",
+            0,
+        );
+        self.slow_paths = r.paths(
+            "slow_paths",
+            args.slow_paths.clone(),
+            "JJ_STARSHIP_SLOW_PATHS",
+        );
+        self.trusted_paths = r.paths("trusted_paths", Vec::new(), "JJ_STARSHIP_TRUSTED_PATHS");
+        self.network_status = r.flag(
+            "network_status",
+            args.network_status,
+            "JJ_STARSHIP_NETWORK_STATUS",
+        );
+        self.logical_pwd = r.flag("logical_pwd", args.logical_pwd, "JJ_STARSHIP_LOGICAL_PWD");
+        let colocated_name = r.string(
+            "colocated",
+            args.colocated.clone(),
+            "JJ_STARSHIP_COLOCATED",
+            Colocated::default().name(),
+        );
+        self.colocated = Colocated::parse(&colocated_name).unwrap_or_else(|| {
+            log::warning!("colocated: unknown value `{colocated_name}`");
+            Colocated::default()
+        });
+        self.disable_in = r
+            .paths("disable_in", Vec::new(), "JJ_STARSHIP_DISABLE_IN")
+            .into_iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        self.detect_max_depth =
+            r.usize("detect.max_depth", None, "JJ_STARSHIP_DETECT_MAX_DEPTH", 0);
+        self.detect_fs_budget =
+            r.usize("detect.fs_budget", None, "JJ_STARSHIP_DETECT_FS_BUDGET", 0);
+        self.segments = r.segments("segments", "JJ_STARSHIP_SEGMENTS", &Segment::DEFAULT);
+        self.hide = r.indicators("hide", args.hide.clone(), "JJ_STARSHIP_HIDE");
+        self.drop_order = r.segments("drop_order", "JJ_STARSHIP_DROP_ORDER", &DEFAULT_DROP_ORDER);
+        self.time_budget_ms = r.usize(
+            "time_budget_ms",
+            args.time_budget_ms,
+            "JJ_STARSHIP_TIME_BUDGET_MS",
+            0,
+        );
+        self.priority = r.details("priority", "JJ_STARSHIP_PRIORITY");
+        self.wip_prefixes = r.strings(
+            "wip_prefixes",
+            "JJ_STARSHIP_WIP_PREFIXES",
+            &DEFAULT_WIP_PREFIXES,
+        );
+        let max_width_percent = r.usize(
+            "max_width_percent",
+            None,
+            "JJ_STARSHIP_MAX_WIDTH_PERCENT",
+            DEFAULT_MAX_WIDTH_PERCENT,
+        );
+        // Shells rarely export COLUMNS, so starship can pass `--columns $COLUMNS`
+        let columns = args
+            .columns
+            .or_else(|| env::var("COLUMNS").ok()?.trim().parse().ok());
+        self.max_width = columns.map_or(0, |columns| columns * max_width_percent / 100);
+    }
 
-        let (jj_symbol, git_symbol) = if no_symbol {
+    /// Symbols, formats, hooks and the text around the prompt
+    fn resolve_text(&mut self, r: &mut Resolver, args: &ConfigArgs) {
+        let no_symbol = r.flag("no_symbol", args.no_symbol, "JJ_STARSHIP_NO_SYMBOL");
+        // Detected capabilities are the defaults; explicit options override them
+        let term = Term::detect();
+        log::debug!("detected terminal: {term:?}");
+        let nerd_font_name = r.string(
+            "nerd_font",
+            args.nerd_font.clone(),
+            "JJ_STARSHIP_NERD_FONT",
+            term.nerd_font.name(),
+        );
+        let nerd_font = NerdFont::parse(&nerd_font_name).unwrap_or_else(|| {
+            log::warning!("nerd_font: unknown value `{nerd_font_name}`");
+            term.nerd_font
+        });
+        let colors_name = r.string(
+            "colors",
+            args.colors.clone(),
+            "JJ_STARSHIP_COLORS",
+            term.colors.name(),
+        );
+        self.colors = ColorTier::parse(&colors_name).unwrap_or_else(|| {
+            log::warning!("colors: unknown value `{colors_name}`");
+            term.colors
+        });
+        let symbols = nerd_font.symbols();
+        let jj_symbol = r.string(
+            "jj.symbol",
+            args.jj_symbol.clone(),
+            "JJ_STARSHIP_JJ_SYMBOL",
+            symbols.jj,
+        );
+        let git_symbol = r.string(
+            "git.symbol",
+            args.git_symbol.clone(),
+            "JJ_STARSHIP_GIT_SYMBOL",
+            symbols.git,
+        );
+        (self.jj_symbol, self.git_symbol) = if no_symbol {
             (Cow::Borrowed(""), Cow::Borrowed(""))
         } else {
-            let jj = jj_symbol
-                .or_else(|| env::var("JJ_STARSHIP_JJ_SYMBOL").ok())
-                .map_or(Cow::Borrowed(DEFAULT_JJ_SYMBOL), Cow::Owned);
-            let git = git_symbol
-                .or_else(|| env::var("JJ_STARSHIP_GIT_SYMBOL").ok())
-                .map_or(Cow::Borrowed(DEFAULT_GIT_SYMBOL), Cow::Owned);
-            (jj, git)
+            (Cow::Owned(jj_symbol), Cow::Owned(git_symbol))
         };
+        self.jj_format = r.string("jj.format", None, "JJ_STARSHIP_JJ_FORMAT", "");
+        self.git_format = r.string("git.format", None, "JJ_STARSHIP_GIT_FORMAT", "");
+        self.custom_timeout_ms = r.usize(
+            "custom_timeout_ms",
+            None,
+            "JJ_STARSHIP_CUSTOM_TIMEOUT_MS",
+            500,
+        );
+        self.pre_hook = r.string("hooks.pre", None, "JJ_STARSHIP_PRE_HOOK", "");
+        self.post_hook = r.string("hooks.post", None, "JJ_STARSHIP_POST_HOOK", "");
+        self.hook_timeout_ms =
+            r.usize("hooks.timeout_ms", None, "JJ_STARSHIP_HOOK_TIMEOUT_MS", 500);
+
+        self.prefix_text = r.string(
+            "prefix_text",
+            args.prefix_text.clone(),
+            "JJ_STARSHIP_PREFIX_TEXT",
+            DEFAULT_PREFIX_TEXT,
+        );
+        self.suffix = r.string("suffix", args.suffix.clone(), "JJ_STARSHIP_SUFFIX", "");
+        self.stale_marker = r.string(
+            "stale_marker",
+            args.stale_marker.clone(),
+            "JJ_STARSHIP_STALE_MARKER",
+            DEFAULT_STALE_MARKER,
+        );
+    }
+
+    /// On/off options shared by both repo types
+    fn resolve_flags(&mut self, r: &mut Resolver, args: &ConfigArgs) {
+        self.jj_tracking = r.flag("jj.tracking", args.jj_tracking, "JJ_STARSHIP_JJ_TRACKING");
+        self.jj_large_files = r.flag(
+            "jj.large_files",
+            args.jj_large_files,
+            "JJ_STARSHIP_JJ_LARGE_FILES",
+        );
+        self.foreign_author = r.flag(
+            "foreign_author",
+            args.foreign_author,
+            "JJ_STARSHIP_FOREIGN_AUTHOR",
+        );
+        self.default_branch = r.flag(
+            "default_branch",
+            args.default_branch,
+            "JJ_STARSHIP_DEFAULT_BRANCH",
+        );
+        self.nested = r.flag("nested", args.nested, "JJ_STARSHIP_NESTED");
+        self.no_cache = r.flag("no_cache", args.no_cache, "JJ_STARSHIP_NO_CACHE");
+        self.jj_config = r.flag("jj.use_jj_config", args.jj_config, "JJ_STARSHIP_JJ_CONFIG");
+        self.state_colors = r.flag(
+            "state_colors",
+            args.state_colors,
+            "JJ_STARSHIP_STATE_COLORS",
+        );
+        self.counts = r.flag("counts", args.counts, "JJ_STARSHIP_COUNTS");
+        self.plain_words = r.flag("plain_words", args.plain_words, "JJ_STARSHIP_PLAIN_WORDS");
+        self.block = r.flag("block", args.block, "JJ_STARSHIP_BLOCK");
+        self.osc = r.flag("osc", args.osc, "JJ_STARSHIP_OSC");
+        self.escape = r.flag("escape", args.escape, "JJ_STARSHIP_ESCAPE");
+        self.trailing_space = r.shown(
+            "trailing_space",
+            args.no_trailing_space,
+            "JJ_STARSHIP_NO_TRAILING_SPACE",
+        );
+        self.newline = r.flag("newline", args.newline, "JJ_STARSHIP_NEWLINE");
+    }
 
+    /// Git-only options and per-repo-type display toggles
+    fn resolve_git(&mut self, r: &mut Resolver, args: &ConfigArgs) {
+        self.git_dirty_count = r.flag(
+            "git.dirty_count",
+            args.git_dirty_count,
+            "JJ_STARSHIP_GIT_DIRTY_COUNT",
+        );
+        self.git_diverged = r.flag(
+            "git.diverged",
+            args.git_diverged,
+            "JJ_STARSHIP_GIT_DIVERGED",
+        );
+        self.git_diverged_symbol = r.string(
+            "git.diverged_symbol",
+            None,
+            "JJ_STARSHIP_GIT_DIVERGED_SYMBOL",
+            DEFAULT_DIVERGED_SYMBOL,
+        );
+        self.git_recurse_untracked = r.flag(
+            "git.recurse_untracked",
+            args.git_recurse_untracked,
+            "JJ_STARSHIP_GIT_RECURSE_UNTRACKED",
+        );
+        self.git_submodules = r.flag(
+            "git.submodules",
+            args.git_submodules,
+            "JJ_STARSHIP_GIT_SUBMODULES",
+        );
+        self.git_simple_dirty = r.flag(
+            "git.simple_dirty",
+            args.git_simple_dirty,
+            "JJ_STARSHIP_GIT_SIMPLE_DIRTY",
+        );
+        self.git_lfs_locks = r.flag(
+            "git.lfs_locks",
+            args.git_lfs_locks,
+            "JJ_STARSHIP_GIT_LFS_LOCKS",
+        );
+        self.git_compare_remotes = r.strings(
+            "git.compare_remotes",
+            "JJ_STARSHIP_GIT_COMPARE_REMOTES",
+            &[],
+        );
+        self.jj_display = r.display("jj", args.jj_flags, "JJ_STARSHIP_NO_JJ");
+        self.git_display = r.display("git", args.git_flags, "JJ_STARSHIP_NO_GIT");
+    }
+
+    /// Effective options annotated with their source (`config show`)
+    #[cfg(feature = "cli")]
+    pub fn describe(&self) -> String {
+        let mut out = match &self.config_file {
+            Some(path) => format!("# config file: {}\n", path.display()),
+            None => "# config file: none\n".to_string(),
+        };
+        let width = self
+            .origins
+            .iter()
+            .map(|o| o.key.len() + o.value.len())
+            .max()
+            .unwrap_or(0);
+        for Origin { key, value, source } in &self.origins {
+            let pad = width - key.len() - value.len();
+            let _ = writeln!(out, "{key} = {value}{:pad$}  # {source}", "");
+        }
+        out
+    }
+
+    /// Config file lines for the options set by env vars or CLI flags
+    /// (`config migrate`), each noting where it came from
+    #[cfg(feature = "cli")]
+    pub fn migrated(&self) -> String {
+        let mut out = String::new();
+        for Origin { key, value, source } in &self.origins {
+            if matches!(source, Source::Env(_) | Source::Cli) {
+                let _ = writeln!(out, "{key} = {value}  # {source}");
+            }
+        }
+        out
+    }
+
+    /// First branch rule matching a branch/bookmark name
+    pub fn branch_rule(&self, name: &str) -> Option<&BranchRule> {
+        self.branch_rules.iter().find(|rule| rule.matches(name))
+    }
+
+    /// First repo alias matching the repo root or its `origin` URL; the URL is
+    /// only read when a root pattern doesn't match first
+    pub fn repo_alias(&self, repo_root: &Path) -> Option<&RepoAlias> {
+        let root = repo_root.to_string_lossy();
+        let cached = OnceCell::new();
+        let url = || cached.get_or_init(|| detect::origin_url(repo_root)).clone();
+        self.repo_aliases
+            .iter()
+            .find(|alias| alias.matches(&root, url))
+    }
+
+    /// This config with a repo alias applied: the alias follows the symbol
+    /// (`🏢 mono main`), and the alias symbol replaces both repo symbols
+    #[must_use]
+    pub fn aliased(&self, alias: &RepoAlias) -> Self {
+        let symbol = |default: &str| {
+            let symbol = alias.symbol.as_deref().unwrap_or(default);
+            Cow::Owned(match &alias.alias {
+                Some(name) => format!("{symbol}{name} "),
+                None => symbol.to_string(),
+            })
+        };
         Self {
-            truncate_name,
-            id_length,
-            jj_symbol,
-            git_symbol,
-            jj_display: jj_flags.into_config("JJ_STARSHIP_NO_JJ"),
-            git_display: git_flags.into_config("JJ_STARSHIP_NO_GIT"),
+            jj_symbol: symbol(&self.jj_symbol),
+            git_symbol: symbol(&self.git_symbol),
+            ..self.clone()
+        }
+    }
+
+    /// Whether `detail` still fits `time_budget_ms` after `elapsed`: of the n
+    /// details in `priority`, the one at rank i (0 first) may start until
+    /// (n - i)/n of the budget is spent, so the least important drop first
+    /// Names and details left out of `priority` are never dropped
+    pub fn within_budget(&self, detail: Detail, elapsed: Duration) -> bool {
+        if self.time_budget_ms == 0 || detail == Detail::Name {
+            return true;
+        }
+        let n = self.priority.len();
+        let Some(rank) = self.priority.iter().position(|&d| d == detail) else {
+            return true;
+        };
+        // In u128, so a huge budget can't overflow
+        elapsed.as_millis() * (n as u128) < (self.time_budget_ms as u128) * ((n - rank) as u128)
+    }
+
+    /// This config without IDs once the run is past the ID's share of the time budget
+    #[must_use]
+    pub fn budgeted(&self, timings: &Timings) -> Option<Self> {
+        if timings.allows(self, Detail::Id) {
+            return None;
+        }
+        Some(Self {
+            jj_display: DisplayConfig {
+                show_id: false,
+                ..self.jj_display
+            },
+            git_display: DisplayConfig {
+                show_id: false,
+                ..self.git_display
+            },
+            ..self.clone()
+        })
+    }
+
+    /// Icon for a conventional-commit type, if it has a non-empty one
+    pub fn commit_icon(&self, commit_type: &str) -> Option<&str> {
+        self.commit_types
+            .iter()
+            .find(|(name, _)| name == commit_type)
+            .map(|(_, icon)| icon.as_str())
+            .filter(|icon| !icon.is_empty())
+    }
+
+    /// Whether a commit description starts with one of `wip_prefixes`
+    pub fn is_wip(&self, description: &str) -> bool {
+        let description = description.trim_start().to_lowercase();
+        self.wip_prefixes
+            .iter()
+            .any(|prefix| !prefix.is_empty() && description.starts_with(&prefix.to_lowercase()))
+    }
+
+    /// Backend to run for a detected repo: colocated repos use Git's with
+    /// `colocated = "git"` (when built with Git support)
+    pub fn backend(&self, repo_type: RepoType) -> RepoType {
+        match repo_type {
+            RepoType::JjColocated if cfg!(feature = "git") && self.colocated == Colocated::Git => {
+                RepoType::Git
+            }
+            repo_type => repo_type,
         }
     }
 
+    /// Whether a repo root falls under a configured slow path or, unless
+    /// `network_status` is set, on a network filesystem
+    pub fn is_slow_path(&self, repo_root: &Path) -> bool {
+        self.slow_paths.iter().any(|p| repo_root.starts_with(p))
+            || (!self.network_status && netfs::is_network(repo_root))
+    }
+
+    /// Whether a Git repo owned by another user may be read anyway
+    #[cfg(feature = "git")]
+    pub fn is_trusted_path(&self, repo_root: &Path) -> bool {
+        self.trusted_paths.iter().any(|p| repo_root.starts_with(p))
+    }
+
     /// Truncate a string to max length, adding ellipsis if needed
     pub fn truncate<'a>(&self, s: &'a str) -> Cow<'a, str> {
         if self.truncate_name == 0 || s.chars().count() <= self.truncate_name {
@@ -140,4 +1166,851 @@ impl Config {
             Cow::Owned(truncated + "…")
         }
     }
+
+    /// Short form for transient prompts: `{symbol}{name}` read from refs only
+    #[cfg(feature = "cli")]
+    #[must_use]
+    pub fn transient(self) -> Self {
+        let segments = self
+            .segments
+            .into_iter()
+            .filter(|s| matches!(s, Segment::Prefix | Segment::Name))
+            .collect();
+        Self {
+            segments,
+            prefix_text: String::new(),
+            // Repo state is unknown without a status scan
+            state_colors: false,
+            refs_only: true,
+            ..self
+        }
+    }
+}
+
+/// Config file location: `$JJ_STARSHIP_CONFIG`, else
+/// `$XDG_CONFIG_HOME/jj-starship/config.toml` (default `~/.config`)
+pub fn file_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("JJ_STARSHIP_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    Some(
+        xdg_dir("XDG_CONFIG_HOME", ".config")?
+            .join("jj-starship")
+            .join("config.toml"),
+    )
+}
+
+/// `$HOME`, unless unset, empty or relative
+pub fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+}
+
+/// An XDG base directory: `$var`, else `~/{fallback}`; empty and relative
+/// values are ignored as the spec asks, so a blank `HOME` in a container
+/// never resolves into the working directory
+pub fn xdg_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| Some(home_dir()?.join(fallback)))
+}
+
+/// Load a config file; a malformed file is ignored rather than failing the prompt
+fn load_file(path: &Path) -> Option<StackedConfig> {
+    match ConfigLayer::load_from_file(ConfigSource::User, path.to_path_buf()) {
+        Ok(layer) => {
+            let mut config = StackedConfig::empty();
+            config.add_layer(layer);
+            Some(config)
+        }
+        Err(e) => {
+            log::warning!("ignoring config file {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Parse config file contents passed in directly; malformed text is ignored like a file
+fn parse_text(text: &str) -> Option<StackedConfig> {
+    match ConfigLayer::parse(ConfigSource::User, text) {
+        Ok(layer) => {
+            let mut config = StackedConfig::empty();
+            config.add_layer(layer);
+            Some(config)
+        }
+        Err(e) => {
+            log::warning!("ignoring config: {e}");
+            None
+        }
+    }
+}
+
+/// Value type expected for a config file key
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Usize,
+    Bool,
+    String,
+    Paths,
+    /// Array of segment names
+    Segments,
+    /// Array of status indicator names
+    Indicators,
+    /// Array of `priority` detail names
+    Details,
+    /// Array of strings
+    Strings,
+    /// One of a fixed set of strings
+    Choice(&'static [&'static str]),
+    Style,
+    /// Table of pattern = style (or `{ style, symbol }`)
+    Rules,
+    /// Table of pattern = alias (or `{ alias, symbol }`)
+    Aliases,
+    /// Table of name = string
+    Table,
+}
+
+/// Every key the config file accepts (`[jj]`/`[git]` keys are dotted)
+#[cfg(feature = "cli")]
+pub const KEYS: &[(&str, Kind)] = &[
+    ("truncate_name", Kind::Usize),
+    ("id_length", Kind::Usize),
+    ("max_untracked", Kind::Usize),
+    ("large_repo_threshold", Kind::Usize),
+    ("slow_paths", Kind::Paths),
+    ("trusted_paths", Kind::Paths),
+    ("network_status", Kind::Bool),
+    ("logical_pwd", Kind::Bool),
+    ("colocated", Kind::Choice(Colocated::NAMES)),
+    ("segments", Kind::Segments),
+    ("hide", Kind::Indicators),
+    ("drop_order", Kind::Segments),
+    ("time_budget_ms", Kind::Usize),
+    ("priority", Kind::Details),
+    ("wip_prefixes", Kind::Strings),
+    ("commit_types", Kind::Table),
+    ("custom", Kind::Table),
+    ("custom_timeout_ms", Kind::Usize),
+    ("hooks.pre", Kind::String),
+    ("hooks.post", Kind::String),
+    ("hooks.timeout_ms", Kind::Usize),
+    ("max_width_percent", Kind::Usize),
+    ("disable_in", Kind::Paths),
+    ("detect.max_depth", Kind::Usize),
+    ("detect.fs_budget", Kind::Usize),
+    ("no_symbol", Kind::Bool),
+    ("nerd_font", Kind::Choice(NerdFont::NAMES)),
+    ("colors", Kind::Choice(ColorTier::NAMES)),
+    ("prefix_text", Kind::String),
+    ("suffix", Kind::String),
+    ("stale_marker", Kind::String),
+    ("state_colors", Kind::Bool),
+    ("foreign_author", Kind::Bool),
+    ("default_branch", Kind::Bool),
+    ("nested", Kind::Bool),
+    ("no_cache", Kind::Bool),
+    ("counts", Kind::Bool),
+    ("plain_words", Kind::Bool),
+    ("block", Kind::Bool),
+    ("osc", Kind::Bool),
+    ("escape", Kind::Bool),
+    ("trailing_space", Kind::Bool),
+    ("newline", Kind::Bool),
+    ("preset", Kind::String),
+    ("branch_styles", Kind::Rules),
+    ("repo_aliases", Kind::Aliases),
+    ("jj.symbol", Kind::String),
+    ("jj.format", Kind::String),
+    ("jj.tracking", Kind::Bool),
+    ("jj.large_files", Kind::Bool),
+    ("jj.use_jj_config", Kind::Bool),
+    ("jj.prefix", Kind::Bool),
+    ("jj.name", Kind::Bool),
+    ("jj.id", Kind::Bool),
+    ("jj.status", Kind::Bool),
+    ("jj.color", Kind::Bool),
+    ("jj.symbol_style", Kind::Style),
+    ("jj.name_style", Kind::Style),
+    ("jj.id_style", Kind::Style),
+    ("jj.status_style", Kind::Style),
+    ("git.symbol", Kind::String),
+    ("git.format", Kind::String),
+    ("git.dirty_count", Kind::Bool),
+    ("git.diverged", Kind::Bool),
+    ("git.diverged_symbol", Kind::String),
+    ("git.recurse_untracked", Kind::Bool),
+    ("git.submodules", Kind::Bool),
+    ("git.simple_dirty", Kind::Bool),
+    ("git.lfs_locks", Kind::Bool),
+    ("git.compare_remotes", Kind::Strings),
+    ("git.prefix", Kind::Bool),
+    ("git.name", Kind::Bool),
+    ("git.id", Kind::Bool),
+    ("git.status", Kind::Bool),
+    ("git.color", Kind::Bool),
+    ("git.symbol_style", Kind::Style),
+    ("git.name_style", Kind::Style),
+    ("git.id_style", Kind::Style),
+    ("git.status_style", Kind::Style),
+    ("jj.status_styles.skipped", Kind::Style),
+    ("jj.status_styles.conflict", Kind::Style),
+    ("jj.status_styles.conflict_below", Kind::Style),
+    ("jj.status_styles.divergent", Kind::Style),
+    ("jj.status_styles.undescribed", Kind::Style),
+    ("jj.status_styles.wip", Kind::Style),
+    ("jj.status_styles.foreign_author", Kind::Style),
+    ("jj.status_styles.default_branch", Kind::Style),
+    ("jj.status_styles.sparse", Kind::Style),
+    ("jj.status_styles.large_file", Kind::Style),
+    ("jj.status_styles.ahead", Kind::Style),
+    ("jj.status_styles.behind", Kind::Style),
+    ("git.status_styles.skipped", Kind::Style),
+    ("git.status_styles.conflict", Kind::Style),
+    ("git.status_styles.unreadable", Kind::Style),
+    ("git.status_styles.lfs_locked", Kind::Style),
+    ("git.status_styles.lfs_unlocked", Kind::Style),
+    ("git.status_styles.staged", Kind::Style),
+    ("git.status_styles.modified", Kind::Style),
+    ("git.status_styles.untracked", Kind::Style),
+    ("git.status_styles.deleted", Kind::Style),
+    ("git.status_styles.renamed", Kind::Style),
+    ("git.status_styles.typechanged", Kind::Style),
+    ("git.status_styles.wip", Kind::Style),
+    ("git.status_styles.foreign_author", Kind::Style),
+    ("git.status_styles.default_branch", Kind::Style),
+    ("git.status_styles.ahead", Kind::Style),
+    ("git.status_styles.behind", Kind::Style),
+    ("jj.thresholds.ahead", Kind::Usize),
+    ("jj.thresholds.behind", Kind::Usize),
+    ("git.thresholds.conflict", Kind::Usize),
+    ("git.thresholds.staged", Kind::Usize),
+    ("git.thresholds.modified", Kind::Usize),
+    ("git.thresholds.untracked", Kind::Usize),
+    ("git.thresholds.deleted", Kind::Usize),
+    ("git.thresholds.renamed", Kind::Usize),
+    ("git.thresholds.typechanged", Kind::Usize),
+    ("git.thresholds.ahead", Kind::Usize),
+    ("git.thresholds.behind", Kind::Usize),
+];
+
+/// Where an effective option value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File,
+    Env(String),
+    Preset(String),
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File => f.write_str("config file"),
+            Self::Env(var) => write!(f, "env {var}"),
+            Self::Preset(name) => write!(f, "preset {name}"),
+            Self::Cli => f.write_str("command line"),
+        }
+    }
+}
+
+/// An option's effective value (TOML-style) and its source
+#[cfg_attr(not(feature = "cli"), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub struct Origin {
+    pub key: String,
+    pub value: String,
+    pub source: Source,
+}
+
+/// Built-in presets
+const PRESETS: &[&str] = &["minimal", "full", "nerd", "ascii"];
+
+/// A built-in preset as a config file snippet; `minimal` hides every indicator
+/// but the conflict marker and `full` shows every segment, including ones added later
+fn preset_text(name: &str) -> Option<String> {
+    let list = |names: &mut dyn Iterator<Item = &str>| {
+        let quoted: Vec<String> = names.map(|name| format!("\"{name}\"")).collect();
+        format!("[{}]", quoted.join(", "))
+    };
+    let text = match name {
+        "minimal" => format!(
+            "prefix_text = \"\"\nhide = {}\n\
+             jj.id = false\njj.color = false\n\
+             git.id = false\ngit.color = false\n",
+            list(
+                &mut Indicator::ALL
+                    .into_iter()
+                    .filter(|&i| i != Indicator::Conflict)
+                    .map(Indicator::name)
+            )
+        ),
+        "full" => format!(
+            "segments = {}\n\
+             counts = true\nforeign_author = true\ndefault_branch = true\nnested = true\n\
+             jj.tracking = true\njj.use_jj_config = true\n",
+            list(&mut Segment::ALL.into_iter().map(Segment::name))
+        ),
+        "nerd" => "nerd_font = \"v3\"\n".to_string(),
+        "ascii" => "nerd_font = \"none\"\n".to_string(),
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// Names of the built-in presets
+#[cfg(feature = "cli")]
+pub fn preset_names() -> impl Iterator<Item = &'static str> {
+    PRESETS.iter().copied()
+}
+
+/// Config source a resolver reads keys from, under a key prefix
+struct Layer {
+    config: StackedConfig,
+    prefix: String,
+    source: Source,
+}
+
+/// Resolves each option as CLI > env > config file > preset > default, recording origins
+struct Resolver {
+    /// Highest priority first
+    layers: Vec<Layer>,
+    origins: Vec<Origin>,
+}
+
+impl Resolver {
+    /// Layer the config file over the preset it (or the CLI/env) selects
+    fn new(file: Option<StackedConfig>, preset: Option<String>) -> Self {
+        let mut r = Self {
+            layers: Vec::new(),
+            origins: Vec::new(),
+        };
+        if let Some(file) = file {
+            r.layers.push(Layer {
+                config: file,
+                prefix: String::new(),
+                source: Source::File,
+            });
+        }
+
+        let layered = r.lookup("preset", |c, k| c.get::<String>(k).ok());
+        let name = r.pick("preset", preset, "JJ_STARSHIP_PRESET", layered);
+        let Some(name) = name else {
+            return r;
+        };
+        // User presets in the config file shadow built-ins of the same name
+        let user_prefix = format!("presets.{name}.");
+        let user = r.layers.first().and_then(|file| {
+            file.config
+                .get_table(["presets", name.as_str()])
+                .is_ok()
+                .then(|| file.config.clone())
+        });
+        let builtin = || {
+            let text = preset_text(&name)?;
+            let layer = ConfigLayer::parse(ConfigSource::Default, &text).ok()?;
+            let mut config = StackedConfig::empty();
+            config.add_layer(layer);
+            Some(config)
+        };
+        match user
+            .map(|c| (c, user_prefix))
+            .or_else(|| builtin().map(|c| (c, String::new())))
+        {
+            Some((config, prefix)) => r.layers.push(Layer {
+                config,
+                prefix,
+                source: Source::Preset(name),
+            }),
+            None => log::warning!("unknown preset `{name}`"),
+        }
+        r
+    }
+
+    /// JSON scalars and arrays double as TOML values here
+    #[allow(clippy::needless_pass_by_value)]
+    fn record(&mut self, key: &str, value: json::Value, source: Source) {
+        self.push(key, value.to_string(), source);
+    }
+
+    fn push(&mut self, key: &str, value: String, source: Source) {
+        self.origins.push(Origin {
+            key: key.to_string(),
+            value,
+            source,
+        });
+    }
+
+    /// First layer defining `key`, read with `get`
+    fn lookup<T>(
+        &self,
+        key: &str,
+        get: impl Fn(&StackedConfig, &ConfigNamePathBuf) -> Option<T>,
+    ) -> Option<(T, Source)> {
+        self.layers.iter().find_map(|layer| {
+            let name = format!("{}{key}", layer.prefix).parse().ok()?;
+            let value = get(&layer.config, &name)?;
+            Some((value, layer.source.clone()))
+        })
+    }
+
+    /// CLI > env > layers, recording `null` when nothing sets it
+    fn pick<T: FromStr + Clone + Into<json::Value>>(
+        &mut self,
+        key: &str,
+        cli: Option<T>,
+        env_var: &str,
+        layered: Option<(T, Source)>,
+    ) -> Option<T> {
+        let env = || env::var(env_var).ok()?.parse().ok();
+        let (value, source) = if let Some(value) = cli {
+            (Some(value), Source::Cli)
+        } else if let Some(value) = env() {
+            (Some(value), Source::Env(env_var.to_string()))
+        } else if let Some((value, source)) = layered {
+            (Some(value), source)
+        } else {
+            (None, Source::Default)
+        };
+        self.record(key, value.clone().into(), source);
+        value
+    }
+
+    fn usize(&mut self, key: &str, cli: Option<usize>, env_var: &str, default: usize) -> usize {
+        let layered = self.lookup(key, |c, k| c.get::<usize>(k).ok());
+        let layered = layered.or(Some((default, Source::Default)));
+        self.pick(key, cli, env_var, layered).unwrap_or(default)
+    }
+
+    fn string(&mut self, key: &str, cli: Option<String>, env_var: &str, default: &str) -> String {
+        let layered = self.lookup(key, |c, k| c.get::<String>(k).ok());
+        let layered = layered.or_else(|| Some((default.to_string(), Source::Default)));
+        self.pick(key, cli, env_var, layered)
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    /// Opt-in switch; the env var only needs to be set
+    fn flag(&mut self, key: &str, cli: bool, env_var: &str) -> bool {
+        let layered = self.lookup(key, |c, k| c.get::<bool>(k).ok());
+        let (value, source) = if cli {
+            (true, Source::Cli)
+        } else if env::var(env_var).is_ok() {
+            (true, Source::Env(env_var.to_string()))
+        } else {
+            layered.unwrap_or((false, Source::Default))
+        };
+        self.record(key, value.into(), source);
+        value
+    }
+
+    /// Visible-by-default part, hidden by a `--no-*` flag or `{env_prefix}_*` var
+    fn shown(&mut self, key: &str, hidden: bool, env_var: &str) -> bool {
+        let layered = self.lookup(key, |c, k| c.get::<bool>(k).ok());
+        let (value, source) = if hidden {
+            (false, Source::Cli)
+        } else if env::var(env_var).is_ok() {
+            (false, Source::Env(env_var.to_string()))
+        } else {
+            layered.unwrap_or((true, Source::Default))
+        };
+        self.record(key, value.into(), source);
+        value
+    }
+
+    fn display(&mut self, section: &str, flags: DisplayFlags, env_prefix: &str) -> DisplayConfig {
+        let mut shown = |part: &str, hidden: bool| {
+            let env_var = format!("{env_prefix}_{}", part.to_uppercase());
+            self.shown(&format!("{section}.{part}"), hidden, &env_var)
+        };
+        DisplayConfig {
+            show_prefix: shown("prefix", flags.no_prefix),
+            show_name: shown("name", flags.no_name),
+            show_id: shown("id", flags.no_id),
+            show_status: shown("status", flags.no_status),
+            show_color: shown("color", flags.no_color),
+        }
+    }
+
+    /// Part styles come from `--{part}-color`, then `JJ_STARSHIP_{PART}_COLOR`,
+    /// then `{section}.{part}_style`; invalid styles fall through to the next source
+    fn palette(&mut self, section: &str, colors: ColorTier, flags: &ColorFlags) -> Palette {
+        let mut style =
+            |part: &str, cli: Option<&str>, default: &'static str, default_name: &str| {
+                let key = format!("{section}.{part}_style");
+                let env_var = format!("JJ_STARSHIP_{}_COLOR", part.to_ascii_uppercase());
+                let overridden = cli
+                    .map(|name| (name.to_string(), Source::Cli))
+                    .or_else(|| Some((env::var(&env_var).ok()?, Source::Env(env_var.clone()))))
+                    .and_then(|(name, source)| match color::parse_style(&name, colors) {
+                        Some(code) => Some(((code, name), source)),
+                        None => {
+                            log::warning!("{part} color: invalid style `{name}`");
+                            None
+                        }
+                    });
+                let layered = overridden.or_else(|| {
+                    self.lookup(&key, |c, k| {
+                        let name = c.get::<String>(k).ok()?;
+                        Some((color::parse_style(&name, colors)?, name))
+                    })
+                });
+                if let Some(((code, name), source)) = layered {
+                    self.record(&key, name.into(), source);
+                    code
+                } else {
+                    self.record(&key, default_name.into(), Source::Default);
+                    Cow::Borrowed(default)
+                }
+            };
+        let symbol = style("symbol", flags.symbol.as_deref(), BLUE, "blue");
+        let name = style("name", flags.name.as_deref(), PURPLE, "magenta");
+        let id = style("id", flags.id.as_deref(), GREEN, "green");
+        let status = style("status", flags.status.as_deref(), RED, "red");
+
+        // Only set indicator colors are recorded; the rest follow `status_style`
+        let mut indicators = Vec::new();
+        for indicator in Indicator::ALL {
+            let key = format!("{section}.status_styles.{}", indicator.name());
+            let layered = self.lookup(&key, |c, k| {
+                let name = c.get::<String>(k).ok()?;
+                Some((color::parse_style(&name, colors)?, name))
+            });
+            if let Some(((code, name), source)) = layered {
+                self.record(&key, name.into(), source);
+                indicators.push((indicator, code));
+            }
+        }
+        Palette {
+            symbol,
+            name,
+            id,
+            status,
+            indicators,
+        }
+    }
+
+    /// Indicator thresholds; only set ones are recorded
+    fn thresholds(&mut self, section: &str) -> Thresholds {
+        let mut thresholds = Vec::new();
+        for indicator in Indicator::ALL {
+            let key = format!("{section}.thresholds.{}", indicator.name());
+            if let Some((min, source)) = self.lookup(&key, |c, k| c.get::<usize>(k).ok()) {
+                self.record(&key, min.into(), source);
+                thresholds.push((indicator, min));
+            }
+        }
+        Thresholds(thresholds)
+    }
+
+    /// `[branch_styles]` from the first layer that has it, in file order
+    fn branch_rules(&mut self, colors: ColorTier) -> Vec<BranchRule> {
+        let Some((table, source)) = self.lookup("branch_styles", |c, k| c.get_table(k).ok()) else {
+            return Vec::new();
+        };
+        let mut rules = Vec::new();
+        for (pattern, item) in &table {
+            let (style, symbol) = if let Some(style) = item.as_str() {
+                (Some(style), None)
+            } else {
+                let Some(entry) = item.as_table_like() else {
+                    continue;
+                };
+                let field = |name: &str| entry.get(name).and_then(toml_edit::Item::as_str);
+                (field("style"), field("symbol"))
+            };
+            self.push(
+                &format!("branch_styles.{}", json::Value::from(pattern)),
+                item.to_string().trim().to_string(),
+                source.clone(),
+            );
+            rules.push(BranchRule {
+                pattern: pattern.to_string(),
+                color: style.and_then(|style| color::parse_style(style, colors)),
+                symbol: symbol.map(String::from),
+            });
+        }
+        rules
+    }
+
+    /// `[repo_aliases]` from the first layer that has it, in file order
+    fn repo_aliases(&mut self) -> Vec<RepoAlias> {
+        let Some((table, source)) = self.lookup("repo_aliases", |c, k| c.get_table(k).ok()) else {
+            return Vec::new();
+        };
+        let mut aliases = Vec::new();
+        for (pattern, item) in &table {
+            let (alias, symbol) = if let Some(alias) = item.as_str() {
+                (Some(alias), None)
+            } else {
+                let Some(entry) = item.as_table_like() else {
+                    continue;
+                };
+                let field = |name: &str| entry.get(name).and_then(toml_edit::Item::as_str);
+                (field("alias"), field("symbol"))
+            };
+            self.push(
+                &format!("repo_aliases.{}", json::Value::from(pattern)),
+                item.to_string().trim().to_string(),
+                source.clone(),
+            );
+            aliases.push(RepoAlias {
+                pattern: pattern.to_string(),
+                alias: alias.map(String::from),
+                symbol: symbol.map(String::from),
+            });
+        }
+        aliases
+    }
+
+    /// `[commit_types]` entries override the built-in icons type by type
+    fn commit_types(&mut self) -> Vec<(String, String)> {
+        let mut types = default_commit_types();
+        let Some((table, source)) = self.lookup("commit_types", |c, k| c.get_table(k).ok()) else {
+            return types;
+        };
+        for (name, item) in &table {
+            let Some(icon) = item.as_str() else {
+                continue;
+            };
+            self.push(
+                &format!("commit_types.{}", json::Value::from(name)),
+                json::Value::from(icon).to_string(),
+                source.clone(),
+            );
+            match types.iter_mut().find(|(known, _)| known == name) {
+                Some(entry) => entry.1 = icon.to_string(),
+                None => types.push((name.to_string(), icon.to_string())),
+            }
+        }
+        types
+    }
+
+    /// `[custom]` commands, in file order
+    fn custom(&mut self) -> Vec<(String, String)> {
+        let Some((table, source)) = self.lookup("custom", |c, k| c.get_table(k).ok()) else {
+            return Vec::new();
+        };
+        let mut commands = Vec::new();
+        for (name, item) in &table {
+            let Some(command) = item.as_str() else {
+                continue;
+            };
+            self.push(
+                &format!("custom.{}", json::Value::from(name)),
+                json::Value::from(command).to_string(),
+                source.clone(),
+            );
+            commands.push((name.to_string(), command.to_string()));
+        }
+        commands
+    }
+
+    /// Segment list from a comma-separated env var or an array; unknown names are dropped
+    fn segments(&mut self, key: &str, env_var: &str, default: &[Segment]) -> Vec<Segment> {
+        let (names, source) = match env::var(env_var) {
+            Ok(list) if !list.trim().is_empty() => (
+                list.split(',').map(|n| n.trim().to_string()).collect(),
+                Source::Env(env_var.to_string()),
+            ),
+            _ => self
+                .lookup(key, |c, k| c.get::<Vec<String>>(k).ok())
+                .unwrap_or_else(|| {
+                    let names = default.iter().map(|s| s.name().into()).collect();
+                    (names, Source::Default)
+                }),
+        };
+        let segments: Vec<Segment> = names
+            .iter()
+            .filter_map(|name| {
+                let segment = Segment::parse(name);
+                if segment.is_none() {
+                    log::warning!("{key}: unknown segment `{name}`");
+                }
+                segment
+            })
+            .collect();
+        let value = segments
+            .iter()
+            .map(|s| json::Value::from(s.name()))
+            .collect();
+        self.record(key, json::Value::Array(value), source);
+        segments
+    }
+
+    /// Indicator names from the CLI, env (comma-separated) or the first layer
+    /// that sets them, plus the preset's; unknown names are dropped with a warning
+    fn indicators(&mut self, key: &str, cli: Vec<String>, env_var: &str) -> Vec<Indicator> {
+        let (names, source) = if cli.is_empty() {
+            match env::var(env_var) {
+                Ok(list) => (
+                    list.split(',').map(|n| n.trim().to_string()).collect(),
+                    Source::Env(env_var.to_string()),
+                ),
+                Err(_) => self
+                    .lookup(key, |c, k| c.get::<Vec<String>>(k).ok())
+                    .unwrap_or((Vec::new(), Source::Default)),
+            }
+        } else {
+            (cli, Source::Cli)
+        };
+        // What the preset hides stays hidden under a list from anywhere else
+        let preset_names = self
+            .layers
+            .iter()
+            .filter(|layer| matches!(layer.source, Source::Preset(_)) && layer.source != source)
+            .find_map(|layer| {
+                let name = format!("{}{key}", layer.prefix).parse().ok()?;
+                layer.config.get::<Vec<String>>(&name).ok()
+            })
+            .unwrap_or_default();
+        let mut indicators: Vec<Indicator> = Vec::new();
+        for name in names.iter().chain(&preset_names) {
+            if name.is_empty() {
+                continue;
+            }
+            match Indicator::parse(name) {
+                Some(indicator) if indicators.contains(&indicator) => {}
+                Some(indicator) => indicators.push(indicator),
+                None => log::warning!("{key}: unknown indicator `{name}`"),
+            }
+        }
+        let value = indicators
+            .iter()
+            .map(|i| json::Value::from(i.name()))
+            .collect();
+        self.record(key, json::Value::Array(value), source);
+        indicators
+    }
+
+    /// Detail list from a comma-separated env var or an array; unknown names are dropped
+    fn details(&mut self, key: &str, env_var: &str) -> Vec<Detail> {
+        let (names, source) = match env::var(env_var) {
+            Ok(list) if !list.trim().is_empty() => (
+                list.split(',').map(|n| n.trim().to_string()).collect(),
+                Source::Env(env_var.to_string()),
+            ),
+            _ => self
+                .lookup(key, |c, k| c.get::<Vec<String>>(k).ok())
+                .unwrap_or_else(|| {
+                    let names = Detail::ALL.iter().map(|d| d.name().into()).collect();
+                    (names, Source::Default)
+                }),
+        };
+        let details: Vec<Detail> = names
+            .iter()
+            .filter_map(|name| {
+                let detail = Detail::parse(name);
+                if detail.is_none() {
+                    log::warning!("{key}: unknown detail `{name}`");
+                }
+                detail
+            })
+            .collect();
+        let value = details
+            .iter()
+            .map(|d| json::Value::from(d.name()))
+            .collect();
+        self.record(key, json::Value::Array(value), source);
+        details
+    }
+
+    /// String list from env (comma-separated) or the first layer that sets it
+    fn strings(&mut self, key: &str, env_var: &str, default: &[&str]) -> Vec<String> {
+        let (strings, source) = match env::var(env_var) {
+            Ok(list) => (
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect(),
+                Source::Env(env_var.to_string()),
+            ),
+            Err(_) => self
+                .lookup(key, |c, k| c.get::<Vec<String>>(k).ok())
+                .unwrap_or_else(|| {
+                    let strings = default.iter().map(|&s| s.to_string()).collect();
+                    (strings, Source::Default)
+                }),
+        };
+        let value = strings
+            .iter()
+            .map(|s| json::Value::from(s.as_str()))
+            .collect();
+        self.record(key, json::Value::Array(value), source);
+        strings
+    }
+
+    /// Path lists combine CLI, env and the first layer that sets them; CLI entries come first
+    fn paths(&mut self, key: &str, cli: Vec<PathBuf>, env_var: &str) -> Vec<PathBuf> {
+        let env_paths: Vec<PathBuf> = env::var_os(env_var)
+            .map(|paths| env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        let layered = self.lookup(key, |c, k| c.get::<Vec<PathBuf>>(k).ok());
+
+        let source = if !cli.is_empty() {
+            Source::Cli
+        } else if !env_paths.is_empty() {
+            Source::Env(env_var.to_string())
+        } else if let Some((_, source)) = &layered {
+            source.clone()
+        } else {
+            Source::Default
+        };
+        let mut paths = cli;
+        paths.extend(env_paths);
+        paths.extend(layered.map(|(paths, _)| paths).unwrap_or_default());
+        let value = paths
+            .iter()
+            .map(|p| json::Value::from(p.to_string_lossy().into_owned()))
+            .collect();
+        self.record(key, json::Value::Array(value), source);
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Config from a preset and `--hide`, with no config file
+    fn preset_config(preset: &str, hide: &[&str]) -> Config {
+        Config::new(ConfigArgs {
+            config_toml: Some(String::new()),
+            preset: Some(preset.to_string()),
+            hide: hide.iter().map(|&name| name.to_string()).collect(),
+            ..ConfigArgs::default()
+        })
+    }
+
+    #[test]
+    fn test_minimal_preset() {
+        let config = preset_config("minimal", &[]);
+        assert_eq!(config.prefix_text, "");
+        assert!(!config.jj_symbol.is_empty());
+        assert!(!config.jj_display.show_id && !config.jj_display.show_color);
+        assert!(config.jj_display.show_name && config.jj_display.show_status);
+        let shown: Vec<Indicator> = Indicator::ALL
+            .into_iter()
+            .filter(|i| !config.hide.contains(i))
+            .collect();
+        assert_eq!(shown, [Indicator::Conflict]);
+
+        // A hide list of its own adds to the preset's
+        let config = preset_config("minimal", &["conflict", "ahead"]);
+        assert_eq!(config.hide.len(), Indicator::ALL.len());
+        assert_eq!(preset_config("nerd", &["ahead"]).hide, [Indicator::Ahead]);
+    }
+
+    #[test]
+    fn test_full_preset() {
+        let config = preset_config("full", &[]);
+        assert_eq!(config.segments, Segment::ALL);
+        assert!(config.counts && config.nested && config.jj_tracking);
+        assert!(config.hide.is_empty());
+    }
 }
@@ -0,0 +1,178 @@
+//! Custom variables (`[custom]`): shell commands run in the repo root whose
+//! output format templates show as `${custom.<name>}`
+//! The commands run in parallel under one shared timeout; one that fails,
+//! prints nothing or runs out of time leaves its variable empty
+//! The render hooks (`hook`) share the shell runner
+
+use crate::config::Config;
+use crate::log;
+use crate::template;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often a running command is checked on
+const POLL: Duration = Duration::from_millis(5);
+
+/// First output line of each `[custom]` command a format template uses, by
+/// name; None when no template uses any
+pub fn run(config: &Config, repo_root: &Path) -> Option<Vec<(String, String)>> {
+    let used: Vec<_> = config
+        .custom
+        .iter()
+        .filter(|(name, _)| is_used(config, name))
+        .collect();
+    if used.is_empty() {
+        return None;
+    }
+    let deadline = Instant::now() + Duration::from_millis(config.custom_timeout_ms as u64);
+    let children: Vec<_> = used
+        .into_iter()
+        .map(|(name, command)| {
+            let child = shell(command, repo_root)
+                .stdin(Stdio::null())
+                .spawn()
+                .inspect_err(|e| log::debug!("custom `{name}` failed to start: {e}"))
+                .ok();
+            (name, child)
+        })
+        .collect();
+    let values = children
+        .into_iter()
+        .map(|(name, child)| {
+            let value = child
+                .and_then(|child| wait(child, deadline))
+                .filter(|(status, _)| status.success())
+                .map(|(_, out)| out.lines().next().unwrap_or_default().trim().to_string());
+            if value.is_none() {
+                log::debug!("custom `{name}` failed or timed out");
+            }
+            (name.clone(), value.unwrap_or_default())
+        })
+        .collect();
+    Some(values)
+}
+
+/// Whether `jj.format` or `git.format` uses `${custom.<name>}`
+fn is_used(config: &Config, name: &str) -> bool {
+    let var = format!("custom.{name}");
+    [&config.jj_format, &config.git_format]
+        .iter()
+        .any(|format| template::variables(format).contains(&var))
+}
+
+/// `command` run by the shell in `dir`, its output piped and errors dropped
+pub fn shell(command: &str, dir: &Path) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell
+        .arg(command)
+        .current_dir(dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    shell
+}
+
+/// Exit status and output of a command; one still running at `deadline` is
+/// killed instead
+/// The output is read on a thread as it arrives, so a command that fills the
+/// pipe isn't left blocked on writing it
+pub fn wait(mut child: Child, deadline: Instant) -> Option<(ExitStatus, String)> {
+    let (sender, output) = mpsc::channel();
+    if let Some(mut stdout) = child.stdout.take() {
+        thread::spawn(move || {
+            let mut out = String::new();
+            let _ = stdout.read_to_string(&mut out);
+            let _ = sender.send(out);
+        });
+    }
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                // A background process can hold the pipe open past the exit
+                let left = deadline.saturating_duration_since(Instant::now());
+                let out = output.recv_timeout(left).unwrap_or_default();
+                return Some((status, out));
+            }
+            Ok(None) if Instant::now() < deadline => thread::sleep(POLL),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let custom = [
+            ("version", "printf 'v1.2 \\nrest'"),
+            ("broken", "echo oops; exit 1"),
+            ("slow", "sleep 5"),
+            ("unused", "echo never"),
+        ];
+        let config = Config {
+            custom: custom
+                .iter()
+                .map(|(name, command)| (name.to_string(), command.to_string()))
+                .collect(),
+            custom_timeout_ms: 200,
+            jj_format: "$name ${custom.version} ${custom.broken} ${custom.slow}".into(),
+            ..Config::default()
+        };
+        let started = Instant::now();
+        let values = run(&config, &std::env::temp_dir()).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let expected = [("version", "v1.2"), ("broken", ""), ("slow", "")];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(values, expected);
+
+        let unused = Config {
+            jj_format: "$name".into(),
+            ..config
+        };
+        assert_eq!(run(&unused, &std::env::temp_dir()), None);
+    }
+
+    #[test]
+    fn test_is_used() {
+        let config = Config {
+            jj_format: "$name ${custom.foobar}".into(),
+            ..Config::default()
+        };
+        assert!(is_used(&config, "foobar"));
+        assert!(!is_used(&config, "foo"));
+    }
+
+    #[test]
+    fn test_wait_large_output() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let child = shell(
+            "head -c 200000 /dev/zero | tr '\\0' x",
+            &std::env::temp_dir(),
+        )
+        .spawn()
+        .unwrap();
+        let (status, out) = wait(child, deadline).unwrap();
+        assert!(status.success());
+        assert_eq!(out.len(), 200_000);
+    }
+}
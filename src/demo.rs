@@ -0,0 +1,201 @@
+//! Gallery of representative prompt states for `jj-starship demo`
+
+use crate::config::Config;
+#[cfg(feature = "git")]
+use crate::git::GitInfo;
+use crate::jj::JjInfo;
+use crate::output;
+use std::fmt::Write;
+
+/// Render each sample state with the active config, one labeled line apiece,
+/// under a heading so the made-up data isn't mistaken for a repo's
+pub fn gallery(config: &Config) -> String {
+    let mut out = String::from("Sample states (made-up data, not read from any repo):\n");
+    for (label, info) in jj_samples() {
+        let _ = writeln!(out, "{label:<22}{}", output::format_jj(&info, config));
+    }
+    #[cfg(feature = "git")]
+    for (label, info) in git_samples() {
+        let _ = writeln!(out, "{label:<22}{}", output::format_git(&info, config));
+    }
+    out
+}
+
+fn jj_samples() -> Vec<(&'static str, JjInfo)> {
+    let base = || JjInfo {
+        change_id: "kxqpmtzv".to_string(),
+        bookmark: Some("main".to_string()),
+        has_remote: true,
+        is_synced: true,
+        ..JjInfo::default()
+    };
+    vec![
+        ("jj clean", base()),
+        (
+            "jj no bookmark",
+            JjInfo {
+                bookmark: None,
+                has_remote: false,
+                ..base()
+            },
+        ),
+        (
+            "jj undescribed",
+            JjInfo {
+                empty_desc: true,
+                ..base()
+            },
+        ),
+        (
+            "jj unsynced",
+            JjInfo {
+                is_synced: false,
+                ..base()
+            },
+        ),
+        (
+            "jj conflicted",
+            JjInfo {
+                conflict: true,
+                ..base()
+            },
+        ),
+        (
+            "jj conflicts below",
+            JjInfo {
+                conflict_below: true,
+                ..base()
+            },
+        ),
+        (
+            "jj divergent",
+            JjInfo {
+                divergent: true,
+                divergent_commits: 2,
+                ..base()
+            },
+        ),
+        (
+            "jj tracking",
+            JjInfo {
+                bookmark: Some("feat/login".to_string()),
+                remote: Some("origin".to_string()),
+                is_synced: false,
+                ahead: 2,
+                behind: 1,
+                ..base()
+            },
+        ),
+        (
+            "jj sparse",
+            JjInfo {
+                sparse_patterns: 2,
+                ..base()
+            },
+        ),
+        (
+            "jj large new file",
+            JjInfo {
+                large_files: 1,
+                ..base()
+            },
+        ),
+        (
+            "jj large repo",
+            JjInfo {
+                status_skipped: true,
+                ..base()
+            },
+        ),
+        // Colocated repos render through the JJ path
+        (
+            "jj colocated",
+            JjInfo {
+                empty_desc: true,
+                is_synced: false,
+                ..base()
+            },
+        ),
+    ]
+}
+
+#[cfg(feature = "git")]
+fn git_samples() -> Vec<(&'static str, GitInfo)> {
+    let base = || GitInfo {
+        branch: Some("main".to_string()),
+        head_short: "3f2a1b9c".to_string(),
+        ..GitInfo::default()
+    };
+    vec![
+        ("git clean", base()),
+        (
+            "git dirty",
+            GitInfo {
+                staged: 2,
+                modified: 3,
+                untracked: 1,
+                deleted: 1,
+                renamed: 1,
+                ..base()
+            },
+        ),
+        (
+            "git ahead/behind",
+            GitInfo {
+                ahead: 3,
+                behind: 2,
+                ..base()
+            },
+        ),
+        (
+            "git detached",
+            GitInfo {
+                branch: None,
+                ..base()
+            },
+        ),
+        // HEAD is detached while a rebase stops on conflicts
+        (
+            "git mid-rebase",
+            GitInfo {
+                branch: None,
+                staged: 1,
+                conflicted: 2,
+                operation: Some("REBASING".into()),
+                step: 3,
+                total: 7,
+                ..base()
+            },
+        ),
+        (
+            "git bisecting",
+            GitInfo {
+                branch: None,
+                operation: Some("BISECTING".into()),
+                bisect_steps: Some(4),
+                ..base()
+            },
+        ),
+        (
+            "git many untracked",
+            GitInfo {
+                untracked: 5000,
+                ..base()
+            },
+        ),
+        (
+            "git large repo",
+            GitInfo {
+                status_skipped: true,
+                ..base()
+            },
+        ),
+        (
+            "git empty repo",
+            GitInfo {
+                head_short: "empty".to_string(),
+                ..base()
+            },
+        ),
+    ]
+}
@@ -1,6 +1,14 @@
 //! Repo type detection - walks up from cwd to find .jj or .git
 
-use std::path::{Path, PathBuf};
+use crate::config::{self, Config};
+use crate::glob;
+use crate::log;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Marker file in a repo root that disables jj-starship for that repo
+pub const IGNORE_MARKER: &str = ".jj-starship-ignore";
 
 /// Type of repository detected
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -15,20 +23,164 @@ pub enum RepoType {
     None,
 }
 
+impl RepoType {
+    /// Stable name used in machine-readable output
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Jj => "jj",
+            Self::JjColocated => "jj-colocated",
+            Self::Git => "git",
+            Self::None => "none",
+        }
+    }
+
+    /// Exit status for `detect --kind`; 0 is left out so plain `&&` checks fail loudly
+    #[cfg(feature = "cli")]
+    pub const fn exit_code(self) -> u8 {
+        match self {
+            Self::Jj => 10,
+            Self::JjColocated => 11,
+            Self::Git => 12,
+            Self::None => 1,
+        }
+    }
+
+    /// Parse a name produced by `as_str`
+    #[cfg(feature = "cli")]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "jj" => Some(Self::Jj),
+            "jj-colocated" => Some(Self::JjColocated),
+            "git" => Some(Self::Git),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+
 /// Result of repo detection
 #[derive(Debug)]
 pub struct DetectResult {
     pub repo_type: RepoType,
     pub repo_root: Option<PathBuf>,
+    /// The repo's metadata directory (`.jj` or `.git`) when the start path is
+    /// inside it rather than in the working tree
+    pub internals: Option<&'static str>,
+}
+
+/// Version tag for continuation data
+#[cfg(feature = "cli")]
+const CONTINUATION_VERSION: &str = "v1";
+
+impl DetectResult {
+    /// Continuation data printed by `prompt --instant` for `prompt --complete`
+    /// Format: `v1<TAB>{repo_type}<TAB>{repo_root}`
+    #[cfg(feature = "cli")]
+    pub fn continuation(&self) -> Option<String> {
+        let root = self.repo_root.as_ref()?;
+        Some(format!(
+            "{CONTINUATION_VERSION}\t{}\t{}",
+            self.repo_type.as_str(),
+            root.display()
+        ))
+    }
+
+    /// Rebuild a detection result from continuation data, skipping the walk
+    /// `cwd` is only placed against the root, for `internals`
+    #[cfg(feature = "cli")]
+    pub fn from_continuation(data: &str, cwd: &Path) -> Option<Self> {
+        let mut parts = data.trim_end_matches(['\r', '\n']).splitn(3, '\t');
+        if parts.next()? != CONTINUATION_VERSION {
+            return None;
+        }
+        let repo_type = RepoType::from_name(parts.next()?)?;
+        let repo_root = PathBuf::from(parts.next()?);
+        (repo_type != RepoType::None && repo_root.is_dir()).then(|| Self {
+            repo_type,
+            internals: internals(cwd, &repo_root),
+            repo_root: Some(repo_root),
+        })
+    }
+}
+
+/// The shell's `$PWD` when it names the current directory, keeping the
+/// symlinked spelling that `current_dir()` resolves away
+pub fn logical_cwd() -> Option<PathBuf> {
+    let pwd = PathBuf::from(env::var_os("PWD")?);
+    let same = pwd.is_absolute()
+        && fs::canonicalize(&pwd).ok()? == fs::canonicalize(env::current_dir().ok()?).ok()?;
+    same.then_some(pwd)
+}
+
+/// Where the walk starts: `start`, in its plain spelling on Windows
+fn start_dir(start: &Path) -> PathBuf {
+    if cfg!(windows) {
+        simplify(start)
+    } else {
+        start.to_path_buf()
+    }
+}
+
+/// Bounds on the walk up from the start directory; 0 means no limit
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Limits {
+    /// Parent directories walked above the start (`detect.max_depth`)
+    pub max_depth: usize,
+    /// Filesystem checks made in total (`detect.fs_budget`)
+    pub fs_budget: usize,
+}
+
+impl Limits {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_depth: config.detect_max_depth,
+            fs_budget: config.detect_fs_budget,
+        }
+    }
+
+    /// Directories the walk may look at, the start included
+    const fn levels(self) -> usize {
+        match self.max_depth {
+            0 => usize::MAX,
+            depth => depth.saturating_add(1),
+        }
+    }
+}
+
+/// Filesystem checks left under `Limits::fs_budget`
+struct Budget(Option<usize>);
+
+impl Budget {
+    fn new(limits: Limits) -> Self {
+        Self((limits.fs_budget > 0).then_some(limits.fs_budget))
+    }
+
+    /// Run one check, or None once the budget is spent
+    fn check(&mut self, check: impl FnOnce() -> bool) -> Option<bool> {
+        if let Some(left) = &mut self.0 {
+            if *left == 0 {
+                log::debug!("detection stopped: fs budget spent");
+                return None;
+            }
+            *left -= 1;
+        }
+        Some(check())
+    }
 }
 
 /// Detect repo type by walking up from the given path
-pub fn detect(start: &Path) -> DetectResult {
-    let mut current = start.to_path_buf();
+pub fn detect(start: &Path, limits: Limits) -> DetectResult {
+    let mut current = start_dir(start);
+    let mut budget = Budget::new(limits);
 
-    loop {
-        let has_jj = current.join(".jj").is_dir();
-        let has_git = current.join(".git").exists(); // can be file (worktree) or dir
+    for _ in 0..limits.levels() {
+        let (Some(has_jj), Some(has_git)) = (
+            budget.check(|| current.join(".jj").is_dir()),
+            // can be file (worktree) or dir
+            budget.check(|| current.join(".git").exists()),
+        ) else {
+            break;
+        };
 
         let repo_type = match (has_jj, has_git) {
             (true, true) => RepoType::JjColocated,
@@ -40,6 +192,7 @@ pub fn detect(start: &Path) -> DetectResult {
         if repo_type != RepoType::None {
             return DetectResult {
                 repo_type,
+                internals: internals(start, &current),
                 repo_root: Some(current),
             };
         }
@@ -53,10 +206,297 @@ pub fn detect(start: &Path) -> DetectResult {
     DetectResult {
         repo_type: RepoType::None,
         repo_root: None,
+        internals: None,
+    }
+}
+
+/// Whether another repo (JJ or Git) encloses the one at `repo_root`, like a
+/// vendored checkout inside a project; detection always picks the nearest root
+/// The walk above the root gets `limits` of its own, as detection did
+pub fn is_nested(repo_root: &Path, limits: Limits) -> bool {
+    let mut budget = Budget::new(limits);
+    repo_root
+        .ancestors()
+        .take(limits.levels())
+        .skip(1)
+        .map_while(|dir| {
+            let has_jj = budget.check(|| dir.join(".jj").is_dir())?;
+            Some(has_jj || budget.check(|| dir.join(".git").exists())?)
+        })
+        .any(|found| found)
+}
+
+/// Nearest directory above `repo_root` holding `.git`, walked under `limits`
+pub fn enclosing_git(repo_root: &Path, limits: Limits) -> Option<&Path> {
+    let mut budget = Budget::new(limits);
+    repo_root
+        .ancestors()
+        .take(limits.levels())
+        .skip(1)
+        .map_while(|dir| Some((dir, budget.check(|| dir.join(".git").exists())?)))
+        .find_map(|(dir, found)| found.then_some(dir))
+}
+
+/// URL of the `origin` remote, read from the Git config file of a Git repo
+/// (or a JJ repo's backing Git store) without opening the repo
+pub fn origin_url(repo_root: &Path) -> Option<String> {
+    let dot_git = repo_root.join(".git");
+    let git_dir = if dot_git.is_dir() {
+        dot_git
+    } else if dot_git.is_file() {
+        // Worktrees: `gitdir: <path>`, sharing the config of `commondir`
+        let text = fs::read_to_string(&dot_git).ok()?;
+        let git_dir = repo_root.join(text.strip_prefix("gitdir:")?.trim());
+        match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common) => git_dir.join(common.trim()),
+            Err(_) => git_dir,
+        }
+    } else {
+        // Secondary workspaces store the repo location in a file
+        let repo_path = repo_root.join(".jj").join("repo");
+        let repo_dir = if repo_path.is_file() {
+            repo_root
+                .join(".jj")
+                .join(fs::read_to_string(&repo_path).ok()?.trim())
+        } else {
+            repo_path
+        };
+        let store = repo_dir.join("store");
+        store.join(fs::read_to_string(store.join("git_target")).ok()?.trim())
+    };
+    remote_url(&fs::read_to_string(git_dir.join("config")).ok()?, "origin")
+}
+
+/// `url` of `[remote "{name}"]` in Git config file text
+fn remote_url(config: &str, name: &str) -> Option<String> {
+    let header = format!("[remote \"{name}\"]");
+    let mut in_remote = false;
+    for line in config.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_remote = line == header;
+            continue;
+        }
+        match line.split_once('=') {
+            Some((key, value)) if in_remote && key.trim() == "url" => {
+                return Some(value.trim().to_string());
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Which metadata directory of the repo at `root` holds `start`, if any
+/// A cwd in `.git/refs` or `.jj/repo/store` finds the repo one level above its
+/// metadata, so the walk alone would report the working tree's state
+fn internals(start: &Path, root: &Path) -> Option<&'static str> {
+    let start = start_dir(start);
+    match start.strip_prefix(root).ok()?.components().next()? {
+        Component::Normal(name) if name == ".jj" => Some(".jj"),
+        Component::Normal(name) if name == ".git" => Some(".git"),
+        _ => None,
+    }
+}
+
+/// Plain spelling of a Windows verbatim path (`\\?\C:\src` -> `C:\src`,
+/// `\\?\UNC\server\share` -> `\\server\share`), so repo roots match
+/// `slow_paths`, `disable_in` globs and cached roots written the usual way
+/// Volume GUID paths have no plain form and are kept as they are
+fn simplify(path: &Path) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        return PathBuf::from(format!(r"\\{rest}"));
+    }
+    match text.strip_prefix(r"\\?\") {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => PathBuf::from(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Walk up to the nearest `.jj` root (for `Jj`) or `.git` root (for `Git`),
+/// passing over the other kind of repo (`--repo-type`)
+pub fn detect_forced(start: &Path, repo_type: RepoType, limits: Limits) -> DetectResult {
+    let start = start_dir(start);
+    let mut budget = Budget::new(limits);
+    let is_root = |dir: &Path| match repo_type {
+        RepoType::Jj | RepoType::JjColocated => dir.join(".jj").is_dir(),
+        RepoType::Git => dir.join(".git").exists(),
+        RepoType::None => false,
+    };
+    let root = start
+        .ancestors()
+        .take(limits.levels())
+        .map_while(|dir| Some((dir, budget.check(|| is_root(dir))?)))
+        .find_map(|(dir, found)| found.then_some(dir));
+    let Some(root) = root else {
+        return DetectResult {
+            repo_type: RepoType::None,
+            repo_root: None,
+            internals: None,
+        };
+    };
+    let repo_type = match repo_type {
+        RepoType::Git => RepoType::Git,
+        _ if root.join(".git").exists() => RepoType::JjColocated,
+        _ => RepoType::Jj,
+    };
+    DetectResult {
+        repo_type,
+        internals: internals(&start, root),
+        repo_root: Some(root.to_path_buf()),
+    }
+}
+
+/// Detect repo type, treating disabled repos as not in a repo
+/// `forced` restricts detection to one backend (`--repo-type`)
+pub fn detect_enabled(start: &Path, config: &Config, forced: Option<RepoType>) -> DetectResult {
+    let limits = Limits::from_config(config);
+    let result = match forced {
+        Some(repo_type) => detect_forced(start, repo_type, limits),
+        None => detect(start, limits),
+    };
+    match &result.repo_root {
+        Some(root) if is_disabled(root, &config.disable_in) => DetectResult {
+            repo_type: RepoType::None,
+            repo_root: None,
+            internals: None,
+        },
+        _ => result,
     }
 }
 
-/// Returns true if in any repo (for `jj-starship detect` command)
-pub fn in_repo(start: &Path) -> bool {
-    detect(start).repo_type != RepoType::None
+/// Returns true if in any enabled repo (for `jj-starship detect` command)
+#[cfg(feature = "cli")]
+pub fn in_repo(start: &Path, config: &Config, forced: Option<RepoType>) -> bool {
+    detect_enabled(start, config, forced).repo_type != RepoType::None
+}
+
+/// Whether a repo opted out via the marker file or a `JJ_STARSHIP_DISABLE_IN` glob
+/// Globs match the repo root or any of its ancestors; a leading `~` is `$HOME`
+fn is_disabled(repo_root: &Path, disable_in: &[String]) -> bool {
+    if repo_root.join(IGNORE_MARKER).exists() {
+        return true;
+    }
+    if disable_in.is_empty() {
+        return false;
+    }
+
+    let home = config::home_dir();
+    disable_in.iter().any(|pattern| {
+        let pattern = match (pattern.strip_prefix('~'), &home) {
+            (Some(rest), Some(home)) => format!("{}{rest}", home.display()),
+            _ => pattern.clone(),
+        };
+        repo_root
+            .ancestors()
+            .any(|dir| glob::matches(&pattern, &dir.to_string_lossy()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_internals() {
+        let root = Path::new("/src/repo");
+        assert_eq!(
+            internals(Path::new("/src/repo/.git/refs/heads"), root),
+            Some(".git")
+        );
+        assert_eq!(internals(Path::new("/src/repo/.jj"), root), Some(".jj"));
+        assert_eq!(internals(Path::new("/src/repo/src/.github"), root), None);
+        assert_eq!(internals(Path::new("/src/repo"), root), None);
+    }
+
+    /// `{tmp}/outer` holding `.git`, with `a/b/inner/.git` below it
+    fn nested_tree(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("jj-starship-{name}-{}", std::process::id()));
+        fs::create_dir_all(dir.join("outer/.git")).unwrap();
+        fs::create_dir_all(dir.join("outer/a/b/inner/.git")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_detect_limits() {
+        let dir = nested_tree("detect-limits");
+        let start = dir.join("outer/a/b");
+        let found = |limits| detect(&start, limits).repo_root;
+        assert_eq!(found(Limits::default()), Some(dir.join("outer")));
+        // The repo is two levels up
+        let depth = |max_depth| Limits {
+            max_depth,
+            fs_budget: 0,
+        };
+        assert_eq!(found(depth(1)), None);
+        assert_eq!(found(depth(2)), Some(dir.join("outer")));
+        // Two checks per directory: b, a, then outer
+        let budget = |fs_budget| Limits {
+            max_depth: 0,
+            fs_budget,
+        };
+        assert_eq!(found(budget(5)), None);
+        assert_eq!(found(budget(6)), Some(dir.join("outer")));
+        let forced = |limits| detect_forced(&start, RepoType::Git, limits).repo_root;
+        assert_eq!(forced(depth(1)), None);
+        assert_eq!(forced(budget(3)), Some(dir.join("outer")));
+        assert_eq!(forced(budget(2)), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_nested_limits() {
+        let dir = nested_tree("nested-limits");
+        let inner = dir.join("outer/a/b/inner");
+        let depth = |max_depth| Limits {
+            max_depth,
+            fs_budget: 0,
+        };
+        let budget = |fs_budget| Limits {
+            max_depth: 0,
+            fs_budget,
+        };
+        assert!(is_nested(&inner, Limits::default()));
+        assert!(!is_nested(&inner, depth(2)));
+        assert!(is_nested(&inner, depth(3)));
+        // b and a take two checks each; outer's `.git` is the sixth
+        assert!(!is_nested(&inner, budget(5)));
+        assert!(is_nested(&inner, budget(6)));
+
+        assert_eq!(
+            enclosing_git(&inner, Limits::default()),
+            Some(dir.join("outer").as_path())
+        );
+        assert_eq!(enclosing_git(&inner, depth(2)), None);
+        assert_eq!(enclosing_git(&inner, budget(2)), None);
+        assert_eq!(
+            enclosing_git(&inner, budget(3)),
+            Some(dir.join("outer").as_path())
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remote_url() {
+        let config = "[core]\n\tbare = false\n[remote \"upstream\"]\n\turl = https://example.com/up.git\n[remote \"origin\"]\n\turl = git@example.com:org/mono.git\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n";
+        assert_eq!(
+            remote_url(config, "origin").as_deref(),
+            Some("git@example.com:org/mono.git")
+        );
+        assert_eq!(remote_url("[core]\n\tbare = false\n", "origin"), None);
+    }
+
+    #[test]
+    fn test_simplify() {
+        let plain = |path: &str| simplify(Path::new(path)).to_string_lossy().into_owned();
+        assert_eq!(plain(r"\\?\C:\src\repo"), r"C:\src\repo");
+        assert_eq!(plain(r"\\?\UNC\server\share\repo"), r"\\server\share\repo");
+        assert_eq!(plain(r"\\server\share\repo"), r"\\server\share\repo");
+        assert_eq!(
+            plain(r"\\?\Volume{b75e2c83}\repo"),
+            r"\\?\Volume{b75e2c83}\repo"
+        );
+    }
 }
@@ -0,0 +1,124 @@
+//! Shell exports of collected repo data for `jj-starship env`
+//! Every variable is always printed (empty or `0` when it doesn't apply), so
+//! evaluating the output in a new directory never leaves stale values behind
+
+use crate::detect::RepoType;
+#[cfg(feature = "git")]
+use crate::git::GitInfo;
+use crate::jj::JjInfo;
+#[cfg(feature = "cli")]
+use std::fmt::Write;
+use std::path::Path;
+
+/// Values exported as `JJ_STARSHIP_*`
+#[derive(Debug)]
+pub struct Exports {
+    pub repo_type: RepoType,
+    pub repo_root: String,
+    /// Bookmark or branch name
+    pub branch: String,
+    /// JJ change ID
+    pub change_id: String,
+    /// Git short commit hash
+    pub commit: String,
+    pub dirty: bool,
+    pub conflict: bool,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+impl Exports {
+    /// Outside any repo: type `none`, everything else empty
+    pub const fn none() -> Self {
+        Self {
+            repo_type: RepoType::None,
+            repo_root: String::new(),
+            branch: String::new(),
+            change_id: String::new(),
+            commit: String::new(),
+            dirty: false,
+            conflict: false,
+            ahead: 0,
+            behind: 0,
+        }
+    }
+
+    pub fn jj(repo_type: RepoType, repo_root: &Path, info: &JjInfo) -> Self {
+        Self {
+            repo_type,
+            repo_root: repo_root.to_string_lossy().into_owned(),
+            branch: info.bookmark.clone().unwrap_or_default(),
+            change_id: info.change_id.clone(),
+            dirty: info.has_changes,
+            conflict: info.conflict,
+            ahead: info.ahead,
+            behind: info.behind,
+            ..Self::none()
+        }
+    }
+
+    #[cfg(feature = "git")]
+    pub fn git(repo_root: &Path, info: &GitInfo) -> Self {
+        Self {
+            repo_type: RepoType::Git,
+            repo_root: repo_root.to_string_lossy().into_owned(),
+            branch: info.branch.clone().unwrap_or_default(),
+            commit: info.head_short.clone(),
+            dirty: info.dirty || info.changed() > info.conflicted,
+            conflict: info.conflicted > 0,
+            ahead: info.ahead,
+            behind: info.behind,
+            ..Self::none()
+        }
+    }
+
+    /// `export NAME='value'` lines, valid in POSIX shells and fish
+    #[cfg(feature = "cli")]
+    pub fn script(&self) -> String {
+        let vars = [
+            ("REPO_TYPE", self.repo_type.as_str().to_string()),
+            ("REPO_ROOT", self.repo_root.clone()),
+            ("BRANCH", self.branch.clone()),
+            ("CHANGE_ID", self.change_id.clone()),
+            ("COMMIT", self.commit.clone()),
+            ("DIRTY", u8::from(self.dirty).to_string()),
+            ("CONFLICT", u8::from(self.conflict).to_string()),
+            ("AHEAD", self.ahead.to_string()),
+            ("BEHIND", self.behind.to_string()),
+        ];
+        let mut out = String::new();
+        for (name, value) in vars {
+            let _ = writeln!(out, "export JJ_STARSHIP_{name}={}", quote(&value));
+        }
+        out
+    }
+}
+
+/// Single-quote for the shell; embedded quotes become `'\''`
+#[cfg(feature = "cli")]
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn test_script() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("it's".into()),
+            conflict: true,
+            ..Default::default()
+        };
+        let script = Exports::jj(RepoType::Jj, Path::new("/src/repo"), &info).script();
+        assert!(script.starts_with("export JJ_STARSHIP_REPO_TYPE='jj'\n"));
+        assert!(script.contains("export JJ_STARSHIP_BRANCH='it'\\''s'\n"));
+        assert!(script.contains("export JJ_STARSHIP_CHANGE_ID='yzxv1234'\n"));
+        assert!(script.contains("export JJ_STARSHIP_COMMIT=''\n"));
+        assert!(script.contains("export JJ_STARSHIP_CONFLICT='1'\n"));
+        assert_eq!(script.lines().count(), 9);
+    }
+}
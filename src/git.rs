@@ -1,11 +1,52 @@
 //! Git repository info collection using git2
 
+use crate::config::{Config, Detail, Segment};
+use crate::detect::{self, Limits};
 use crate::error::{Error, Result};
-use git2::{Repository, Status, StatusOptions};
-use std::path::Path;
+use crate::json;
+use crate::log;
+use crate::message;
+use crate::timing::Timings;
+use git2::{
+    AttrCheckFlags, AttrValue, ErrorClass, ErrorCode, Repository, RepositoryState, Status,
+    StatusOptions,
+};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Commits ahead of and behind one remote's copy of the branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteCounts {
+    pub remote: String,
+    pub ahead: usize,
+    pub behind: usize,
+}
+
+impl RemoteCounts {
+    /// `{"remote", "ahead", "behind"}` object
+    fn to_json(&self) -> json::Value {
+        json::Value::Object(vec![
+            ("remote".into(), self.remote.as_str().into()),
+            ("ahead".into(), self.ahead.into()),
+            ("behind".into(), self.behind.into()),
+        ])
+    }
+
+    fn from_json(value: &json::Value) -> Option<Self> {
+        Some(Self {
+            remote: value.get_str("remote")?,
+            ahead: value.get_usize("ahead"),
+            behind: value.get_usize("behind"),
+        })
+    }
+}
 
 /// Git repository status info
-#[derive(Debug)]
+#[derive(Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct GitInfo {
     /// Branch name (None if detached)
     pub branch: Option<String>,
@@ -21,64 +62,628 @@ pub struct GitInfo {
     pub deleted: usize,
     /// Count of conflicted files
     pub conflicted: usize,
+    /// Count of renamed files (index or worktree)
+    pub renamed: usize,
+    /// Count of files whose type changed (index or worktree)
+    pub typechanged: usize,
+    /// Something changed, from the uncategorized `git_simple_dirty` check
+    /// (the counts stay 0)
+    pub dirty: bool,
     /// Commits ahead of upstream
     pub ahead: usize,
     /// Commits behind upstream
     pub behind: usize,
+    /// Name of the superproject when the repo is a submodule checkout
+    pub superproject: Option<String>,
+    /// Another repo encloses this one (set by the caller with `nested`)
+    pub nested: bool,
+    /// Ahead/behind against each `compare_remotes` remote that has the branch
+    /// (or a default branch)
+    pub remotes: Vec<RemoteCounts>,
+    /// Default branch (`origin/HEAD`'s target, without the remote), only
+    /// collected for the `base` segment
+    pub base: Option<String>,
+    /// Commits on HEAD that aren't on the default branch
+    pub base_ahead: usize,
+    /// Action of the latest HEAD reflog entry (`checkout`, `commit`), only
+    /// collected for the `action` segment
+    pub last_action: Option<String>,
+    /// Time of that entry, in seconds since the Unix epoch
+    pub action_time: usize,
+    /// First line of the commit message
+    pub description: Option<String>,
+    /// Name of the linked worktree, when not in the main one
+    pub workspace: Option<String>,
+    /// Branch is the default branch (only collected with `default_branch`)
+    pub on_default: bool,
+    /// File status skipped by the large-repo policy
+    pub status_skipped: bool,
+    /// Commit message starts with one of `wip_prefixes`
+    pub wip: bool,
+    /// Conventional-commit type of the message (`feat`, `fix`, ...)
+    pub commit_type: Option<String>,
+    /// In-progress operation (`REBASING`, `MERGING`, ...)
+    pub operation: Option<String>,
+    /// Current step of the operation, 1-based (0 when unknown)
+    pub step: usize,
+    /// Steps in total; for cherry-pick/revert sequences, picks left (`step` is 0)
+    pub total: usize,
+    /// Rough bisect steps left, as `git bisect` estimates them
+    pub bisect_steps: Option<usize>,
+    /// Author email differs from the configured `user.email` (only collected with `foreign_author`)
+    pub foreign_author: bool,
+    /// Owned by another user and not in `trusted_paths`, so left unread
+    pub untrusted: bool,
+    /// Paths the status scan couldn't read (permissions), so counts are partial
+    pub unreadable: usize,
+    /// Files with a Git LFS lock held by the user (only collected with `git_lfs_locks`)
+    pub lfs_locked: usize,
+    /// Modified `lockable` files the user holds no Git LFS lock on
+    pub lfs_unlocked: usize,
 }
 
-/// Collect Git repo info from the given path
-pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
-    let repo = Repository::open(repo_root).map_err(|e| Error::Git(format!("open: {e}")))?;
+impl GitInfo {
+    /// Total of the per-category file counts (a file both staged and
+    /// modified counts twice)
+    pub const fn changed(&self) -> usize {
+        self.staged
+            + self.modified
+            + self.untracked
+            + self.deleted
+            + self.conflicted
+            + self.renamed
+            + self.typechanged
+    }
 
-    // Status counts - compute once for both empty and normal repos
+    /// Any changed files, counting untracked ones only when asked
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    pub const fn is_dirty(&self, include_untracked: bool) -> bool {
+        let ignored = if include_untracked { 0 } else { self.untracked };
+        self.dirty || self.changed() > ignored
+    }
+
+    /// Serialize as a JSON object (field names match the struct)
+    pub fn to_json(&self) -> json::Value {
+        json::Value::Object(vec![
+            ("branch".into(), self.branch.as_deref().into()),
+            ("head_short".into(), self.head_short.as_str().into()),
+            ("staged".into(), self.staged.into()),
+            ("modified".into(), self.modified.into()),
+            ("untracked".into(), self.untracked.into()),
+            ("deleted".into(), self.deleted.into()),
+            ("conflicted".into(), self.conflicted.into()),
+            ("renamed".into(), self.renamed.into()),
+            ("typechanged".into(), self.typechanged.into()),
+            ("dirty".into(), self.dirty.into()),
+            ("ahead".into(), self.ahead.into()),
+            ("behind".into(), self.behind.into()),
+            ("superproject".into(), self.superproject.as_deref().into()),
+            ("nested".into(), self.nested.into()),
+            (
+                "remotes".into(),
+                json::Value::Array(self.remotes.iter().map(RemoteCounts::to_json).collect()),
+            ),
+            ("base".into(), self.base.as_deref().into()),
+            ("base_ahead".into(), self.base_ahead.into()),
+            ("last_action".into(), self.last_action.as_deref().into()),
+            ("action_time".into(), self.action_time.into()),
+            ("description".into(), self.description.as_deref().into()),
+            ("workspace".into(), self.workspace.as_deref().into()),
+            ("on_default".into(), self.on_default.into()),
+            ("status_skipped".into(), self.status_skipped.into()),
+            ("wip".into(), self.wip.into()),
+            ("commit_type".into(), self.commit_type.as_deref().into()),
+            ("operation".into(), self.operation.as_deref().into()),
+            ("step".into(), self.step.into()),
+            ("total".into(), self.total.into()),
+            ("bisect_steps".into(), self.bisect_steps.into()),
+            ("foreign_author".into(), self.foreign_author.into()),
+            ("untrusted".into(), self.untrusted.into()),
+            ("unreadable".into(), self.unreadable.into()),
+            ("lfs_locked".into(), self.lfs_locked.into()),
+            ("lfs_unlocked".into(), self.lfs_unlocked.into()),
+        ])
+    }
+
+    /// Deserialize from a JSON object (field names match the struct); only
+    /// `head_short` is required
+    pub fn from_json(value: &json::Value) -> Option<Self> {
+        Some(Self {
+            branch: value.get_str("branch"),
+            head_short: value.get_str("head_short")?,
+            staged: value.get_usize("staged"),
+            modified: value.get_usize("modified"),
+            untracked: value.get_usize("untracked"),
+            deleted: value.get_usize("deleted"),
+            conflicted: value.get_usize("conflicted"),
+            renamed: value.get_usize("renamed"),
+            typechanged: value.get_usize("typechanged"),
+            dirty: value.get_bool("dirty"),
+            ahead: value.get_usize("ahead"),
+            behind: value.get_usize("behind"),
+            superproject: value.get_str("superproject"),
+            nested: value.get_bool("nested"),
+            remotes: match value.get("remotes") {
+                Some(json::Value::Array(items)) => {
+                    items.iter().filter_map(RemoteCounts::from_json).collect()
+                }
+                _ => Vec::new(),
+            },
+            base: value.get_str("base"),
+            base_ahead: value.get_usize("base_ahead"),
+            last_action: value.get_str("last_action"),
+            action_time: value.get_usize("action_time"),
+            description: value.get_str("description"),
+            workspace: value.get_str("workspace"),
+            on_default: value.get_bool("on_default"),
+            status_skipped: value.get_bool("status_skipped"),
+            wip: value.get_bool("wip"),
+            commit_type: value.get_str("commit_type"),
+            operation: value.get_str("operation"),
+            step: value.get_usize("step"),
+            total: value.get_usize("total"),
+            bisect_steps: value.get("bisect_steps").and_then(json::Value::as_usize),
+            foreign_author: value.get_bool("foreign_author"),
+            untrusted: value.get_bool("untrusted"),
+            unreadable: value.get_usize("unreadable"),
+            lfs_locked: value.get_usize("lfs_locked"),
+            lfs_unlocked: value.get_usize("lfs_unlocked"),
+        })
+    }
+}
+
+/// Status scan preferences honored from git config
+#[allow(clippy::struct_excessive_bools)]
+struct StatusPrefs {
+    /// `bash.showDirtyState` (default true)
+    dirty_state: bool,
+    /// `status.showUntrackedFiles != no` and `bash.showUntrackedFiles` (default true)
+    untracked: bool,
+    /// `status.showUntrackedFiles = all`
+    recurse_untracked: bool,
+    /// `core.fsmonitor` is set (builtin daemon or hook such as watchman)
+    fsmonitor: bool,
+    /// `core.untrackedCache = true`, which only `git status` itself uses
+    untracked_cache: bool,
+    /// Count changed submodules (`git.submodules`, not a git setting)
+    submodules: bool,
+}
+
+impl StatusPrefs {
+    /// Git's defaults, for repos whose config can't be read
+    const DEFAULT: Self = Self {
+        dirty_state: true,
+        untracked: true,
+        recurse_untracked: false,
+        fsmonitor: false,
+        untracked_cache: false,
+        submodules: false,
+    };
+
+    fn from_config(repo: &Repository) -> Self {
+        let Ok(cfg) = repo.config() else {
+            return Self::DEFAULT;
+        };
+
+        let show_untracked = cfg
+            .get_string("status.showUntrackedFiles")
+            .map(|v| v.to_ascii_lowercase())
+            .ok();
+        let untracked = !matches!(
+            show_untracked.as_deref(),
+            Some("no" | "false" | "off" | "0")
+        ) && cfg.get_bool("bash.showUntrackedFiles").unwrap_or(true);
+
+        Self {
+            dirty_state: cfg.get_bool("bash.showDirtyState").unwrap_or(true),
+            untracked,
+            recurse_untracked: show_untracked.as_deref() == Some("all"),
+            fsmonitor: cfg.get_string("core.fsmonitor").is_ok_and(|v| {
+                !matches!(
+                    v.to_ascii_lowercase().as_str(),
+                    "" | "false" | "no" | "off" | "0"
+                )
+            }),
+            untracked_cache: cfg.get_bool("core.untrackedCache").unwrap_or(false),
+            submodules: false,
+        }
+    }
+}
+
+/// File status counts
+#[derive(Debug, Default)]
+struct StatusCounts {
+    staged: usize,
+    modified: usize,
+    untracked: usize,
+    deleted: usize,
+    conflicted: usize,
+    renamed: usize,
+    typechanged: usize,
+    /// Paths git couldn't read and left out
+    unreadable: usize,
+}
+
+impl StatusCounts {
+    /// Count a tracked entry from its porcelain `XY` (index, worktree) codes
+    /// Renames/copies and type changes get their own counters, as in `git status`
+    fn add_porcelain(&mut self, x: u8, y: u8) {
+        match x {
+            b'.' => {}
+            b'R' | b'C' => self.renamed += 1,
+            b'T' => self.typechanged += 1,
+            _ => self.staged += 1,
+        }
+        match y {
+            b'M' => self.modified += 1,
+            b'T' => self.typechanged += 1,
+            b'D' => self.deleted += 1,
+            b'R' | b'C' => self.renamed += 1,
+            _ => {}
+        }
+    }
+
+    /// Info carrying just these counts, for the caller to fill in the rest
+    fn into_info(self) -> GitInfo {
+        GitInfo {
+            staged: self.staged,
+            modified: self.modified,
+            untracked: self.untracked,
+            deleted: self.deleted,
+            conflicted: self.conflicted,
+            renamed: self.renamed,
+            typechanged: self.typechanged,
+            unreadable: self.unreadable,
+            ..GitInfo::default()
+        }
+    }
+}
+
+/// Full working-tree status scan via libgit2
+/// With a cap, untracked files are counted by `count_untracked` instead so
+/// enumeration can stop early
+fn scan_status(
+    repo: &Repository,
+    repo_root: &Path,
+    prefs: &StatusPrefs,
+    max_untracked: usize,
+) -> std::result::Result<StatusCounts, git2::Error> {
+    let bounded = prefs.untracked && max_untracked > 0;
+    let capped = bounded
+        .then(|| count_untracked(repo_root, prefs.recurse_untracked, max_untracked))
+        .flatten();
+    log::debug!("libgit2 status scan (bounded: {})", capped.is_some());
     let mut opts = StatusOptions::new();
-    opts.include_untracked(true)
-        .recurse_untracked_dirs(false)
+    opts.include_untracked(prefs.untracked && capped.is_none())
+        .recurse_untracked_dirs(prefs.recurse_untracked)
         .include_ignored(false)
-        .exclude_submodules(true);
+        .exclude_submodules(!prefs.submodules)
+        // Match `git status`, which detects staged renames by default
+        .renames_head_to_index(true);
 
-    let statuses = repo
-        .statuses(Some(&mut opts))
-        .map_err(|e| Error::Git(format!("statuses: {e}")))?;
+    let statuses = repo.statuses(Some(&mut opts))?;
 
-    let mut staged = 0usize;
-    let mut modified = 0usize;
-    let mut untracked = 0usize;
-    let mut deleted = 0usize;
-    let mut conflicted = 0usize;
+    let mut counts = StatusCounts::default();
 
     for entry in statuses.iter() {
         let status = entry.status();
 
         // Conflicted
         if status.contains(Status::CONFLICTED) {
-            conflicted += 1;
+            counts.conflicted += 1;
             continue;
         }
 
         // Staged (index changes)
-        if status.intersects(
-            Status::INDEX_NEW
-                | Status::INDEX_MODIFIED
-                | Status::INDEX_DELETED
-                | Status::INDEX_RENAMED
-                | Status::INDEX_TYPECHANGE,
-        ) {
-            staged += 1;
+        if status.intersects(Status::INDEX_NEW | Status::INDEX_MODIFIED | Status::INDEX_DELETED) {
+            counts.staged += 1;
+        }
+
+        // Renames and type changes, on either side
+        if status.intersects(Status::INDEX_RENAMED | Status::WT_RENAMED) {
+            counts.renamed += 1;
+        }
+        if status.intersects(Status::INDEX_TYPECHANGE | Status::WT_TYPECHANGE) {
+            counts.typechanged += 1;
         }
 
         // Working tree changes
-        if status.intersects(Status::WT_MODIFIED | Status::WT_TYPECHANGE) {
-            modified += 1;
+        if status.contains(Status::WT_MODIFIED) {
+            counts.modified += 1;
         }
         if status.contains(Status::WT_DELETED) {
-            deleted += 1;
+            counts.deleted += 1;
         }
         if status.contains(Status::WT_NEW) {
-            untracked += 1;
+            counts.untracked += 1;
         }
     }
+    if let Some(untracked) = capped {
+        counts.untracked = untracked;
+    }
+
+    Ok(counts)
+}
+
+/// Untracked entries listed by `git ls-files`, read up to one past `cap`, at
+/// which point git is stopped rather than left to walk the rest of the tree
+/// An untracked directory counts once unless `recurse`, as in `git status`
+/// Returns None if git can't be run, so the libgit2 scan counts them instead
+fn count_untracked(repo_root: &Path, recurse: bool, cap: usize) -> Option<usize> {
+    log::debug!("running git ls-files in {}", repo_root.display());
+    let mut git = git_in(repo_root);
+    git.args(["ls-files", "--others", "--exclude-standard", "-z"]);
+    if !recurse {
+        git.args(["--directory", "--no-empty-directory"]);
+    }
+    let mut child = git
+        .stdout(Stdio::piped())
+        .spawn()
+        .inspect_err(|e| log::warning!("git ls-files failed to start: {e}"))
+        .ok()?;
+    let count = count_entries(BufReader::new(child.stdout.take()?), cap);
+    if count > cap {
+        let _ = child.kill();
+    }
+    let status = child.wait().ok()?;
+    if count <= cap && !status.success() {
+        log::warning!("git ls-files exited with {status}");
+        return None;
+    }
+    Some(count)
+}
+
+/// NUL-terminated entries in `reader`, reading no further than entry `cap + 1`
+fn count_entries(reader: impl BufRead, cap: usize) -> usize {
+    reader
+        .split(0)
+        .take(cap.saturating_add(1))
+        .take_while(|entry| entry.is_ok())
+        .count()
+}
+
+/// Status via `git status`, so the configured fsmonitor answers instead of a
+/// full working-tree walk (libgit2 has no fsmonitor support), or so a repo
+/// owned by another user can be read with `trusted` (`safe.directory`)
+/// Returns None if git can't be run, so the caller falls back to libgit2
+fn cli_status(repo_root: &Path, prefs: &StatusPrefs, trusted: bool) -> Option<StatusCounts> {
+    let untracked = if !prefs.untracked {
+        "no"
+    } else if prefs.recurse_untracked {
+        "all"
+    } else {
+        "normal"
+    };
+
+    log::debug!("running git status in {}", repo_root.display());
+    let mut git = Command::new("git");
+    if trusted {
+        git.arg("-c")
+            .arg(format!("safe.directory={}", repo_root.display()));
+    }
+    let output = git
+        .arg("--no-optional-locks")
+        .arg("-C")
+        .arg(repo_root)
+        .args(["status", "--porcelain=v2", "-z"])
+        .arg(ignore_submodules(prefs))
+        .arg(format!("--untracked-files={untracked}"))
+        // `count_unreadable` reads the untranslated messages
+        .env("LC_ALL", "C")
+        .stdin(Stdio::null())
+        .output()
+        .inspect_err(|e| log::warning!("git status failed to start: {e}"))
+        .ok()?;
+
+    // Unreadable paths are reported and skipped; the rest of the status stands
+    let unreadable = count_unreadable(&output.stderr);
+    if !output.status.success() && unreadable == 0 {
+        log::warning!("git status exited with {}", output.status);
+        return None;
+    }
+    let mut counts = parse_porcelain_v2(&output.stdout);
+    counts.unreadable = unreadable;
+    Some(counts)
+}
+
+/// Submodules are left out unless `git.submodules` is set, then git's own
+/// `diff.ignoreSubmodules` and per-submodule `ignore` settings apply
+const fn ignore_submodules(prefs: &StatusPrefs) -> &'static str {
+    if prefs.submodules {
+        "--ignore-submodules=none"
+    } else {
+        "--ignore-submodules=all"
+    }
+}
+
+/// Whether anything changed, without categorizing; None when git can't tell
+/// (no commits yet, git missing), leaving it to the full scan
+/// `git diff --quiet` stops at the first changed file and answers from the
+/// index stat data (and fsmonitor); untracked directories are listed without
+/// being walked into
+fn quick_dirty(repo_root: &Path, prefs: &StatusPrefs) -> Option<bool> {
+    log::debug!("running git diff (dirty check) in {}", repo_root.display());
+    let tracked = git_in(repo_root)
+        .args(["diff", "--quiet", "HEAD"])
+        .arg(ignore_submodules(prefs))
+        .stdout(Stdio::null())
+        .status()
+        .inspect_err(|e| log::warning!("git diff failed to start: {e}"))
+        .ok()?;
+    match tracked.code() {
+        Some(0) => {}
+        Some(1) => return Some(true),
+        _ => return None,
+    }
+    if !prefs.untracked {
+        return Some(false);
+    }
+    let output = git_in(repo_root)
+        .args(["ls-files", "--others", "--exclude-standard"])
+        .args(["--directory", "--no-empty-directory", "-z"])
+        .output()
+        .inspect_err(|e| log::warning!("git ls-files failed to start: {e}"))
+        .ok()?;
+    output.status.success().then(|| !output.stdout.is_empty())
+}
+
+/// A git command for the repo that takes no locks, input or diagnostics
+fn git_in(repo_root: &Path) -> Command {
+    let mut git = Command::new("git");
+    git.arg("--no-optional-locks")
+        .arg("-C")
+        .arg(repo_root)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null());
+    git
+}
+
+/// Paths `git status` reports it couldn't read (`could not open directory
+/// 'x/': Permission denied`, `open("y"): Permission denied`)
+fn count_unreadable(stderr: &[u8]) -> usize {
+    String::from_utf8_lossy(stderr)
+        .lines()
+        .filter(|line| line.ends_with(": Permission denied"))
+        .count()
+}
+
+/// Whether libgit2 failed on a path it wasn't allowed to read
+/// Its OS errors end in the C library's message, untranslated in this process
+/// (nothing calls `setlocale`)
+fn is_permission_denied(e: &git2::Error) -> bool {
+    e.class() == ErrorClass::Os && e.message().ends_with("Permission denied")
+}
+
+/// Count entries in `git status --porcelain=v2 -z` output
+fn parse_porcelain_v2(out: &[u8]) -> StatusCounts {
+    let mut counts = StatusCounts::default();
+    let mut records = out.split(|&b| b == 0);
+
+    while let Some(record) = records.next() {
+        match record {
+            [kind @ (b'1' | b'2'), b' ', x, y, ..] => {
+                // Renames/copies carry the original path as an extra record
+                if *kind == b'2' {
+                    records.next();
+                }
+                counts.add_porcelain(*x, *y);
+            }
+            [b'u', ..] => counts.conflicted += 1,
+            [b'?', ..] => counts.untracked += 1,
+            _ => {}
+        }
+    }
+
+    counts
+}
+
+/// Collect Git repo info from the given path
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(root = %repo_root.display())))]
+pub fn collect(repo_root: &Path, config: &Config, timings: &Timings) -> Result<GitInfo> {
+    // Refs-only needs just the branch and hash, readable without libgit2
+    if config.refs_only && config.git_ref.is_none() {
+        match timings.time("refs read", || read_head(repo_root, config.id_length)) {
+            Some((branch, head_short)) => {
+                return Ok(GitInfo {
+                    branch,
+                    head_short,
+                    ..GitInfo::default()
+                });
+            }
+            None => log::debug!("refs not readable directly, opening repo"),
+        }
+    }
+
+    let repo = match timings.time("repo open", || Repository::open(repo_root)) {
+        Ok(repo) => repo,
+        // Refused by the ownership check (`safe.directory`)
+        Err(e) if e.code() == ErrorCode::Owner => {
+            return if config.is_trusted_path(repo_root) {
+                collect_trusted(repo_root, config, timings)
+            } else {
+                log::debug!("not owned by the current user: {e}");
+                Ok(GitInfo {
+                    untrusted: true,
+                    ..GitInfo::default()
+                })
+            };
+        }
+        Err(e) => return Err(Error::Git(format!("open: {e}"))),
+    };
+    if let Some(name) = config.git_ref.as_deref() {
+        return collect_ref(&repo, name, config, timings);
+    }
+    collect_head(&repo, repo_root, config, timings)
+}
+
+/// Tracked files from which a clean tree is confirmed by `quick_dirty` before
+/// the libgit2 scan; in smaller trees the scan costs less than starting git
+const PRECHECK_MIN_FILES: usize = 5000;
+
+/// Status counts, whether a bare dirty check answered instead, and whether
+/// the scan was skipped (large repo or out of time)
+fn collect_status(
+    repo: &Repository,
+    repo_root: &Path,
+    config: &Config,
+    timings: &Timings,
+) -> Result<(StatusCounts, Option<bool>, bool)> {
+    // bash.showDirtyState=false opts the repo out of the scan entirely
+    let mut prefs = StatusPrefs::from_config(repo);
+    prefs.recurse_untracked |= config.git_recurse_untracked;
+    prefs.submodules = config.git_submodules;
+    let large = !config.refs_only && is_large(repo, repo_root, config);
+    if large {
+        log::debug!("large repo, skipping status scan");
+    }
+    // Out of time (`priority`), the scan is skipped the same way
+    let status_skipped = large || (!config.refs_only && !timings.allows(config, Detail::Status));
+    let scan = prefs.dirty_state && !status_skipped && !config.refs_only;
+    // `git_simple_dirty` only needs a yes or no, so nothing is categorized;
+    // a large tree gets the yes or no first too, so a clean one isn't scanned
+    let git_caches = prefs.fsmonitor || prefs.untracked_cache;
+    let precheck = config.git_simple_dirty
+        || (!git_caches && repo.index().is_ok_and(|i| i.len() >= PRECHECK_MIN_FILES));
+    let quick = (scan && precheck)
+        .then(|| timings.time("dirty check", || quick_dirty(repo_root, &prefs)))
+        .flatten();
+    let dirty = quick.filter(|_| config.git_simple_dirty);
+    let counts = if !scan || dirty.is_some() || quick == Some(false) {
+        StatusCounts::default()
+    } else {
+        timings.time("status scan", || {
+            // libgit2 scans everything itself; git status answers from its caches
+            git_caches
+                .then(|| cli_status(repo_root, &prefs, false))
+                .flatten()
+                .map_or_else(
+                    || scan_status(repo, repo_root, &prefs, config.max_untracked),
+                    Ok,
+                )
+                // libgit2 gives up at the first unreadable directory; git skips it
+                .or_else(|e| {
+                    if !is_permission_denied(&e) {
+                        return Err(e);
+                    }
+                    log::debug!("{e}, retrying with git status");
+                    cli_status(repo_root, &prefs, false).ok_or(e)
+                })
+                .map_err(|e| Error::Git(format!("statuses: {e}")))
+        })?
+    };
+    Ok((counts, dirty, status_skipped))
+}
+
+/// Info for the working tree and HEAD of an opened repo
+fn collect_head(
+    repo: &Repository,
+    repo_root: &Path,
+    config: &Config,
+    timings: &Timings,
+) -> Result<GitInfo> {
+    // Status counts - compute once for both empty and normal repos
+    let (counts, dirty, status_skipped) = collect_status(repo, repo_root, config, timings)?;
+    let dirty = dirty.unwrap_or(false);
 
     // Get HEAD - may fail if no commits yet
     let Ok(head) = repo.head() else {
@@ -92,16 +697,19 @@ pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
         return Ok(GitInfo {
             branch,
             head_short: "empty".into(),
-            staged,
-            modified,
-            untracked,
-            deleted,
-            conflicted,
-            ahead: 0,
-            behind: 0,
+            dirty,
+            status_skipped,
+            ..counts.into_info()
         });
     };
 
+    let (operation, step, total) = operation(repo).unwrap_or_default();
+    let bisect_steps = if repo.state() == RepositoryState::Bisect {
+        timings.time("bisect", || bisect_steps(repo))
+    } else {
+        None
+    };
+
     let detached = repo
         .head_detached()
         .map_err(|e| Error::Git(format!("head_detached: {e}")))?;
@@ -118,46 +726,681 @@ pub fn collect(repo_root: &Path, id_length: usize) -> Result<GitInfo> {
         .peel_to_commit()
         .map_err(|e| Error::Git(format!("peel_to_commit: {e}")))?;
     let full_hash = head_commit.id().to_string();
-    let head_short = full_hash[..id_length.min(full_hash.len())].to_string();
+    let head_short = full_hash[..config.id_length.min(full_hash.len())].to_string();
+    let commit_message = String::from_utf8_lossy(head_commit.message_bytes());
+    let wip = config.is_wip(&commit_message);
+    let commit_type = message::conventional_type(&commit_message);
+    let foreign_author =
+        config.foreign_author && !config.refs_only && is_foreign(repo, &head_commit);
 
-    // Ahead/behind upstream
-    let (ahead, behind) = get_ahead_behind(&repo, &head).unwrap_or((0, 0));
+    // Ahead/behind upstream and compared remotes (graph walks, skipped for
+    // refs-only and out of time)
+    let walk = !config.refs_only && timings.allows(config, Detail::AheadBehind);
+    let ((ahead, behind), remotes) = if walk {
+        timings.time("ahead/behind", || branch_counts(repo, &head, config))
+    } else {
+        ((0, 0), Vec::new())
+    };
+    let (base, base_ahead) = base_counts(repo, head_commit.id(), config, timings);
+    let on_default = config.default_branch && branch.is_some() && branch == default_branch(repo);
+    let (last_action, action_time) = if config.segments.contains(&Segment::Action) {
+        last_action(repo).unwrap_or_default()
+    } else {
+        (None, 0)
+    };
+    let (lfs_locked, lfs_unlocked) = if config.git_lfs_locks && !config.refs_only {
+        timings.time("lfs locks", || lfs_locks(repo, repo_root))
+    } else {
+        (0, 0)
+    };
 
     Ok(GitInfo {
         branch,
         head_short,
-        staged,
-        modified,
-        untracked,
-        deleted,
-        conflicted,
+        dirty,
         ahead,
         behind,
+        superproject: superproject(repo_root, config),
+        remotes,
+        base,
+        base_ahead,
+        last_action,
+        action_time,
+        description: message::summary(&commit_message),
+        workspace: worktree_name(repo),
+        on_default,
+        status_skipped,
+        wip,
+        commit_type,
+        operation,
+        step,
+        total,
+        bisect_steps,
+        foreign_author,
+        lfs_locked,
+        lfs_unlocked,
+        ..counts.into_info()
     })
 }
 
-/// Get ahead/behind counts relative to upstream
-fn get_ahead_behind(
+/// A `trusted_paths` repo libgit2 won't open: branch and hash read from the
+/// files, status from `git status` told the path is safe
+fn collect_trusted(repo_root: &Path, config: &Config, timings: &Timings) -> Result<GitInfo> {
+    let (branch, head_short) = read_head(repo_root, config.id_length)
+        .ok_or_else(|| Error::Git("HEAD not readable without libgit2".into()))?;
+    let skipped = !config.refs_only
+        && (config.is_slow_path(repo_root) || !timings.allows(config, Detail::Status));
+    let counts = if config.refs_only || skipped {
+        None
+    } else {
+        timings.time("status scan", || {
+            cli_status(repo_root, &StatusPrefs::DEFAULT, true)
+        })
+    };
+    // A scan that failed says nothing about the tree, so it isn't shown clean
+    let status_skipped = skipped || (!config.refs_only && counts.is_none());
+    Ok(GitInfo {
+        branch,
+        head_short,
+        status_skipped,
+        ..counts.unwrap_or_default().into_info()
+    })
+}
+
+/// In-progress operation with its progress, from the git dir: rebases count
+/// `msgnum`/`end` (or `next`/`last`), sequences the picks left in `sequencer/todo`
+fn operation(repo: &Repository) -> Option<(Option<String>, usize, usize)> {
+    let label = match repo.state() {
+        RepositoryState::Clean => return None,
+        RepositoryState::Merge => "MERGING",
+        RepositoryState::Revert | RepositoryState::RevertSequence => "REVERTING",
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => "CHERRY-PICKING",
+        RepositoryState::Bisect => "BISECTING",
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge => "REBASING",
+        RepositoryState::ApplyMailbox => "AM",
+        RepositoryState::ApplyMailboxOrRebase => "AM/REBASE",
+    };
+    let git_dir = repo.path();
+    let number = |path: &str| -> Option<usize> {
+        fs::read_to_string(git_dir.join(path))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    };
+    let (step, total) = [
+        ("rebase-merge/msgnum", "rebase-merge/end"),
+        ("rebase-apply/next", "rebase-apply/last"),
+    ]
+    .iter()
+    .find_map(|(step, total)| Some((number(step)?, number(total)?)))
+    .or_else(|| {
+        let todo = fs::read_to_string(git_dir.join("sequencer").join("todo")).ok()?;
+        Some((0, picks_left(&todo)))
+    })
+    .unwrap_or((0, 0));
+    Some((Some(label.to_string()), step, total))
+}
+
+/// Steps `git bisect` still expects: the commits reachable from
+/// `refs/bisect/bad` but not from any `refs/bisect/good-*`
+fn bisect_steps(repo: &Repository) -> Option<usize> {
+    let bad = repo.refname_to_id("refs/bisect/bad").ok()?;
+    let mut walk = repo.revwalk().ok()?;
+    walk.push(bad).ok()?;
+    let goods = repo.references_glob("refs/bisect/good-*").ok()?;
+    let mut any_good = false;
+    for good in goods.flatten() {
+        walk.hide(good.target()?).ok()?;
+        any_good = true;
+    }
+    // Without a good commit the range is the whole history
+    any_good.then(|| estimate_steps(walk.count()))
+}
+
+/// Git's estimate of the bisect steps for `all` candidate commits
+/// (`estimate_bisect_steps` in bisect.c)
+const fn estimate_steps(all: usize) -> usize {
+    if all < 3 {
+        return 0;
+    }
+    let n = all.ilog2() as usize;
+    let e = 1 << n;
+    if e < 3 * (all - e) { n } else { n - 1 }
+}
+
+/// Commands in a sequencer todo list; the first is the one in progress
+fn picks_left(todo: &str) -> usize {
+    todo.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .count()
+}
+
+/// Info for `--ref` instead of HEAD: branch, hash and upstream ahead/behind
+/// (file status describes the working tree, so it is left out)
+fn collect_ref(
+    repo: &Repository,
+    name: &str,
+    config: &Config,
+    timings: &Timings,
+) -> Result<GitInfo> {
+    let (object, reference) = repo
+        .revparse_ext(name)
+        .map_err(|e| Error::Git(format!("revparse {name}: {e}")))?;
+    let commit = object
+        .peel_to_commit()
+        .map_err(|e| Error::Git(format!("peel_to_commit: {e}")))?;
+    let full_hash = commit.id().to_string();
+    let head_short = full_hash[..config.id_length.min(full_hash.len())].to_string();
+    let commit_message = String::from_utf8_lossy(commit.message_bytes());
+    let wip = config.is_wip(&commit_message);
+    let commit_type = message::conventional_type(&commit_message);
+    let foreign_author = config.foreign_author && !config.refs_only && is_foreign(repo, &commit);
+
+    let branch = reference.as_ref().filter(|r| r.is_branch());
+    let ((ahead, behind), remotes) = match branch {
+        Some(branch) if !config.refs_only && timings.allows(config, Detail::AheadBehind) => {
+            timings.time("ahead/behind", || branch_counts(repo, branch, config))
+        }
+        _ => ((0, 0), Vec::new()),
+    };
+    let (base, base_ahead) = base_counts(repo, commit.id(), config, timings);
+    let branch = branch
+        .and_then(git2::Reference::shorthand)
+        .map(String::from);
+    let on_default = config.default_branch && branch.is_some() && branch == default_branch(repo);
+
+    Ok(GitInfo {
+        branch,
+        head_short,
+        ahead,
+        behind,
+        remotes,
+        base,
+        base_ahead,
+        on_default,
+        wip,
+        commit_type,
+        description: message::summary(&commit_message),
+        foreign_author,
+        ..GitInfo::default()
+    })
+}
+
+/// A linked worktree's name: its directory under `.git/worktrees`
+fn worktree_name(repo: &Repository) -> Option<String> {
+    if !repo.is_worktree() {
+        return None;
+    }
+    let name = repo.path().file_name()?;
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// The commit's author email isn't the configured `user.email` (or none is set)
+fn is_foreign(repo: &Repository, commit: &git2::Commit<'_>) -> bool {
+    let user_email = repo
+        .config()
+        .and_then(|config| config.get_string("user.email"))
+        .unwrap_or_default();
+    !commit
+        .author()
+        .email()
+        .unwrap_or_default()
+        .eq_ignore_ascii_case(&user_email)
+}
+
+/// Branch and short hash read straight from the git dir (`HEAD`, the loose
+/// ref, then `packed-refs`); None for layouts this doesn't handle (reftable,
+/// nested symbolic refs), which fall back to libgit2
+fn read_head(repo_root: &Path, id_length: usize) -> Option<(Option<String>, String)> {
+    let git_dir = git_dir(repo_root)?;
+    // Linked worktrees keep their own HEAD but share refs with the main repo
+    let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => git_dir.join(common.trim()),
+        Err(_) => git_dir.clone(),
+    };
+    if common_dir.join("reftable").exists() {
+        return None;
+    }
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let (branch, hash) = match head.trim().strip_prefix("ref: ") {
+        Some(name) => {
+            let hash = match fs::read_to_string(common_dir.join(name)) {
+                Ok(loose) => Some(loose.trim().to_string()),
+                Err(_) => fs::read_to_string(common_dir.join("packed-refs"))
+                    .ok()
+                    .and_then(|packed| packed_ref(&packed, name).map(String::from)),
+            };
+            (name.strip_prefix("refs/heads/").map(String::from), hash)
+        }
+        None => (None, Some(head.trim().to_string())),
+    };
+    let head_short = match hash {
+        Some(hash) if is_object_id(&hash) => hash[..id_length.min(hash.len())].to_string(),
+        Some(_) => return None,
+        // Unborn branch, as libgit2 reports it
+        None => "empty".into(),
+    };
+    Some((branch, head_short))
+}
+
+/// The git dir: `.git` itself, or where a `.git` file's `gitdir:` line points
+fn git_dir(repo_root: &Path) -> Option<PathBuf> {
+    let dot_git = repo_root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let link = fs::read_to_string(&dot_git).ok()?;
+    Some(repo_root.join(link.trim().strip_prefix("gitdir: ")?))
+}
+
+/// Target of `name` in `packed-refs` (`<hash> <name>` lines; `#` headers and
+/// `^` peeled tag lines are skipped)
+fn packed_ref<'a>(packed: &'a str, name: &str) -> Option<&'a str> {
+    packed
+        .lines()
+        .filter(|line| !line.starts_with(['#', '^']))
+        .find_map(|line| {
+            let (hash, ref_name) = line.split_once(' ')?;
+            (ref_name == name).then_some(hash)
+        })
+}
+
+/// A full SHA-1 or SHA-256 hex object ID
+fn is_object_id(hash: &str) -> bool {
+    matches!(hash.len(), 40 | 64) && hash.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Large-repo policy: on a configured slow path, or the index exceeds the threshold
+fn is_large(repo: &Repository, repo_root: &Path, config: &Config) -> bool {
+    config.is_slow_path(repo_root)
+        || (config.large_repo_threshold > 0
+            && repo
+                .index()
+                .is_ok_and(|index| index.len() > config.large_repo_threshold))
+}
+
+/// Ahead/behind the upstream, and against each `compare_remotes` remote's
+/// copy of the branch, falling back to the remote's default branch
+/// (`<remote>/HEAD`); zero and empty for a detached HEAD
+fn branch_counts(
     repo: &Repository,
     head: &git2::Reference<'_>,
+    config: &Config,
+) -> ((usize, usize), Vec<RemoteCounts>) {
+    let Some(name) = head.shorthand().filter(|_| head.is_branch()) else {
+        return ((0, 0), Vec::new());
+    };
+    let exists = |target: &String| repo.find_reference(target).is_ok();
+    let upstream = repo
+        .branch_upstream_name(&format!("refs/heads/{name}"))
+        .ok()
+        .and_then(|upstream| upstream.as_str().map(String::from))
+        .filter(exists);
+    let remotes: Vec<(&String, String)> = config
+        .git_compare_remotes
+        .iter()
+        .filter_map(|remote| {
+            let target = [
+                format!("refs/remotes/{remote}/{name}"),
+                format!("refs/remotes/{remote}/HEAD"),
+            ]
+            .into_iter()
+            .find(exists)?;
+            Some((remote, target))
+        })
+        .collect();
+
+    let Ok(local) = head.peel_to_commit().map(|commit| commit.id()) else {
+        return ((0, 0), Vec::new());
+    };
+    // Each target on its own, so one that can't be walked leaves the rest
+    let counts = |target: &str| {
+        get_ahead_behind(repo, local, target)
+            .inspect_err(|e| log::debug!("no ahead/behind for {target}: {e}"))
+            .ok()
+    };
+    let upstream_counts = upstream.as_deref().and_then(counts).unwrap_or_default();
+    let remotes = remotes
+        .into_iter()
+        .filter_map(|(remote, target)| {
+            let (ahead, behind) = counts(&target)?;
+            Some(RemoteCounts {
+                remote: remote.clone(),
+                ahead,
+                behind,
+            })
+        })
+        .collect();
+    (upstream_counts, remotes)
+}
+
+/// Default branch name and the commits `oid` has over it, for the `base`
+/// segment (a graph walk, so only when it's listed)
+fn base_counts(
+    repo: &Repository,
+    oid: git2::Oid,
+    config: &Config,
+    timings: &Timings,
+) -> (Option<String>, usize) {
+    if config.refs_only || !config.segments.contains(&Segment::Base) {
+        return (None, 0);
+    }
+    timings
+        .time("base", || default_ahead(repo, oid))
+        .inspect_err(|e| log::debug!("no default branch: {e}"))
+        .unwrap_or((None, 0))
+}
+
+fn default_ahead(
+    repo: &Repository,
+    oid: git2::Oid,
+) -> std::result::Result<(Option<String>, usize), git2::Error> {
+    let default = repo.find_reference("refs/remotes/origin/HEAD")?;
+    let name = default_branch(repo);
+    let default_oid = default.resolve()?.peel_to_commit()?.id();
+    let (ahead, _) = repo.graph_ahead_behind(oid, default_oid)?;
+    Ok((name, ahead))
+}
+
+/// Action and time of the latest HEAD reflog entry
+fn last_action(repo: &Repository) -> Option<(Option<String>, usize)> {
+    let reflog = repo.reflog("HEAD").ok()?;
+    let entry = reflog.get(0)?;
+    let time = usize::try_from(entry.committer().when().seconds()).unwrap_or_default();
+    Some((entry.message().and_then(reflog_action), time))
+}
+
+/// The command in a reflog message: `checkout: moving from a to b` →
+/// `checkout`, `rebase (finish): ...` → `rebase`, `merge topic: Fast-forward` → `merge`
+fn reflog_action(message: &str) -> Option<String> {
+    let command = message
+        .split_once(':')
+        .map_or(message, |(command, _)| command);
+    command.split_whitespace().next().map(String::from)
+}
+
+/// Git LFS locks held by the user and modified `lockable` files without one
+/// The lock list comes from git-lfs's local cache (`git lfs locks --local`),
+/// which only knows the user's own locks, so nothing goes over the network
+fn lfs_locks(repo: &Repository, repo_root: &Path) -> (usize, usize) {
+    if !repo.path().join("lfs").is_dir() {
+        return (0, 0);
+    }
+    log::debug!("running git lfs locks in {}", repo_root.display());
+    let locked = Command::new("git")
+        .arg("-C")
+        .arg(repo_root)
+        .args(["lfs", "locks", "--local", "--json"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .inspect_err(|e| log::debug!("git lfs locks failed to start: {e}"))
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| lock_paths(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default();
+
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).exclude_submodules(true);
+    let Ok(statuses) = repo.statuses(Some(&mut opts)) else {
+        return (locked.len(), 0);
+    };
+    let unlocked = statuses
+        .iter()
+        .filter(|entry| {
+            entry
+                .status()
+                .intersects(Status::WT_MODIFIED | Status::WT_DELETED | Status::INDEX_MODIFIED)
+        })
+        .filter_map(|entry| entry.path().map(String::from))
+        .filter(|path| !locked.contains(path))
+        .filter(|path| {
+            let attr = repo.get_attr(Path::new(path), "lockable", AttrCheckFlags::FILE_THEN_INDEX);
+            matches!(attr.map(AttrValue::from_string), Ok(AttrValue::True))
+        })
+        .count();
+    (locked.len(), unlocked)
+}
+
+/// Paths in `git lfs locks --json` output (`[{"path": "a.psd", ...}]`)
+fn lock_paths(output: &str) -> HashSet<String> {
+    match json::parse(output) {
+        Ok(json::Value::Array(locks)) => locks.iter().filter_map(|l| l.get_str("path")).collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// Directory name of the repo this one is a submodule of: the nearest
+/// enclosing repo whose `.gitmodules` lists it, within the detection limits
+fn superproject(repo_root: &Path, config: &Config) -> Option<String> {
+    let parent = detect::enclosing_git(repo_root, Limits::from_config(config))?;
+    let path = repo_root
+        .strip_prefix(parent)
+        .ok()?
+        .to_string_lossy()
+        .replace('\\', "/");
+    let gitmodules = fs::read_to_string(parent.join(".gitmodules")).ok()?;
+    if !lists_submodule(&gitmodules, &path) {
+        return None;
+    }
+    Some(parent.file_name()?.to_string_lossy().into_owned())
+}
+
+/// A `.gitmodules` file has a `path = {path}` entry
+fn lists_submodule(gitmodules: &str, path: &str) -> bool {
+    gitmodules.lines().any(|line| {
+        line.split_once('=')
+            .is_some_and(|(key, value)| key.trim() == "path" && value.trim() == path)
+    })
+}
+
+/// The default branch: `origin/HEAD`'s target, without the remote
+fn default_branch(repo: &Repository) -> Option<String> {
+    repo.find_reference("refs/remotes/origin/HEAD")
+        .ok()?
+        .symbolic_target()?
+        .strip_prefix("refs/remotes/origin/")
+        .map(String::from)
+}
+
+/// Ahead/behind counts of `head` against each target ref, in order
+fn get_ahead_behind(
+    repo: &Repository,
+    local_oid: git2::Oid,
+    target: &str,
 ) -> std::result::Result<(usize, usize), git2::Error> {
-    // Need a branch, not detached HEAD
-    if repo.head_detached()? {
-        return Ok((0, 0));
+    let target_oid = repo.revparse_single(target)?.peel_to_commit()?.id();
+    repo.graph_ahead_behind(local_oid, target_oid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_counts_json() {
+        let counts = RemoteCounts {
+            remote: "upstream".into(),
+            ahead: 2,
+            behind: 14,
+        };
+        let value = counts.to_json();
+        assert_eq!(
+            value.to_string(),
+            r#"{"remote":"upstream","ahead":2,"behind":14}"#
+        );
+        assert_eq!(RemoteCounts::from_json(&value), Some(counts));
     }
 
-    // Get the branch
-    let branch = repo.find_branch(
-        head.shorthand()
-            .ok_or_else(|| git2::Error::from_str("no branch name"))?,
-        git2::BranchType::Local,
-    )?;
+    #[test]
+    fn test_count_entries() {
+        assert_eq!(count_entries(&b"a\0dir/\0b\0"[..], 10), 3);
+        assert_eq!(count_entries(&b""[..], 10), 0);
+        // An endless listing is read only to one past the cap
+        let endless = std::io::repeat(0);
+        assert_eq!(count_entries(BufReader::new(endless), 99), 100);
+    }
 
-    // Get upstream
-    let upstream = branch.upstream()?;
+    #[test]
+    fn test_parse_porcelain_v2() {
+        let out = b"1 M. N... 100644 100644 100644 aaa bbb staged.rs\0\
+1 .M N... 100644 100644 100644 aaa bbb modified.rs\0\
+1 .D N... 100644 100644 000000 aaa bbb deleted.rs\0\
+2 R. N... 100644 100644 100644 aaa bbb R100 new.rs\0old.rs\0\
+1 .T N... 100644 100644 120000 aaa bbb link.rs\0\
+u UU N... 100644 100644 100644 100644 aaa bbb ccc conflict.rs\0\
+? untracked.rs\0";
+        let counts = parse_porcelain_v2(out);
+        assert_eq!(counts.staged, 1);
+        assert_eq!(counts.renamed, 1);
+        assert_eq!(counts.typechanged, 1);
+        assert_eq!(counts.modified, 1);
+        assert_eq!(counts.deleted, 1);
+        assert_eq!(counts.conflicted, 1);
+        assert_eq!(counts.untracked, 1);
+    }
 
-    let local_oid = head.peel_to_commit()?.id();
-    let upstream_oid = upstream.get().peel_to_commit()?.id();
+    #[test]
+    fn test_packed_ref() {
+        let packed = "# pack-refs with: peeled fully-peeled sorted
+1111111111111111111111111111111111111111 refs/heads/main
+2222222222222222222222222222222222222222 refs/tags/v1
+^3333333333333333333333333333333333333333
+4444444444444444444444444444444444444444 refs/heads/main-2
+";
+        assert_eq!(
+            packed_ref(packed, "refs/heads/main"),
+            Some("1111111111111111111111111111111111111111")
+        );
+        assert_eq!(
+            packed_ref(packed, "refs/heads/main-2"),
+            Some("4444444444444444444444444444444444444444")
+        );
+        assert_eq!(packed_ref(packed, "refs/heads/feature"), None);
+    }
 
-    repo.graph_ahead_behind(local_oid, upstream_oid)
+    #[test]
+    fn test_read_head() {
+        let root = std::env::temp_dir().join(format!("jj-starship-head-{}", std::process::id()));
+        let git_dir = root.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        let hash = "0123456789abcdef0123456789abcdef01234567";
+
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert_eq!(
+            read_head(&root, 7),
+            Some((Some("main".into()), "empty".into()))
+        );
+
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!("{hash} refs/heads/main\n"),
+        )
+        .unwrap();
+        assert_eq!(
+            read_head(&root, 7),
+            Some((Some("main".into()), "0123456".into()))
+        );
+
+        fs::write(git_dir.join("HEAD"), format!("{hash}\n")).unwrap();
+        assert_eq!(read_head(&root, 8), Some((None, "01234567".into())));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_estimate_steps() {
+        assert_eq!(estimate_steps(1), 0);
+        assert_eq!(estimate_steps(2), 0);
+        assert_eq!(estimate_steps(3), 1);
+        assert_eq!(estimate_steps(16), 3);
+        assert_eq!(estimate_steps(30), 4);
+        assert_eq!(estimate_steps(100), 6);
+    }
+
+    #[test]
+    fn test_count_unreadable() {
+        let stderr = b"warning: could not open directory 'secret/': Permission denied\n\
+error: open(\"locked.txt\"): Permission denied\n\
+warning: in the working copy of 'a.txt', LF will be replaced by CRLF\n";
+        assert_eq!(count_unreadable(stderr), 2);
+        assert_eq!(count_unreadable(b""), 0);
+    }
+
+    #[test]
+    fn test_is_permission_denied() {
+        let error = |class, message| git2::Error::new(ErrorCode::GenericError, class, message);
+        assert!(is_permission_denied(&error(
+            ErrorClass::Os,
+            "could not open directory 'secret/': Permission denied"
+        )));
+        assert!(!is_permission_denied(&error(
+            ErrorClass::Index,
+            "index file corrupt"
+        )));
+    }
+
+    #[test]
+    fn test_reflog_action() {
+        assert_eq!(
+            reflog_action("checkout: moving from main to feature").as_deref(),
+            Some("checkout")
+        );
+        assert_eq!(
+            reflog_action("rebase (finish): returning to refs/heads/x").as_deref(),
+            Some("rebase")
+        );
+        assert_eq!(
+            reflog_action("commit (amend): Fix typo").as_deref(),
+            Some("commit")
+        );
+        assert_eq!(
+            reflog_action("merge origin/main: Fast-forward").as_deref(),
+            Some("merge")
+        );
+        assert_eq!(reflog_action(""), None);
+    }
+
+    #[test]
+    fn test_lists_submodule() {
+        let gitmodules = "[submodule \"vendor/lib\"]\n\tpath = vendor/lib\n\turl = ../lib.git\n";
+        assert!(lists_submodule(gitmodules, "vendor/lib"));
+        assert!(!lists_submodule(gitmodules, "vendor"));
+        assert!(!lists_submodule(gitmodules, "../lib.git"));
+    }
+
+    #[test]
+    fn test_lock_paths() {
+        let out = r#"[{"id":"1","path":"art/hero.psd","owner":{"name":"me"},"locked_at":"2024-05-01T10:00:00Z"},
+            {"id":"2","path":"levels/one.umap","owner":{"name":"me"}}]"#;
+        assert_eq!(
+            lock_paths(out),
+            HashSet::from(["art/hero.psd".to_string(), "levels/one.umap".to_string()])
+        );
+        assert!(lock_paths("[]").is_empty());
+        assert!(lock_paths("Error: not a git-lfs repo").is_empty());
+    }
+
+    #[test]
+    fn test_picks_left() {
+        let todo = "pick 1a2b3c4 first\n# comment\n\nrevert 5d6e7f8 second\n";
+        assert_eq!(picks_left(todo), 2);
+        assert_eq!(picks_left(""), 0);
+    }
+
+    #[test]
+    fn test_is_dirty() {
+        let info = GitInfo {
+            untracked: 2,
+            ..GitInfo::default()
+        };
+        assert!(!info.is_dirty(false));
+        assert!(info.is_dirty(true));
+        assert!(GitInfo { staged: 1, ..info }.is_dirty(false));
+    }
 }
@@ -0,0 +1,42 @@
+//! Minimal glob matching for paths and names
+//! Supports `*` (within a segment), `**` (across segments) and `?`
+
+/// Match `text` against a glob `pattern` (whole string)
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    match_from(&pattern, &text)
+}
+
+fn match_from(p: &[char], t: &[char]) -> bool {
+    match p {
+        [] => t.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` may also match zero segments
+            (0..=t.len()).any(|i| match_from(rest, &t[i..]))
+                || (rest.first() == Some(&'/') && match_from(&rest[1..], t))
+        }
+        ['*', rest @ ..] => (0..=t.len())
+            .take_while(|&i| i == 0 || t[i - 1] != '/')
+            .any(|i| match_from(rest, &t[i..])),
+        ['?', rest @ ..] => matches!(t, [c, ..] if *c != '/') && match_from(rest, &t[1..]),
+        [c, rest @ ..] => t.first() == Some(c) && match_from(rest, &t[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(matches("hotfix/*", "hotfix/login"));
+        assert!(!matches("hotfix/*", "hotfix/a/b"));
+        assert!(matches("/src/**", "/src/a/b/c"));
+        assert!(matches("/src/**/vendor", "/src/vendor"));
+        assert!(matches("/src/**/vendor", "/src/a/b/vendor"));
+        assert!(matches("v?.?", "v1.2"));
+        assert!(!matches("main", "main2"));
+        assert!(matches("*", ""));
+    }
+}
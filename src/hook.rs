@@ -0,0 +1,146 @@
+//! Render hooks (`[hooks]`): shell commands run in the repo root around
+//! rendering, with the collected info as JSON on stdin
+//! The pre hook vetoes the prompt by failing; the post hook also gets the
+//! rendered prompt in `JJ_STARSHIP_OUTPUT` and vetoes it by failing or replaces
+//! it with what it prints
+//! A hook that can't start or outlives `hooks.timeout_ms` is ignored
+
+use crate::config::Config;
+use crate::custom;
+use crate::json;
+use crate::log;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Outcome of a hook run
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    /// Exited non-zero
+    Veto,
+    /// Succeeded, with what it printed
+    Output(String),
+    /// Failed to start or timed out
+    Ignored,
+}
+
+/// The prompt `render` draws, passed through the configured hooks; None when
+/// a hook vetoes it
+pub fn wrap(
+    config: &Config,
+    repo_root: &Path,
+    info: impl FnOnce() -> json::Value,
+    render: impl FnOnce() -> String,
+) -> Option<String> {
+    if config.pre_hook.is_empty() && config.post_hook.is_empty() {
+        return Some(render());
+    }
+    let info = info().to_string();
+    let timeout = Duration::from_millis(config.hook_timeout_ms as u64);
+    if !config.pre_hook.is_empty() {
+        let outcome = run(&config.pre_hook, repo_root, &info, None, timeout);
+        if outcome == Outcome::Veto {
+            log::debug!("pre hook vetoed the prompt");
+            return None;
+        }
+    }
+    let output = render();
+    if config.post_hook.is_empty() {
+        return Some(output);
+    }
+    match run(&config.post_hook, repo_root, &info, Some(&output), timeout) {
+        Outcome::Veto => {
+            log::debug!("post hook vetoed the prompt");
+            None
+        }
+        // Printing nothing keeps the prompt
+        Outcome::Output(out) if !out.trim().is_empty() => {
+            Some(out.trim_end_matches(['\n', '\r']).to_string())
+        }
+        Outcome::Output(_) | Outcome::Ignored => Some(output),
+    }
+}
+
+fn run(
+    command: &str,
+    repo_root: &Path,
+    info: &str,
+    output: Option<&str>,
+    timeout: Duration,
+) -> Outcome {
+    let deadline = Instant::now() + timeout;
+    let mut shell = custom::shell(command, repo_root);
+    shell.stdin(Stdio::piped());
+    if let Some(output) = output {
+        shell.env("JJ_STARSHIP_OUTPUT", output);
+    }
+    let mut child = match shell.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            log::warning!("hook `{command}` failed to start: {e}");
+            return Outcome::Ignored;
+        }
+    };
+    // Written on a thread so a hook that doesn't read its input can't hold
+    // the prompt past the timeout; one that exits early closes the pipe
+    if let Some(mut stdin) = child.stdin.take() {
+        let info = info.to_string();
+        thread::spawn(move || {
+            let _ = stdin.write_all(info.as_bytes());
+        });
+    }
+    match custom::wait(child, deadline) {
+        Some((status, out)) if status.success() => Outcome::Output(out),
+        Some(_) => Outcome::Veto,
+        None => {
+            log::warning!("hook `{command}` timed out");
+            Outcome::Ignored
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn wrap_with(pre: &str, post: &str) -> Option<String> {
+        let config = Config {
+            pre_hook: pre.into(),
+            post_hook: post.into(),
+            hook_timeout_ms: 200,
+            ..Config::default()
+        };
+        let info = || json::Value::Object(vec![("bookmark".into(), "WIP-fix".into())]);
+        wrap(&config, &std::env::temp_dir(), info, || "on main".into())
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap_with("", ""), Some("on main".into()));
+        assert_eq!(wrap_with("grep -q WIP && exit 1; exit 0", ""), None);
+        assert_eq!(wrap_with("cat >/dev/null", ""), Some("on main".into()));
+        assert_eq!(
+            wrap_with("", "printf '%s!\\n' \"$JJ_STARSHIP_OUTPUT\""),
+            Some("on main!".into())
+        );
+        assert_eq!(wrap_with("", "true"), Some("on main".into()));
+        assert_eq!(wrap_with("", "false"), None);
+        // A hung hook is ignored rather than blocking the prompt
+        assert_eq!(wrap_with("sleep 5", "sleep 5"), Some("on main".into()));
+    }
+
+    #[test]
+    fn test_run_large_input() {
+        // Bigger than a pipe buffer both ways
+        let info = "x".repeat(1 << 20);
+        let dir = std::env::temp_dir();
+        let outcome = run("cat", &dir, &info, None, Duration::from_secs(5));
+        assert_eq!(outcome, Outcome::Output(info.clone()));
+        let started = Instant::now();
+        let outcome = run("sleep 5", &dir, &info, None, Duration::from_millis(200));
+        assert_eq!(outcome, Outcome::Ignored);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,95 @@
+//! HTML output for prompt previews (`--output html`)
+//! Converts the ANSI render, so both outputs come from the same segments;
+//! each styled run becomes a `<span>` with inline CSS
+
+use crate::color::Style;
+
+/// Convert ANSI-styled output to HTML-escaped text and inline-styled spans
+pub fn from_ansi(ansi: &str) -> String {
+    let mut out = String::with_capacity(ansi.len() * 2);
+    let mut style = Style::default();
+    let mut rest = ansi;
+    while !rest.is_empty() {
+        if let Some(seq) = rest.strip_prefix("\x1b[") {
+            let end = seq.find('m').unwrap_or(seq.len());
+            style = style.apply_sgr(&seq[..end]);
+            rest = seq.get(end + 1..).unwrap_or_default();
+            continue;
+        }
+        // OSC sequences (titles, hyperlinks) run to BEL or ST and show nothing
+        if let Some(seq) = rest.strip_prefix("\x1b]") {
+            let end = seq.find(['\x07', '\x1b']).unwrap_or(seq.len());
+            rest = &seq[end..];
+            rest = rest
+                .strip_prefix('\x07')
+                .or_else(|| rest.strip_prefix("\x1b\\"))
+                .unwrap_or(rest);
+            continue;
+        }
+        let end = rest.find('\x1b').unwrap_or(rest.len());
+        // Any other escape is dropped on its own
+        if end == 0 {
+            rest = &rest[1..];
+            continue;
+        }
+        let css = style.css();
+        if css.is_empty() {
+            escape(&rest[..end], &mut out);
+        } else {
+            out.push_str("<span style=\"");
+            out.push_str(&css);
+            out.push_str("\">");
+            escape(&rest[..end], &mut out);
+            out.push_str("</span>");
+        }
+        rest = &rest[end..];
+    }
+    out
+}
+
+fn escape(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::{PURPLE, RED, RESET};
+
+    #[test]
+    fn test_from_ansi() {
+        assert_eq!(
+            from_ansi(&format!("on {PURPLE}main{RESET} {RED}[!]{RESET}")),
+            "on <span style=\"color:#cd00cd\">main</span> <span style=\"color:#cd0000\">[!]</span>"
+        );
+        // Empty styled runs leave no span; styles stack until reset
+        assert_eq!(
+            from_ansi(&format!("{RED}{RESET}\x1b[1m{RED}x{RESET}")),
+            "<span style=\"color:#cd0000;font-weight:bold\">x</span>"
+        );
+    }
+
+    #[test]
+    fn test_other_escapes() {
+        assert_eq!(from_ansi("\x1b]0;x\x07"), "");
+        assert_eq!(from_ansi("\x1b]8;;url\x1b\\a\x1b]8;;\x1b\\"), "a");
+        assert_eq!(from_ansi("a\x1bb"), "ab");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            from_ansi(&format!("{PURPLE}a<b>&\"c\"{RESET}")),
+            "<span style=\"color:#cd00cd\">a&lt;b&gt;&amp;&quot;c&quot;</span>"
+        );
+    }
+}
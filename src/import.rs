@@ -0,0 +1,258 @@
+//! Config import for `jj-starship import-starship`
+//! Carries over what starship's `[git_branch]`, `[git_commit]` and
+//! `[git_status]` modules set that jj-starship has a key for; the rest is
+//! reported so nothing is dropped silently
+
+use crate::color::{Color, Style};
+use std::env;
+use std::path::PathBuf;
+use toml_edit::{Document, DocumentMut, Item, Table, value};
+
+/// Starship's `git_status` symbols: jj-starship draws its own status symbols
+const STATUS_SYMBOLS: &[&str] = &[
+    "conflicted",
+    "ahead",
+    "behind",
+    "diverged",
+    "up_to_date",
+    "untracked",
+    "stashed",
+    "modified",
+    "staged",
+    "renamed",
+    "deleted",
+    "typechanged",
+];
+
+/// A converted config file and the starship settings it couldn't carry over
+#[derive(Debug)]
+pub struct Import {
+    pub config: String,
+    pub skipped: Vec<String>,
+}
+
+/// starship's config: `$STARSHIP_CONFIG`, else `~/.config/starship.toml`
+pub fn starship_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("STARSHIP_CONFIG") {
+        return Some(PathBuf::from(path));
+    }
+    Some(
+        crate::config::home_dir()?
+            .join(".config")
+            .join("starship.toml"),
+    )
+}
+
+/// Convert starship.toml text into jj-starship config file text
+pub fn import(text: &str) -> Result<Import, String> {
+    let doc = Document::parse(text).map_err(|e| e.message().trim().to_string())?;
+    let mut out = DocumentMut::new();
+    let mut git = Table::new();
+    let mut skipped = Vec::new();
+    let module = |name: &str| doc.as_table().get(name).and_then(Item::as_table_like);
+
+    if let Some(branch) = module("git_branch") {
+        for (key, item) in branch.iter() {
+            match key {
+                "symbol" => copy_str(item, "symbol", &mut git),
+                "style" => copy_style(item, "git_branch", "name_style", &mut git, &mut skipped),
+                "disabled" => disable(item, "name", &mut git),
+                "truncation_length" => {
+                    // starship's default (2^63 - 1) means unlimited, jj-starship's is 0
+                    if let Some(n) = item.as_integer().filter(|n| *n >= 0) {
+                        out["truncate_name"] = value(if n == i64::MAX { 0 } else { n });
+                    }
+                }
+                key => skipped.push(format!("git_branch.{key}")),
+            }
+        }
+    }
+    if let Some(commit) = module("git_commit") {
+        for (key, item) in commit.iter() {
+            match key {
+                "commit_hash_length" => {
+                    if let Some(n) = item.as_integer().filter(|n| *n > 0) {
+                        out["id_length"] = value(n);
+                    }
+                }
+                "style" => copy_style(item, "git_commit", "id_style", &mut git, &mut skipped),
+                key => skipped.push(format!("git_commit.{key}")),
+            }
+        }
+    }
+    if let Some(status) = module("git_status") {
+        for (key, item) in status.iter() {
+            match key {
+                "style" => copy_style(item, "git_status", "status_style", &mut git, &mut skipped),
+                "disabled" => disable(item, "status", &mut git),
+                key if STATUS_SYMBOLS.contains(&key) => {
+                    skipped.push(format!("git_status.{key} (status symbols are fixed)"));
+                }
+                key => skipped.push(format!("git_status.{key}")),
+            }
+        }
+    }
+
+    if !git.is_empty() {
+        out["git"] = Item::Table(git);
+    }
+    Ok(Import {
+        config: out.to_string(),
+        skipped,
+    })
+}
+
+/// `imported` config file text laid over `existing`: imported keys replace
+/// theirs, the rest of the file (other keys, comments) stays as it was
+pub fn merge(existing: &str, imported: &str) -> Result<String, String> {
+    let parse = |text: &str| {
+        text.parse::<DocumentMut>()
+            .map_err(|e| e.message().trim().to_string())
+    };
+    let mut doc = parse(existing)?;
+    let imported = parse(imported)?;
+    for (key, item) in imported.iter() {
+        match (
+            item.as_table(),
+            doc.get_mut(key).and_then(Item::as_table_like_mut),
+        ) {
+            (Some(table), Some(target)) => {
+                for (sub, sub_item) in table.iter() {
+                    target.insert(sub, sub_item.clone());
+                }
+            }
+            _ => doc[key] = item.clone(),
+        }
+    }
+    Ok(doc.to_string())
+}
+
+fn copy_str(item: &Item, key: &str, table: &mut Table) {
+    if let Some(s) = item.as_str() {
+        table[key] = value(s);
+    }
+}
+
+/// `disabled = true` on a module turns off the matching part of the prompt
+fn disable(item: &Item, key: &str, table: &mut Table) {
+    if item.as_bool() == Some(true) {
+        table[key] = value(false);
+    }
+}
+
+fn copy_style(item: &Item, module: &str, key: &str, table: &mut Table, skipped: &mut Vec<String>) {
+    match item.as_str().and_then(style) {
+        Some(style) => table[key] = value(style),
+        None => skipped.push(format!("{module}.style (unsupported style)")),
+    }
+}
+
+/// A starship style (`bold fg:#ff8000 bg:black`) in jj-starship's syntax
+/// (`bold #ff8000 on black`); None for parts it can't express (`inverted`,
+/// `blink`, `strikethrough`, ...)
+/// `none` anywhere turns styling off in starship, which is `default` here
+fn style(spec: &str) -> Option<String> {
+    let mut words = Vec::new();
+    for word in spec.split_whitespace() {
+        let lower = word.to_ascii_lowercase();
+        match lower.as_str() {
+            "bold" | "italic" | "underline" => words.push(lower),
+            "dimmed" => words.push("dim".into()),
+            "none" => return Some("default".into()),
+            _ => match lower.split_once(':') {
+                Some(("fg", name)) => words.push(color(name)?),
+                Some(("bg", name)) => words.push(format!("on {}", color(name)?)),
+                Some(_) => return None,
+                None => words.push(color(&lower)?),
+            },
+        }
+    }
+    let style = words.join(" ");
+    Style::parse(&style).map(|_| style)
+}
+
+/// A starship color: names (`bright-red`), `#rrggbb` or a 0-255 palette index
+fn color(name: &str) -> Option<String> {
+    if let Ok(index) = name.parse::<u8>() {
+        return Some(format!("ansi-color-{index}"));
+    }
+    let name = name.replace("bright-", "bright ");
+    Color::parse(&name).map(|_| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import() {
+        let import = import(
+            r##"
+            format = "$all"
+
+            [git_branch]
+            symbol = "🌱 "
+            style = "bold fg:#ff8000 bg:black"
+            truncation_length = 20
+            truncation_symbol = "…"
+
+            [git_commit]
+            commit_hash_length = 6
+
+            [git_status]
+            style = "inverted red"
+            modified = "M"
+            disabled = true
+            "##,
+        )
+        .unwrap();
+        assert_eq!(
+            import.config,
+            "truncate_name = 20\nid_length = 6\n\n[git]\nsymbol = \"🌱 \"\n\
+             name_style = \"bold #ff8000 on black\"\nstatus = false\n"
+        );
+        assert_eq!(
+            import.skipped,
+            [
+                "git_branch.truncation_symbol",
+                "git_status.style (unsupported style)",
+                "git_status.modified (status symbols are fixed)",
+            ]
+        );
+        assert!(crate::validate::validate(&import.config).is_empty());
+    }
+
+    #[test]
+    fn test_style() {
+        assert_eq!(style("bold purple").as_deref(), Some("bold purple"));
+        assert_eq!(style("dimmed 208").as_deref(), Some("dim ansi-color-208"));
+        assert_eq!(
+            style("fg:bright-red bg:blue").as_deref(),
+            Some("bright red on blue")
+        );
+        assert_eq!(style("blink red"), None);
+        assert_eq!(style("none").as_deref(), Some("default"));
+        assert_eq!(style("bold none red").as_deref(), Some("default"));
+    }
+
+    #[test]
+    fn test_merge() {
+        let existing =
+            "# mine\nid_length = 12\nnerd_font = \"v2\"\n\n[git]\nsymbol = \"g \"\nid = false\n";
+        let imported = "id_length = 6\n\n[git]\nsymbol = \"🌱 \"\nname_style = \"default\"\n";
+        let merged = merge(existing, imported).unwrap();
+        assert_eq!(
+            merged,
+            "# mine\nid_length = 6\nnerd_font = \"v2\"\n\n[git]\nsymbol = \"🌱 \"\nid = false\n\
+             name_style = \"default\"\n"
+        );
+        assert_eq!(merge("", imported).unwrap(), imported);
+        assert!(merge("[git\n", imported).is_err());
+    }
+
+    #[test]
+    fn test_unlimited_truncation() {
+        let import = import("[git_branch]\ntruncation_length = 9223372036854775807\n").unwrap();
+        assert_eq!(import.config, "truncate_name = 0\n");
+    }
+}
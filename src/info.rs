@@ -0,0 +1,51 @@
+//! Collected info documents: `info` output and `render --stdin-json` input
+
+use crate::cache;
+use crate::config::Config;
+use crate::detect::{DetectResult, RepoType};
+#[cfg(feature = "git")]
+use crate::git;
+use crate::jj;
+use crate::json;
+use crate::log;
+use crate::output;
+use crate::timing::Timings;
+use std::path::Path;
+
+/// Collected info as JSON for `info`; None outside a repo or on error
+#[allow(unreachable_patterns)]
+pub fn collect(result: DetectResult, config: &Config, timings: &Timings) -> Option<json::Value> {
+    match config.backend(result.repo_type) {
+        RepoType::Jj | RepoType::JjColocated => {
+            cache::collect_jj(&result.repo_root?, config, None, timings)
+                .inspect_err(|e| log::warning!("{e}"))
+                .ok()
+                .map(|info| info.to_json())
+        }
+        #[cfg(feature = "git")]
+        RepoType::Git => git::collect(&result.repo_root?, config, timings)
+            .inspect_err(|e| log::warning!("{e}"))
+            .ok()
+            .map(|info| info.to_json()),
+        _ => None,
+    }
+}
+
+/// Render an info document for `render --stdin-json`
+/// A `change_id` field marks JJ info, `head_short` marks Git info
+pub fn render(input: &str, cwd: &Path, config: &Config) -> Result<String, String> {
+    let value = json::parse(input).map_err(|e| format!("invalid JSON: {e}"))?;
+    if let Some(info) = jj::JjInfo::from_json(&value) {
+        if config.jj_config {
+            let mut config = config.clone();
+            jj::apply_user_config(&jj::load_user_config(cwd), &mut config);
+            return Ok(output::format_jj(&info, &config));
+        }
+        return Ok(output::format_jj(&info, config));
+    }
+    #[cfg(feature = "git")]
+    if let Some(info) = git::GitInfo::from_json(&value) {
+        return Ok(output::format_git(&info, config));
+    }
+    Err("expected an object with `change_id` (JJ) or `head_short` (Git)".into())
+}
@@ -0,0 +1,52 @@
+//! Shell hooks printed by `jj-starship init <shell>`
+//! Runs `prefetch` in the background on directory change so the first prompt
+//! in a new repo hits warm caches
+
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Shell {
+    Zsh,
+    Fish,
+    Bash,
+}
+
+/// Hook script for `shell`, invoking the binary at `exe`
+pub fn script(shell: Shell, exe: &str) -> String {
+    match shell {
+        Shell::Zsh => format!(
+            "_jj_starship_prefetch() {{ {exe} prefetch >/dev/null 2>&1 &! }}\n\
+             autoload -Uz add-zsh-hook\n\
+             add-zsh-hook chpwd _jj_starship_prefetch\n",
+            exe = posix_quote(exe)
+        ),
+        Shell::Fish => format!(
+            "function __jj_starship_prefetch --on-variable PWD\n    \
+             {exe} prefetch >/dev/null 2>&1 &\n    \
+             disown 2>/dev/null\n\
+             end\n",
+            exe = fish_quote(exe)
+        ),
+        // bash has no chpwd hook: compare $PWD before each prompt
+        Shell::Bash => format!(
+            "__jj_starship_prefetch() {{\n    \
+             if [[ \"$PWD\" != \"${{__jj_starship_pwd-}}\" ]]; then\n        \
+             __jj_starship_pwd=\"$PWD\"\n        \
+             ({exe} prefetch >/dev/null 2>&1 &)\n    \
+             fi\n\
+             }}\n\
+             PROMPT_COMMAND=\"__jj_starship_prefetch${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}\"\n",
+            exe = posix_quote(exe)
+        ),
+    }
+}
+
+fn posix_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn fish_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', r"\\").replace('\'', r"\'"))
+}
@@ -1,18 +1,41 @@
 //! JJ repository info collection
 
+use crate::color::{self, Color, Style};
+use crate::config::{self, Config, Detail};
 use crate::error::{Error, Result};
-use jj_lib::config::{ConfigLayer, ConfigSource, StackedConfig};
+use crate::json;
+use crate::log;
+use crate::message;
+use crate::term::ColorTier;
+use crate::timing::Timings;
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::config::{ConfigLayer, ConfigNamePathBuf, ConfigSource, StackedConfig};
+use jj_lib::git::REMOTE_NAME_FOR_LOCAL_GIT_REPO;
+use jj_lib::gitignore::GitIgnoreFile;
 use jj_lib::hex_util::encode_reverse_hex;
+use jj_lib::merged_tree::MergedTree;
 use jj_lib::object_id::ObjectId;
-use jj_lib::repo::{Repo, StoreFactories};
+use jj_lib::operation::Operation;
+use jj_lib::repo::{ReadonlyRepo, Repo, StoreFactories};
+use jj_lib::repo_path::{RepoPathBuf, RepoPathUiConverter};
+use jj_lib::revset::{
+    self, ResolvedRevsetExpression, RevsetAliasesMap, RevsetDiagnostics, RevsetExtensions,
+    RevsetParseContext, RevsetWorkspaceContext, SymbolResolver,
+};
 use jj_lib::settings::UserSettings;
 use jj_lib::str_util::{StringMatcher, StringPattern};
+use jj_lib::view::View;
 use jj_lib::workspace::{Workspace, default_working_copy_factories};
-use std::path::Path;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 /// JJ repository status info
-#[derive(Debug)]
+#[derive(Debug, Default)]
 #[allow(clippy::struct_excessive_bools)]
 pub struct JjInfo {
     /// Short change ID (8 chars)
@@ -23,19 +46,149 @@ pub struct JjInfo {
     pub empty_desc: bool,
     /// Has conflicts in tree
     pub conflict: bool,
+    /// An ancestor down to the nearest bookmark has conflicts
+    pub conflict_below: bool,
     /// Multiple commits for same `change_id`
     pub divergent: bool,
+    /// Visible commits for the `change_id` (0 when not checked)
+    pub divergent_commits: usize,
+    /// Shortest unique commit ID prefixes of the divergent commits, to address each one
+    pub divergent_ids: Vec<String>,
     /// Bookmark exists on a remote
     pub has_remote: bool,
     /// Local bookmark == remote bookmark
     pub is_synced: bool,
+    /// Remote the bookmark tracks (only collected with `jj_tracking`)
+    pub remote: Option<String>,
+    /// Commits ahead of the tracked remote bookmark
+    pub ahead: usize,
+    /// Commits behind the tracked remote bookmark
+    pub behind: usize,
+    /// Status checks skipped by the large-repo policy
+    pub status_skipped: bool,
+    /// Working-copy commit has changes (only collected with `state_colors`)
+    pub has_changes: bool,
+    /// Sparse patterns of a sparse workspace (0 for a full checkout)
+    pub sparse_patterns: usize,
+    /// New files over `snapshot.max-new-file-size` (only collected with `jj_large_files`)
+    pub large_files: usize,
+    /// Description starts with one of `wip_prefixes`
+    pub wip: bool,
+    /// Conventional-commit type of the description (`feat`, `fix`, ...)
+    pub commit_type: Option<String>,
+    /// Author email differs from the configured `user.email` (only collected with `foreign_author`)
+    pub foreign_author: bool,
+    /// End of the last operation, in seconds since the Unix epoch (0 when unknown)
+    pub op_time: usize,
+    /// First word of the last operation that isn't a snapshot (`rebase`, `squash`)
+    pub last_action: Option<String>,
+    /// End of that operation, in seconds since the Unix epoch
+    pub action_time: usize,
+    /// First line of the description
+    pub description: Option<String>,
+    /// Workspace name, unless it is `default`
+    pub workspace: Option<String>,
+    /// Short ID of the operation the repo was loaded at
+    pub op_id: Option<String>,
+    /// A bookmark on the commit is the default branch (only collected with `default_branch`)
+    pub on_default: bool,
+    /// Served from an outdated cache entry because collection failed
+    pub stale: bool,
+    /// Another repo encloses this one (set by the caller with `nested`)
+    pub nested: bool,
+}
+
+impl JjInfo {
+    /// Placeholder for a repo jj-lib can't load (e.g. written by an incompatible
+    /// jj version): `?` with status marked skipped rather than a blank prompt
+    pub fn unreadable() -> Self {
+        Self {
+            change_id: "?".into(),
+            status_skipped: true,
+            ..Self::default()
+        }
+    }
+
+    /// Serialize as a JSON object (field names match the struct)
+    pub fn to_json(&self) -> json::Value {
+        json::Value::Object(vec![
+            ("change_id".into(), self.change_id.as_str().into()),
+            ("bookmark".into(), self.bookmark.as_deref().into()),
+            ("empty_desc".into(), self.empty_desc.into()),
+            ("conflict".into(), self.conflict.into()),
+            ("conflict_below".into(), self.conflict_below.into()),
+            ("divergent".into(), self.divergent.into()),
+            ("divergent_commits".into(), self.divergent_commits.into()),
+            ("divergent_ids".into(), self.divergent_ids.clone().into()),
+            ("sparse_patterns".into(), self.sparse_patterns.into()),
+            ("large_files".into(), self.large_files.into()),
+            ("wip".into(), self.wip.into()),
+            ("commit_type".into(), self.commit_type.as_deref().into()),
+            ("foreign_author".into(), self.foreign_author.into()),
+            ("op_time".into(), self.op_time.into()),
+            ("last_action".into(), self.last_action.as_deref().into()),
+            ("action_time".into(), self.action_time.into()),
+            ("description".into(), self.description.as_deref().into()),
+            ("workspace".into(), self.workspace.as_deref().into()),
+            ("op_id".into(), self.op_id.as_deref().into()),
+            ("on_default".into(), self.on_default.into()),
+            ("stale".into(), self.stale.into()),
+            ("nested".into(), self.nested.into()),
+            ("has_remote".into(), self.has_remote.into()),
+            ("is_synced".into(), self.is_synced.into()),
+            ("remote".into(), self.remote.as_deref().into()),
+            ("ahead".into(), self.ahead.into()),
+            ("behind".into(), self.behind.into()),
+            ("status_skipped".into(), self.status_skipped.into()),
+            ("has_changes".into(), self.has_changes.into()),
+        ])
+    }
+
+    /// Deserialize from a JSON object; only `change_id` is required
+    pub fn from_json(value: &json::Value) -> Option<Self> {
+        Some(Self {
+            change_id: value.get_str("change_id")?,
+            bookmark: value.get_str("bookmark"),
+            empty_desc: value.get_bool("empty_desc"),
+            conflict: value.get_bool("conflict"),
+            conflict_below: value.get_bool("conflict_below"),
+            divergent: value.get_bool("divergent"),
+            divergent_commits: value.get_usize("divergent_commits"),
+            divergent_ids: value.get_strings("divergent_ids"),
+            sparse_patterns: value.get_usize("sparse_patterns"),
+            large_files: value.get_usize("large_files"),
+            wip: value.get_bool("wip"),
+            commit_type: value.get_str("commit_type"),
+            foreign_author: value.get_bool("foreign_author"),
+            op_time: value.get_usize("op_time"),
+            last_action: value.get_str("last_action"),
+            action_time: value.get_usize("action_time"),
+            description: value.get_str("description"),
+            workspace: value.get_str("workspace"),
+            op_id: value.get_str("op_id"),
+            on_default: value.get_bool("on_default"),
+            stale: value.get_bool("stale"),
+            nested: value.get_bool("nested"),
+            has_remote: value.get_bool("has_remote"),
+            is_synced: value.get_bool("is_synced"),
+            remote: value.get_str("remote"),
+            ahead: value.get_usize("ahead"),
+            behind: value.get_usize("behind"),
+            status_skipped: value.get_bool("status_skipped"),
+            has_changes: value.get_bool("has_changes"),
+        })
+    }
 }
 
 /// Create minimal `UserSettings` for read-only operations
-fn create_user_settings() -> Result<UserSettings> {
-    let mut config = StackedConfig::with_defaults();
+/// Layers the user's jj config underneath when given (`--jj-config`)
+fn create_user_settings(user_config: Option<&StackedConfig>) -> Result<UserSettings> {
+    let mut config = user_config
+        .cloned()
+        .unwrap_or_else(StackedConfig::with_defaults);
 
-    // Minimal config required by UserSettings
+    // Minimal config required by UserSettings; the user's own identity is
+    // read from `user_config` where it matters
     let mut user_layer = ConfigLayer::empty(ConfigSource::User);
     user_layer
         .set_value("user.name", "jj-starship")
@@ -48,53 +201,187 @@ fn create_user_settings() -> Result<UserSettings> {
     UserSettings::from_config(config).map_err(|e| Error::Jj(format!("settings: {e}")))
 }
 
-/// Collect JJ repo info from the given path
-pub fn collect(repo_root: &Path, id_length: usize) -> Result<JjInfo> {
-    let settings = create_user_settings()?;
+/// Load the user's jj config (user files + repo config) on top of jj-lib defaults
+/// Unreadable or malformed files are skipped rather than failing the prompt
+pub fn load_user_config(repo_root: &Path) -> StackedConfig {
+    let mut config = StackedConfig::with_defaults();
+    for path in user_config_paths() {
+        if let Ok(layer) = ConfigLayer::load_from_file(ConfigSource::User, path) {
+            config.add_layer(layer);
+        }
+    }
+    let repo_config = repo_root.join(".jj").join("repo").join("config.toml");
+    if repo_config.is_file() {
+        if let Ok(layer) = ConfigLayer::load_from_file(ConfigSource::Repo, repo_config) {
+            config.add_layer(layer);
+        }
+    }
+    config
+}
 
-    let workspace = Workspace::load(
-        &settings,
-        repo_root,
-        &StoreFactories::default(),
-        &default_working_copy_factories(),
-    )
-    .map_err(|e| Error::Jj(format!("load workspace: {e}")))?;
+/// User config locations, following jj: `$JJ_CONFIG`, else `~/.jjconfig.toml`
+/// and `$XDG_CONFIG_HOME/jj/{config.toml,conf.d/}`
+fn user_config_paths() -> Vec<PathBuf> {
+    if let Some(paths) = env::var_os("JJ_CONFIG") {
+        return env::split_paths(&paths)
+            .flat_map(expand_config_path)
+            .collect();
+    }
 
-    let repo: Arc<jj_lib::repo::ReadonlyRepo> = workspace
-        .repo_loader()
-        .load_at_head()
-        .map_err(|e| Error::Jj(format!("load repo: {e}")))?;
+    let config_dir = config::xdg_dir("XDG_CONFIG_HOME", ".config");
 
-    let view = repo.view();
+    let mut paths = Vec::new();
+    if let Some(home) = config::home_dir() {
+        paths.extend(expand_config_path(home.join(".jjconfig.toml")));
+    }
+    if let Some(dir) = config_dir {
+        let jj_dir = dir.join("jj");
+        paths.extend(expand_config_path(jj_dir.join("config.toml")));
+        paths.extend(expand_config_path(jj_dir.join("conf.d")));
+    }
+    paths
+}
+
+/// A config path is either a file or a directory of `*.toml` files (sorted)
+fn expand_config_path(path: PathBuf) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path];
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(&path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Apply the user's jj `ui.color` and `[colors]` settings to the JJ prompt
+pub fn apply_user_config(user_config: &StackedConfig, config: &mut Config) {
+    if user_config
+        .get::<String>("ui.color")
+        .is_ok_and(|mode| mode == "never")
+    {
+        config.jj_display.show_color = false;
+    }
+
+    let tier = config.colors;
+    let palette = &mut config.jj_palette;
+    if let Some(c) = user_color(user_config, &["colors.bookmark", "colors.bookmarks"], tier) {
+        palette.name = c;
+    }
+    if let Some(c) = user_color(user_config, &["colors.change_id"], tier) {
+        palette.id = c;
+    }
+    if let Some(c) = user_color(user_config, &["colors.conflict"], tier) {
+        palette.status = c;
+    }
+}
 
-    // Get WC commit ID
-    let wc_id = view
-        .wc_commit_ids()
-        .get(workspace.workspace_name())
-        .ok_or_else(|| Error::Jj("no working copy".into()))?;
+/// First configured style among `names`; accepts `"red"` or
+/// `{ fg = "red", bg = "black", bold = true, italic = true, underline = true }`
+fn user_color(
+    user_config: &StackedConfig,
+    names: &[&str],
+    tier: ColorTier,
+) -> Option<Cow<'static, str>> {
+    names.iter().find_map(|name| {
+        let name: ConfigNamePathBuf = name.parse().ok()?;
+        let value = user_config.get_value(&name).ok()?;
+        if let Some(fg) = value.as_str() {
+            return color::parse(fg, tier);
+        }
+        let table = value.as_inline_table()?;
+        let color_of = |key| table.get(key)?.as_str().and_then(Color::parse);
+        let flag = |key| {
+            table
+                .get(key)
+                .and_then(toml_edit::Value::as_bool)
+                .unwrap_or(false)
+        };
+        let style = Style {
+            fg: color_of("fg"),
+            bg: color_of("bg"),
+            bold: flag("bold"),
+            dim: flag("dim"),
+            italic: flag("italic"),
+            underline: flag("underline"),
+        };
+        (style != Style::default()).then(|| style.downgrade(tier).escape())
+    })
+}
+
+/// Collect JJ repo info from the given path
+#[cfg_attr(feature = "tracing", tracing::instrument(skip_all, fields(root = %repo_root.display())))]
+pub fn collect(
+    repo_root: &Path,
+    config: &Config,
+    user_config: Option<&StackedConfig>,
+    timings: &Timings,
+) -> Result<JjInfo> {
+    // A user config UserSettings rejects shouldn't blank the prompt
+    let settings = create_user_settings(user_config).or_else(|e| {
+        log::warning!("jj config rejected, using defaults: {e}");
+        create_user_settings(None)
+    })?;
+    let (workspace, repo) = timings.time("repo open", || load_workspace(repo_root, &settings))?;
 
-    // Load commit
-    let commit = repo
-        .store()
-        .get_commit(wc_id)
-        .map_err(|e| Error::Jj(format!("get commit: {e}")))?;
+    let view = repo.view();
+    let op_time = end_time(repo.operation());
+    let op_hex = repo.op_id().hex();
+    let op_id = op_hex[..config.id_length.min(op_hex.len())].to_string();
+    let (last_action, action_time) = last_action(repo.operation());
+
+    let commit = target_commit(&repo, &workspace, user_config, config)?;
+    let wc_id = commit.id();
 
     // Change ID in JJ's reverse hex format
     let change_id_full = encode_reverse_hex(commit.change_id().as_bytes());
-    let change_id = change_id_full[..id_length.min(change_id_full.len())].to_string();
+    let change_id = change_id_full[..config.id_length.min(change_id_full.len())].to_string();
 
     // Empty description check
     let empty_desc = commit.description().trim().is_empty();
+    let wip = config.is_wip(commit.description());
+    let commit_type = message::conventional_type(commit.description());
+    let foreign_author =
+        config.foreign_author && !config.refs_only && is_foreign(&commit, user_config);
 
-    // Conflict check
-    let conflict = commit.has_conflict();
+    // Large-repo policy: skip tree/index queries on configured slow paths, and
+    // the scan once it no longer fits the time budget (`priority`)
+    let slow_path = config.is_slow_path(repo_root);
+    let check_conflict =
+        !slow_path && !config.refs_only && timings.allows(config, Detail::Conflict);
+    let status_skipped =
+        slow_path || (!config.refs_only && !timings.allows(config, Detail::Status));
+    let check_status = !status_skipped && !config.refs_only;
 
-    // Divergent check - multiple commits for same change_id
-    let divergent = repo
-        .resolve_change_id(commit.change_id())
-        .ok()
-        .flatten()
-        .is_some_and(|commits| commits.len() > 1);
+    let (conflict, conflict_below, divergent_ids, has_changes) = timings
+        .time("status scan", || {
+            scan_commit(&repo, &commit, check_conflict, check_status, config)
+        });
+    let divergent_commits = divergent_ids.len();
+    let divergent = divergent_commits > 1;
+    let divergent_ids = if divergent && timings.allows(config, Detail::Id) {
+        divergent_ids
+            .iter()
+            .map(|id| shortest_commit_prefix(&repo, id))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let sparse_patterns = if check_status {
+        sparse_pattern_count(&workspace)
+    } else {
+        0
+    };
+
+    let large_files = if check_status && config.jj_large_files && config.rev.is_none() {
+        large_new_files(repo_root, &commit, &settings, timings)
+    } else {
+        0
+    };
 
     // Find bookmark at WC commit
     let bookmark: Option<String> = view
@@ -102,29 +389,529 @@ pub fn collect(repo_root: &Path, id_length: usize) -> Result<JjInfo> {
         .next()
         .map(|(name, _)| name.as_str().to_string());
 
+    let on_default = config.default_branch && on_default(&repo, &workspace, user_config, wc_id);
+
     // Check remote sync status (only if we have a bookmark)
-    let (has_remote, is_synced) = if let Some(ref bm_name) = bookmark {
-        let name_matcher = StringPattern::exact(bm_name).to_matcher();
-        let remote_matcher = StringMatcher::All;
-
-        // Single pass over remote bookmarks
-        view.remote_bookmarks_matching(&name_matcher, &remote_matcher)
-            .filter(|(symbol, _)| symbol.remote.as_str() != "git")
-            .fold((false, false), |(_, synced), (_, remote_ref)| {
-                let this_synced = remote_ref.target.as_normal().is_some_and(|id| id == wc_id);
-                (true, synced || this_synced)
-            })
-    } else {
-        (false, true)
-    };
+    let (has_remote, is_synced) = bookmark
+        .as_deref()
+        .map_or((false, true), |bm_name| remote_sync(view, bm_name, wc_id));
+    let (remote, ahead, behind) = tracking(&repo, bookmark.as_deref(), wc_id, config, timings);
 
     Ok(JjInfo {
         change_id,
         bookmark,
         empty_desc,
         conflict,
+        conflict_below,
         divergent,
+        divergent_commits,
+        divergent_ids,
         has_remote,
         is_synced,
+        remote,
+        ahead,
+        behind,
+        status_skipped,
+        has_changes,
+        sparse_patterns,
+        large_files,
+        wip,
+        commit_type,
+        foreign_author,
+        op_time,
+        last_action,
+        action_time,
+        description: message::summary(commit.description()),
+        workspace: Some(workspace.workspace_name().as_str())
+            .filter(|name| *name != "default")
+            .map(String::from),
+        op_id: Some(op_id),
+        on_default,
+        stale: false,
+        nested: false,
     })
 }
+
+/// The working-copy commit, or the `--rev` revision's
+fn target_commit(
+    repo: &Arc<ReadonlyRepo>,
+    workspace: &Workspace,
+    user_config: Option<&StackedConfig>,
+    config: &Config,
+) -> Result<Commit> {
+    let id = match config.rev.as_deref() {
+        None | Some("@") => repo
+            .view()
+            .wc_commit_ids()
+            .get(workspace.workspace_name())
+            .ok_or_else(|| Error::Jj("no working copy".into()))?
+            .clone(),
+        Some(rev) => resolve_rev(repo, workspace, user_config, rev)?,
+    };
+    repo.store()
+        .get_commit(&id)
+        .map_err(|e| Error::Jj(format!("get commit: {e}")))
+}
+
+/// Load the workspace and its repo at the current operation
+fn load_workspace(
+    repo_root: &Path,
+    settings: &UserSettings,
+) -> Result<(Workspace, Arc<ReadonlyRepo>)> {
+    let workspace = Workspace::load(
+        settings,
+        repo_root,
+        &StoreFactories::default(),
+        &default_working_copy_factories(),
+    )
+    .map_err(|e| Error::Jj(format!("load workspace: {e}")))?;
+
+    let repo: Arc<ReadonlyRepo> = workspace
+        .repo_loader()
+        .load_at_head()
+        .map_err(|e| Error::Jj(format!("load repo: {e}")))?;
+    Ok((workspace, repo))
+}
+
+/// Someone else's change (or no `user.email` here); settings carry a
+/// placeholder email, so the user's config is asked directly
+fn is_foreign(commit: &Commit, user_config: Option<&StackedConfig>) -> bool {
+    !commit
+        .author()
+        .email
+        .eq_ignore_ascii_case(&user_email(user_config))
+}
+
+/// The user's `user.email`, empty when not configured
+fn user_email(user_config: Option<&StackedConfig>) -> String {
+    user_config
+        .and_then(|c| c.get::<String>("user.email").ok())
+        .unwrap_or_default()
+}
+
+/// Conflicts on and below the commit, the commits sharing its change ID, and
+/// whether it has changes
+fn scan_commit(
+    repo: &ReadonlyRepo,
+    commit: &Commit,
+    check_conflict: bool,
+    check_status: bool,
+    config: &Config,
+) -> (bool, bool, Vec<CommitId>, bool) {
+    // Conflict check
+    let conflict = check_conflict && commit.has_conflict();
+    let conflict_below = check_status && conflict_below(repo, commit);
+
+    // Divergent check - multiple commits for same change_id
+    let divergent_ids = if check_status {
+        repo.resolve_change_id(commit.change_id())
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    // Non-empty working copy (diffs the tree against its parents), the most
+    // expensive check, so slow paths and the time budget skip it too
+    let has_changes =
+        check_status && config.state_colors && commit.is_empty(repo).is_ok_and(|empty| !empty);
+    (conflict, conflict_below, divergent_ids, has_changes)
+}
+
+/// Sparse workspace: anything but the single root pattern of a full checkout
+fn sparse_pattern_count(workspace: &Workspace) -> usize {
+    workspace
+        .working_copy()
+        .sparse_patterns()
+        .map_or(0, |patterns| match patterns {
+            [pattern] if pattern.is_root() => 0,
+            patterns => patterns.len(),
+        })
+}
+
+/// New files jj leaves out of snapshots, which it only reports once
+fn large_new_files(
+    repo_root: &Path,
+    commit: &Commit,
+    settings: &UserSettings,
+    timings: &Timings,
+) -> usize {
+    let limit = max_new_file_size(settings);
+    if limit == 0 {
+        return 0;
+    }
+    timings.time("new file scan", || {
+        let mut count = 0;
+        let ignores = exclude_file(repo_root);
+        count_large_new_files(repo_root, "", &ignores, &commit.tree(), limit, &mut count);
+        count
+    })
+}
+
+/// Whether a bookmark has remote copies (other than `git`), and whether any
+/// of them points at `wc_id`, in a single pass over the remote bookmarks
+fn remote_sync(view: &View, bm_name: &str, wc_id: &CommitId) -> (bool, bool) {
+    let name_matcher = StringPattern::exact(bm_name).to_matcher();
+    view.remote_bookmarks_matching(&name_matcher, &StringMatcher::All)
+        .filter(|(symbol, _)| symbol.remote.as_str() != "git")
+        .fold((false, false), |(_, synced), (_, remote_ref)| {
+            let this_synced = remote_ref.target.as_normal().is_some_and(|id| id == wc_id);
+            (true, synced || this_synced)
+        })
+}
+
+/// Tracked remote and per-remote ahead/behind (opt-in, needs revset evaluation)
+fn tracking(
+    repo: &ReadonlyRepo,
+    bookmark: Option<&str>,
+    wc_id: &CommitId,
+    config: &Config,
+    timings: &Timings,
+) -> (Option<String>, usize, usize) {
+    let Some(bm_name) = bookmark else {
+        return (None, 0, 0);
+    };
+    if !config.jj_tracking || config.refs_only || !timings.allows(config, Detail::AheadBehind) {
+        return (None, 0, 0);
+    }
+    match tracked_remote(repo.view(), bm_name) {
+        Some((remote, Some(remote_id))) => timings.time("ahead/behind", || {
+            let ahead = count_range(repo, &remote_id, wc_id);
+            let behind = count_range(repo, wc_id, &remote_id);
+            (Some(remote), ahead, behind)
+        }),
+        Some((remote, None)) => (Some(remote), 0, 0),
+        None => (None, 0, 0),
+    }
+}
+
+/// End of an operation, in seconds since the Unix epoch
+fn end_time(op: &Operation) -> usize {
+    usize::try_from(op.metadata().time.end.timestamp.0 / 1000).unwrap_or_default()
+}
+
+/// First word of the latest operation's description (`rebase`, `squash`,
+/// `new`) and its end time, skipping working-copy snapshots, which would
+/// otherwise always be the latest; gives up after 10 operations
+fn last_action(op: &Operation) -> (Option<String>, usize) {
+    let mut op = op.clone();
+    for _ in 0..10 {
+        let metadata = op.metadata();
+        if !metadata.is_snapshot {
+            let action = metadata.description.split_whitespace().next();
+            return (action.map(String::from), end_time(&op));
+        }
+        let Some(Ok(parent)) = op.parents().next() else {
+            break;
+        };
+        op = parent;
+    }
+    (None, 0)
+}
+
+/// Shortest commit ID prefix that resolves to `id` alone
+fn shortest_commit_prefix(repo: &ReadonlyRepo, id: &CommitId) -> String {
+    let hex = id.hex();
+    let len = repo
+        .index()
+        .shortest_unique_commit_id_prefix_len(id)
+        .unwrap_or(hex.len());
+    hex[..len.min(hex.len())].to_string()
+}
+
+/// Most ancestors `conflict_below` looks at
+const STACK_LIMIT: usize = 100;
+
+/// Some ancestor from the parents down to the nearest bookmarked commit has
+/// conflicts, i.e. the stack needs resolving; stops at the root commit and
+/// after `STACK_LIMIT` commits
+fn conflict_below(repo: &ReadonlyRepo, commit: &Commit) -> bool {
+    let store = repo.store();
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<CommitId> = commit.parent_ids().iter().cloned().collect();
+    while let Some(id) = queue.pop_front() {
+        if id == *store.root_commit_id() || !seen.insert(id.clone()) {
+            continue;
+        }
+        if seen.len() > STACK_LIMIT {
+            return false;
+        }
+        let Ok(ancestor) = store.get_commit(&id) else {
+            continue;
+        };
+        if ancestor.has_conflict() {
+            return true;
+        }
+        if repo.view().local_bookmarks_for_commit(&id).next().is_none() {
+            queue.extend(ancestor.parent_ids().iter().cloned());
+        }
+    }
+    false
+}
+
+/// jj's built-in `trunk()`, which jj-lib leaves to the CLI's default config
+const BUILTIN_TRUNK: &str = r#"latest(
+  remote_bookmarks(exact:"main", exact:"origin") |
+  remote_bookmarks(exact:"master", exact:"origin") |
+  remote_bookmarks(exact:"trunk", exact:"origin") |
+  remote_bookmarks(exact:"main", exact:"upstream") |
+  remote_bookmarks(exact:"master", exact:"upstream") |
+  remote_bookmarks(exact:"trunk", exact:"upstream") |
+  root()
+)"#;
+
+/// The user's `revset-aliases` over the built-in `trunk()`; an alias jj-lib
+/// rejects is skipped
+fn revset_aliases(user_config: Option<&StackedConfig>) -> RevsetAliasesMap {
+    let mut aliases = RevsetAliasesMap::new();
+    if let Err(e) = aliases.insert("trunk()", BUILTIN_TRUNK) {
+        log::warning!("built-in trunk(): {e}");
+    }
+    for layer in user_config.iter().flat_map(|c| c.layers()) {
+        let Ok(Some(table)) = layer.look_up_table("revset-aliases") else {
+            continue;
+        };
+        for (decl, item) in table.iter() {
+            let Some(value) = item.as_str() else {
+                continue;
+            };
+            if let Err(e) = aliases.insert(decl, value) {
+                log::warning!("revset-aliases.{decl}: {e}");
+            }
+        }
+    }
+    aliases
+}
+
+/// Resolve a revset (`@-`, `main@origin`, `trunk()`, a change ID prefix) to a
+/// single commit, expanding the user's revset aliases as jj does
+fn resolve_rev(
+    repo: &Arc<ReadonlyRepo>,
+    workspace: &Workspace,
+    user_config: Option<&StackedConfig>,
+    rev: &str,
+) -> Result<CommitId> {
+    let aliases = revset_aliases(user_config);
+    let root = workspace.workspace_root().to_path_buf();
+    let path_converter = RepoPathUiConverter::Fs {
+        cwd: root.clone(),
+        base: root,
+    };
+    let extensions = RevsetExtensions::default();
+    let user_email = user_email(user_config);
+    let context = RevsetParseContext {
+        aliases_map: &aliases,
+        local_variables: HashMap::new(),
+        user_email: &user_email,
+        date_pattern_context: chrono::Local::now().into(),
+        default_ignored_remote: Some(REMOTE_NAME_FOR_LOCAL_GIT_REPO),
+        use_glob_by_default: false,
+        extensions: &extensions,
+        workspace: Some(RevsetWorkspaceContext {
+            path_converter: &path_converter,
+            workspace_name: workspace.workspace_name(),
+        }),
+    };
+    let expression = revset::parse(&mut RevsetDiagnostics::new(), rev, &context)
+        .map_err(|e| Error::Jj(format!("parse {rev}: {e}")))?;
+    let symbol_resolver = SymbolResolver::new(repo.as_ref(), extensions.symbol_resolvers());
+    let revset = expression
+        .resolve_user_expression(repo.as_ref(), &symbol_resolver)
+        .map_err(|e| Error::Jj(format!("resolve {rev}: {e}")))?
+        .evaluate(repo.as_ref())
+        .map_err(|e| Error::Jj(format!("evaluate {rev}: {e}")))?;
+    let mut commits = revset.iter();
+    let id = commits
+        .next()
+        .transpose()
+        .map_err(|e| Error::Jj(format!("evaluate {rev}: {e}")))?
+        .ok_or_else(|| Error::Jj(format!("{rev}: no such revision")))?;
+    if commits.next().is_some() {
+        return Err(Error::Jj(format!(
+            "{rev}: resolves to more than one revision"
+        )));
+    }
+    Ok(id)
+}
+
+/// jj's default `snapshot.max-new-file-size`
+const DEFAULT_MAX_NEW_FILE_SIZE: u64 = 1024 * 1024;
+
+/// `snapshot.max-new-file-size` in bytes; 0 means no limit
+fn max_new_file_size(settings: &UserSettings) -> u64 {
+    settings
+        .get_value("snapshot.max-new-file-size")
+        .ok()
+        .and_then(|value| match value.as_integer() {
+            Some(bytes) => u64::try_from(bytes).ok(),
+            None => parse_size(value.as_str()?),
+        })
+        .unwrap_or(DEFAULT_MAX_NEW_FILE_SIZE)
+}
+
+/// Byte size with an optional binary unit: `1048576`, `512KiB`, `1MiB`, `2 GB`
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let shift = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 0,
+        "k" | "kb" | "kib" => 10,
+        "m" | "mb" | "mib" => 20,
+        "g" | "gb" | "gib" => 30,
+        "t" | "tb" | "tib" => 40,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+/// Ignore rules jj starts from: the repo's `info/exclude`, if any
+fn exclude_file(repo_root: &Path) -> Arc<GitIgnoreFile> {
+    let ignores = GitIgnoreFile::empty();
+    let exclude = repo_root.join(".git").join("info").join("exclude");
+    ignores.chain_with_file("", exclude).unwrap_or(ignores)
+}
+
+/// Count unignored files under `dir` that are over `limit` and not in `tree`,
+/// following `.gitignore` files as jj does; `prefix` is `dir`'s repo path
+fn count_large_new_files(
+    dir: &Path,
+    prefix: &str,
+    ignores: &Arc<GitIgnoreFile>,
+    tree: &MergedTree,
+    limit: u64,
+    count: &mut usize,
+) {
+    let ignores = ignores
+        .chain_with_file(prefix, dir.join(".gitignore"))
+        .unwrap_or_else(|_| ignores.clone());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str() else {
+            continue;
+        };
+        if prefix.is_empty() && (name == ".jj" || name == ".git") {
+            continue;
+        }
+        let path = format!("{prefix}{name}");
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            let dir_prefix = format!("{path}/");
+            if !ignores.matches(&dir_prefix) {
+                count_large_new_files(&entry.path(), &dir_prefix, &ignores, tree, limit, count);
+            }
+        } else if file_type.is_file()
+            && !ignores.matches(&path)
+            && entry.metadata().is_ok_and(|m| m.len() > limit)
+            && RepoPathBuf::from_internal_string(path).is_ok_and(|repo_path| {
+                tree.path_value(&repo_path)
+                    .is_ok_and(|value| value.is_absent())
+            })
+        {
+            *count += 1;
+        }
+    }
+}
+
+/// Names of the remote bookmarks `trunk()` resolves to: the user's alias
+/// (`main@origin`, as `jj git clone` sets it) or jj's built-in one, the first
+/// of `main`, `master` and `trunk` on `origin` or `upstream`
+fn default_bookmarks(
+    repo: &Arc<ReadonlyRepo>,
+    workspace: &Workspace,
+    user_config: Option<&StackedConfig>,
+) -> Vec<String> {
+    let trunk = match resolve_rev(repo, workspace, user_config, "trunk()") {
+        Ok(trunk) => trunk,
+        Err(e) => {
+            log::debug!("no default branch: {e}");
+            return Vec::new();
+        }
+    };
+    repo.view()
+        .remote_bookmarks_matching(&StringMatcher::All, &StringMatcher::All)
+        .filter(|(symbol, remote_ref)| {
+            symbol.remote.as_str() != "git" && remote_ref.target.as_normal() == Some(&trunk)
+        })
+        .map(|(symbol, _)| symbol.name.as_str().to_string())
+        .collect()
+}
+
+/// One of the default branch's bookmarks points at `wc_id`
+fn on_default(
+    repo: &Arc<ReadonlyRepo>,
+    workspace: &Workspace,
+    user_config: Option<&StackedConfig>,
+    wc_id: &CommitId,
+) -> bool {
+    let defaults = default_bookmarks(repo, workspace, user_config);
+    repo.view()
+        .local_bookmarks_for_commit(wc_id)
+        .any(|(name, _)| defaults.iter().any(|default| name.as_str() == default))
+}
+
+/// Find the remote a bookmark tracks, preferring `origin` when several do
+fn tracked_remote(view: &View, bm_name: &str) -> Option<(String, Option<CommitId>)> {
+    let name_matcher = StringPattern::exact(bm_name).to_matcher();
+    let mut tracked: Vec<(String, Option<CommitId>)> = view
+        .remote_bookmarks_matching(&name_matcher, &StringMatcher::All)
+        .filter(|(symbol, remote_ref)| symbol.remote.as_str() != "git" && remote_ref.is_tracked())
+        .map(|(symbol, remote_ref)| {
+            (
+                symbol.remote.as_str().to_string(),
+                remote_ref.target.as_normal().cloned(),
+            )
+        })
+        .collect();
+
+    let idx = tracked
+        .iter()
+        .position(|(remote, _)| remote == "origin")
+        .unwrap_or(0);
+    (!tracked.is_empty()).then(|| tracked.swap_remove(idx))
+}
+
+/// Count commits in `from..to`
+fn count_range(repo: &ReadonlyRepo, from: &CommitId, to: &CommitId) -> usize {
+    ResolvedRevsetExpression::commits(vec![from.clone()])
+        .range(&ResolvedRevsetExpression::commits(vec![to.clone()]))
+        .evaluate(repo)
+        .map_or(0, |revset| revset.iter().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Some(1024 * 1024));
+        assert_eq!(parse_size("1MiB"), Some(1024 * 1024));
+        assert_eq!(parse_size("512 KiB"), Some(512 * 1024));
+        assert_eq!(parse_size("2gb"), Some(2 << 30));
+        assert_eq!(parse_size("MiB"), None);
+        assert_eq!(parse_size("1 parsec"), None);
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let info = JjInfo {
+            change_id: "kxqpmtzv".into(),
+            divergent: true,
+            divergent_commits: 2,
+            divergent_ids: vec!["3f2a".into(), "9c0".into()],
+            ..JjInfo::default()
+        };
+        let parsed = JjInfo::from_json(&json::parse(&info.to_json().to_string()).unwrap()).unwrap();
+        assert_eq!(parsed.divergent_commits, 2);
+        assert_eq!(parsed.divergent_ids, ["3f2a", "9c0"]);
+    }
+}
@@ -0,0 +1,78 @@
+//! jj-starship as a library: the binary's entry point, plus collection and
+//! rendering for embedders such as the C bindings in `ffi/`
+
+#[cfg(feature = "cli")]
+mod bench;
+mod cache;
+mod cli;
+mod color;
+mod config;
+mod custom;
+#[cfg(feature = "cli")]
+mod demo;
+mod detect;
+mod error;
+mod exports;
+#[cfg(feature = "git")]
+mod git;
+mod glob;
+mod hook;
+#[cfg(feature = "cli")]
+mod html;
+#[cfg(feature = "cli")]
+mod import;
+mod info;
+#[cfg(feature = "cli")]
+mod init;
+mod jj;
+mod json;
+mod log;
+mod message;
+#[cfg(feature = "cli")]
+mod migrate;
+mod netfs;
+mod osc;
+mod output;
+#[cfg(feature = "cli")]
+mod porcelain;
+#[cfg(feature = "cli")]
+mod schema;
+#[cfg(feature = "cli")]
+mod serve;
+mod symbols;
+mod template;
+mod term;
+mod timing;
+#[cfg(feature = "cli")]
+mod validate;
+#[cfg(feature = "cli")]
+mod version;
+
+pub use cli::run;
+
+use config::{Config, ConfigArgs};
+use std::path::Path;
+use timing::Timings;
+
+/// Info for the repo containing `path`, as the JSON document `jj-starship info`
+/// prints; None outside a repo or on error
+#[must_use]
+pub fn collect_json(path: &Path) -> Option<String> {
+    let config = Config::new(ConfigArgs::default());
+    let result = detect::detect_enabled(path, &config, None);
+    info::collect(result, &config, &Timings::new(false)).map(|value| value.to_string())
+}
+
+/// Render an info document (from `collect_json` or hand-written) as a prompt
+/// `config_toml` is config file contents used in place of the config file, or
+/// None to read it as the binary does; None on invalid input
+#[must_use]
+pub fn render_json(json: &str, config_toml: Option<&str>) -> Option<String> {
+    let config = Config::new(ConfigArgs {
+        config_toml: config_toml.map(String::from),
+        ..ConfigArgs::default()
+    });
+    info::render(json, Path::new("."), &config)
+        .inspect_err(|e| log::warning!("{e}"))
+        .ok()
+}
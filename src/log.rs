@@ -0,0 +1,58 @@
+//! Structured logging behind the `tracing` feature
+//! `JJ_STARSHIP_LOG` sets the filter (e.g. `debug`, `jj_starship=trace`) and
+//! `JJ_STARSHIP_LOG_FILE` appends to a file instead of stderr.
+//! Without the feature the macros compile to nothing; they take format
+//! arguments only, not `tracing` field syntax.
+
+/// Debug event, compiled out without the `tracing` feature
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+/// Warning event for fallbacks, compiled out without the `tracing` feature
+macro_rules! warning {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        ::tracing::warn!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+pub(crate) use {debug, warning};
+
+/// Install the subscriber when `JJ_STARSHIP_LOG` is set
+#[cfg(feature = "tracing")]
+pub fn init() {
+    use std::env;
+    use std::fs::OpenOptions;
+    use std::io;
+    use std::sync::Mutex;
+    use tracing_subscriber::EnvFilter;
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    let Ok(filter) = EnvFilter::try_from_env("JJ_STARSHIP_LOG") else {
+        return;
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_span_events(FmtSpan::CLOSE);
+
+    let file = env::var_os("JJ_STARSHIP_LOG_FILE")
+        .and_then(|path| OpenOptions::new().create(true).append(true).open(path).ok());
+    let _ = match file {
+        Some(file) => builder
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .try_init(),
+        None => builder.with_writer(io::stderr).try_init(),
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+pub const fn init() {}
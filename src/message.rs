@@ -0,0 +1,50 @@
+//! Commit message parsing shared by the JJ and Git collectors
+
+/// Conventional-commit type of a message's first line (`feat(ui)!: ...` gives
+/// `feat`), lowercased
+pub fn conventional_type(message: &str) -> Option<String> {
+    let line = message.lines().next()?.trim_start();
+    let end = line
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(line.len());
+    let (kind, mut rest) = line.split_at(end);
+    if kind.is_empty() {
+        return None;
+    }
+    if let Some(scoped) = rest.strip_prefix('(') {
+        rest = &scoped[scoped.find(')')? + 1..];
+    }
+    let rest = rest.strip_prefix('!').unwrap_or(rest);
+    rest.starts_with(':').then(|| kind.to_ascii_lowercase())
+}
+
+/// A message's first line, trimmed; None when it is blank
+pub fn summary(message: &str) -> Option<String> {
+    let line = message.lines().next()?.trim();
+    (!line.is_empty()).then(|| line.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conventional_type() {
+        assert_eq!(conventional_type("feat: add it"), Some("feat".into()));
+        assert_eq!(
+            conventional_type("Fix(parser)!: oops\n\nbody"),
+            Some("fix".into())
+        );
+        assert_eq!(conventional_type("chore!: drop 1.x"), Some("chore".into()));
+        assert_eq!(conventional_type("Update README"), None);
+        assert_eq!(conventional_type("fix(unclosed: no"), None);
+        assert_eq!(conventional_type(""), None);
+    }
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(summary("  fix: it \n\nbody"), Some("fix: it".into()));
+        assert_eq!(summary("\nbody"), None);
+        assert_eq!(summary(""), None);
+    }
+}
@@ -0,0 +1,108 @@
+//! Command line lookup for `jj-starship config migrate`
+//! Finds the jj-starship invocation in a starship.toml `[custom.*]` module so
+//! its flags can be resolved like any other command line
+
+use std::path::Path;
+use toml_edit::{Document, Item};
+
+/// Arguments of the first `[custom.*]` `command` that runs jj-starship,
+/// program name included
+pub fn command_args(starship_toml: &str) -> Result<Vec<String>, String> {
+    let doc = Document::parse(starship_toml).map_err(|e| e.message().trim().to_string())?;
+    let Some(custom) = doc.as_table().get("custom").and_then(Item::as_table_like) else {
+        return Err("no [custom] modules".into());
+    };
+    custom
+        .iter()
+        .filter_map(|(_, module)| module.as_table_like()?.get("command")?.as_str())
+        .filter_map(split)
+        .find(|args| {
+            args.first()
+                .and_then(|program| Path::new(program).file_name())
+                .is_some_and(|name| name == "jj-starship")
+        })
+        .ok_or_else(|| "no [custom] module runs jj-starship".into())
+}
+
+/// Shell words: whitespace-separated, with `'...'`, `"..."` and backslash
+/// escapes; None for an unterminated quote
+fn split(command: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next()? {
+                        '"' => break,
+                        '\\' => match chars.next()? {
+                            c @ ('"' | '\\' | '$' | '`') => word.push(c),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).push(chars.next()?),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_args() {
+        let toml = r#"
+            [custom.time]
+            command = "date +%T"
+
+            [custom.jj]
+            command = "~/bin/jj-starship --id-length 6 --prefix-text '' --suffix \" \""
+            when = "jj-starship detect"
+        "#;
+        assert_eq!(
+            command_args(toml).unwrap(),
+            [
+                "~/bin/jj-starship",
+                "--id-length",
+                "6",
+                "--prefix-text",
+                "",
+                "--suffix",
+                " "
+            ]
+        );
+        assert!(command_args("[custom.time]\ncommand = \"date\"\n").is_err());
+        assert!(command_args("format = \"$all\"\n").is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            split(r#"a "b \"c\"" d\ e 'f\g'"#).unwrap(),
+            ["a", "b \"c\"", "d e", r"f\g"]
+        );
+        assert_eq!(split("  ").unwrap(), Vec::<String>::new());
+        assert_eq!(split("'open"), None);
+    }
+}
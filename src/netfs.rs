@@ -0,0 +1,134 @@
+//! Network filesystem detection for the large-repo policy
+//! Status scans over NFS, SMB or network FUSE mounts can take seconds, so repos on them
+//! skip file status like `slow_paths` unless `network_status` is set.
+//! Linux reads the mount table; on Windows UNC paths are network shares
+
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::sync::OnceLock;
+
+/// Filesystem types served over the network, FUSE ones included; local FUSE
+/// filesystems (`fuse.gocryptfs`, `fuse.lxcfs`, ...) aren't listed
+const NETWORK_TYPES: &[&str] = &[
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "afs",
+    "ceph",
+    "glusterfs",
+    "lustre",
+    "gpfs",
+    "davfs",
+    "fuse.sshfs",
+    "fuse.rclone",
+    "fuse.s3fs",
+    "fuse.gcsfuse",
+    "fuse.goofys",
+    "fuse.glusterfs",
+    "fuse.ceph-fuse",
+    "fuse.cephfs",
+    "fuse.davfs2",
+    "fuse.smbnetfs",
+    "fuse.curlftpfs",
+    "fuse.juicefs",
+    "fuse.blobfuse",
+    "fuse.blobfuse2",
+    "fuse.mountpoint-s3",
+];
+
+/// Whether `path` is on a network filesystem
+pub fn is_network(path: &Path) -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        static MOUNTS: OnceLock<String> = OnceLock::new();
+        let mounts =
+            MOUNTS.get_or_init(|| std::fs::read_to_string("/proc/self/mounts").unwrap_or_default());
+        fstype(mounts, path).is_some_and(is_network_type)
+    }
+    #[cfg(windows)]
+    {
+        path.to_string_lossy().starts_with(r"\\")
+    }
+    #[cfg(not(any(target_os = "linux", windows)))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn is_network_type(fstype: &str) -> bool {
+    NETWORK_TYPES.contains(&fstype)
+}
+
+/// Type of the innermost mount containing `path`, from `/proc/self/mounts` lines
+/// (`device mount_point type options dump pass`)
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn fstype<'a>(mounts: &'a str, path: &Path) -> Option<&'a str> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(' ');
+            let mount_point = unescape(fields.nth(1)?);
+            let fstype = fields.next()?;
+            path.starts_with(&mount_point)
+                .then_some((mount_point.len(), fstype))
+        })
+        // Later mounts shadow earlier ones on the same point
+        .max_by_key(|(len, _)| *len)
+        .map(|(_, fstype)| fstype)
+}
+
+/// Undo the octal escapes (`\040` for a space) the kernel uses in mount points
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(pos) = rest.find('\\') {
+        out.push_str(&rest[..pos]);
+        let code = rest.get(pos + 1..pos + 4);
+        if let Some(byte) = code.and_then(|c| u8::from_str_radix(c, 8).ok()) {
+            out.push(char::from(byte));
+            rest = &rest[pos + 4..];
+        } else {
+            out.push('\\');
+            rest = &rest[pos + 1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTS: &str = "/dev/sda1 / ext4 rw,relatime 0 0
+server:/export /home nfs4 rw,vers=4.2 0 0
+/dev/sdb1 /home/me/local ext4 rw 0 0
+//nas/share /mnt/my\\040share cifs rw 0 0
+sshfs#me@host: /mnt/remote fuse.sshfs rw 0 0";
+
+    #[test]
+    fn test_fstype() {
+        let mount_type = |path: &str| fstype(MOUNTS, Path::new(path));
+        assert_eq!(mount_type("/usr/src"), Some("ext4"));
+        assert_eq!(mount_type("/home/me/repo"), Some("nfs4"));
+        assert_eq!(mount_type("/home/me/local/repo"), Some("ext4"));
+        assert_eq!(mount_type("/mnt/my share/repo"), Some("cifs"));
+        assert_eq!(mount_type("/mnt/remote/repo"), Some("fuse.sshfs"));
+    }
+
+    #[test]
+    fn test_network_types() {
+        assert!(is_network_type("nfs4"));
+        assert!(is_network_type("fuse.sshfs"));
+        assert!(is_network_type("fuse.rclone"));
+        assert!(!is_network_type("fuse.gocryptfs"));
+        assert!(!is_network_type("fuse.lxcfs"));
+        assert!(!is_network_type("fuseblk"));
+        assert!(!is_network_type("ext4"));
+    }
+}
@@ -0,0 +1,91 @@
+//! Shell-integration escape sequences (`osc`)
+//! OSC 7 reports the working directory; OSC 1337 `SetUserVar` (`iTerm2`, `WezTerm`)
+//! carries repo data for status bars. Both go straight to `/dev/tty`: inside the
+//! prompt text they would count toward its width in shells that only know how
+//! to skip color codes
+
+use crate::exports::Exports;
+use std::env;
+use std::fmt::Write as _;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Write the cwd and repo user vars to the controlling terminal, if there is one
+pub fn emit(cwd: &Path, exports: &Exports) {
+    let out = sequences(cwd, exports);
+    if let Ok(mut tty) = OpenOptions::new().write(true).open("/dev/tty") {
+        let _ = tty.write_all(out.as_bytes());
+    }
+}
+
+fn sequences(cwd: &Path, exports: &Exports) -> String {
+    let host = env::var("HOSTNAME").unwrap_or_default();
+    let mut out = format!(
+        "\x1b]7;file://{host}{}\x1b\\",
+        percent_encode(&cwd.to_string_lossy())
+    );
+    for (name, value) in [
+        ("jj_starship_repo_type", exports.repo_type.as_str()),
+        ("jj_starship_branch", &exports.branch),
+        ("jj_starship_change_id", &exports.change_id),
+        ("jj_starship_commit", &exports.commit),
+    ] {
+        let _ = write!(
+            out,
+            "\x1b]1337;SetUserVar={name}={}\x07",
+            base64(value.as_bytes())
+        );
+    }
+    out
+}
+
+/// Percent-encode a path for a `file://` URL, keeping `/` and unreserved characters
+fn percent_encode(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            out.push(char::from(byte));
+        } else {
+            let _ = write!(out, "%{byte:02X}");
+        }
+    }
+    out
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let byte = |i: usize| u32::from(chunk.get(i).copied().unwrap_or(0));
+        let n = (byte(0) << 16) | (byte(1) << 8) | byte(2);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(BASE64[(n >> (18 - 6 * i)) as usize & 63]));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"m"), "bQ==");
+        assert_eq!(base64(b"ma"), "bWE=");
+        assert_eq!(base64(b"main"), "bWFpbg==");
+    }
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("/src/my repo"), "/src/my%20repo");
+        assert_eq!(percent_encode("/tmp/ü"), "/tmp/%C3%BC");
+    }
+}
@@ -1,14 +1,15 @@
 //! Output formatting for prompt strings
 
 use std::borrow::Cow;
-#[cfg(feature = "git")]
-use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::color::{BLUE, GREEN, PURPLE, RED, RESET};
-use crate::config::Config;
+use crate::color::{GREEN, RED, RESET, YELLOW};
+use crate::config::{Config, Indicator, Palette, Segment};
 #[cfg(feature = "git")]
 use crate::git::GitInfo;
 use crate::jj::JjInfo;
+use crate::log;
+use crate::template;
 
 fn format_segment(text: &str, color: &str, show_color: bool) -> String {
     if show_color {
@@ -18,139 +19,968 @@ fn format_segment(text: &str, color: &str, show_color: bool) -> String {
     }
 }
 
-/// Format JJ info as prompt string
-/// Pattern: `on {symbol}{name} ({id}) [{status}]`
-pub fn format_jj(info: &JjInfo, config: &Config) -> String {
-    let mut out = String::with_capacity(128);
-    let display = &config.jj_display;
+/// Status block built from individually colored indicators, rendered as
+/// glyphs (`+2⇡1`) or, with `plain_words`, as words (`2 staged, 1 ahead`)
+struct StatusLine<'a> {
+    palette: &'a Palette,
+    pieces: Vec<(String, &'a str)>,
+    /// Append counts to file-status and jj symbols (`counts`)
+    counts: bool,
+    /// Words instead of glyphs (`plain_words`)
+    words: bool,
+    /// Indicators left out (`hide`)
+    hidden: &'a [Indicator],
+}
 
-    // "on {symbol}" prefix
-    if display.show_prefix {
-        out.push_str("on ");
-        out.push_str(&format_segment(&config.jj_symbol, BLUE, display.show_color));
+impl<'a> StatusLine<'a> {
+    fn new(palette: &'a Palette, config: &'a Config) -> Self {
+        Self {
+            palette,
+            pieces: Vec::new(),
+            counts: config.counts,
+            words: config.plain_words,
+            hidden: &config.hide,
+        }
     }
 
-    // Name in purple (bookmark or change_id prefix)
-    let name: Cow<str> = info
-        .bookmark
-        .as_ref()
-        .map_or(Cow::Borrowed(&info.change_id), |bm| config.truncate(bm));
+    /// Push the glyph or word form of an indicator; words are comma-separated
+    fn push_text(
+        &mut self,
+        indicator: Indicator,
+        glyph: impl Into<String>,
+        word: impl Into<String>,
+    ) {
+        if self.hidden.contains(&indicator) {
+            return;
+        }
+        let palette = self.palette;
+        let text = if self.words {
+            if !self.pieces.is_empty() {
+                let status: &'a str = &palette.status;
+                self.pieces.push((", ".to_string(), status));
+            }
+            word.into()
+        } else {
+            glyph.into()
+        };
+        self.pieces.push((text, palette.indicator(indicator)));
+    }
 
-    if display.show_name {
-        out.push_str(&format_segment(&name, PURPLE, display.show_color));
+    fn push(&mut self, indicator: Indicator, glyph: &str) {
+        self.push_text(indicator, glyph, word(indicator));
     }
 
-    // ID in green - skip if same as name (deduplicate)
-    if display.show_id && *name != info.change_id {
-        if !out.is_empty() {
-            out.push(' ');
+    /// Push an indicator that always carries its count (ahead/behind)
+    fn push_number(&mut self, indicator: Indicator, glyph: &str, count: usize) {
+        self.push_text(
+            indicator,
+            format!("{glyph}{count}"),
+            format!("{count} {}", word(indicator)),
+        );
+    }
+
+    /// Push a symbol, followed by its count when counts are on
+    fn push_count(&mut self, indicator: Indicator, glyph: &str, count: usize) {
+        if self.counts {
+            self.push_number(indicator, glyph, count);
+        } else {
+            self.push(indicator, glyph);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pieces.is_empty()
+    }
+
+    /// `[…]` in the status color; runs of same-colored pieces share one segment
+    fn render(&self, show_color: bool) -> String {
+        self.render_runs(true, show_color)
+    }
+
+    /// The indicators without brackets, for format templates
+    fn render_bare(&self, show_color: bool) -> String {
+        self.render_runs(false, show_color)
+    }
+
+    fn render_runs(&self, brackets: bool, show_color: bool) -> String {
+        let status: &str = &self.palette.status;
+        let bracket = |text: &str| brackets.then(|| (text.to_string(), status));
+        let mut runs: Vec<(String, &str)> = bracket("[").into_iter().collect();
+        for (text, color) in self.pieces.iter().cloned().chain(bracket("]")) {
+            match runs.last_mut() {
+                Some((run, run_color)) if *run_color == color => run.push_str(&text),
+                _ => runs.push((text, color)),
+            }
+        }
+        runs.iter()
+            .map(|(text, color)| format_segment(text, color, show_color))
+            .collect()
+    }
+}
+
+/// Word form of an indicator for `plain_words`
+const fn word(indicator: Indicator) -> &'static str {
+    match indicator {
+        Indicator::Skipped => "status skipped",
+        Indicator::Conflict => "conflicted",
+        Indicator::ConflictBelow => "conflicts below",
+        Indicator::Divergent => "divergent",
+        Indicator::Undescribed => "undescribed",
+        Indicator::Wip => "draft",
+        Indicator::ForeignAuthor => "foreign author",
+        Indicator::DefaultBranch => "default branch",
+        Indicator::Sparse => "sparse",
+        Indicator::LargeFile => "too large to snapshot",
+        Indicator::Unreadable => "unreadable",
+        Indicator::LfsLocked => "locked",
+        Indicator::LfsUnlocked => "edited without lock",
+        Indicator::Staged => "staged",
+        Indicator::Modified => "modified",
+        Indicator::Untracked => "untracked",
+        Indicator::Deleted => "deleted",
+        Indicator::Renamed => "renamed",
+        Indicator::TypeChanged => "type changed",
+        Indicator::Ahead => "ahead",
+        Indicator::Behind => "behind",
+    }
+}
+
+/// Name color for `state_colors`: red conflicted, yellow dirty, green clean
+const fn state_color(conflicted: bool, dirty: bool) -> &'static str {
+    if conflicted {
+        RED
+    } else if dirty {
+        YELLOW
+    } else {
+        GREEN
+    }
+}
+
+/// Prompt line assembled from segments in the configured order
+struct Line {
+    out: String,
+    last: Option<Segment>,
+    /// Status goes on a second line (`block`)
+    block: bool,
+    details: String,
+    /// Each segment's text, for format templates
+    segments: Vec<(Segment, String)>,
+    /// Template variables beyond the segments, looked up first
+    vars: Vec<(String, String)>,
+    /// Marks appended after a format template's output
+    marker: String,
+}
+
+impl Line {
+    fn new(block: bool) -> Self {
+        Self {
+            out: String::with_capacity(128),
+            last: None,
+            block,
+            details: String::new(),
+            segments: Vec::new(),
+            vars: Vec::new(),
+            marker: String::new(),
+        }
+    }
+
+    /// Space-separated, except a name directly after the `{prefix_text}{symbol}` prefix
+    fn push(&mut self, segment: Segment, text: &str) {
+        self.segments.push((segment, text.to_string()));
+        if self.block && segment == Segment::Status {
+            self.details.push_str(text);
+            return;
+        }
+        let glued = segment == Segment::Name && self.last == Some(Segment::Prefix);
+        if !self.out.is_empty() && !glued {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+        self.last = Some(segment);
+    }
+
+    /// Append `marker` to the last segment, unspaced
+    fn mark(&mut self, marker: &str) {
+        if !self.out.is_empty() {
+            self.out.push_str(marker);
         }
-        let id_text = format!("({})", &info.change_id);
-        out.push_str(&format_segment(&id_text, GREEN, display.show_color));
+        self.marker.push_str(marker);
+    }
+
+    /// Set a format template variable
+    fn var(&mut self, name: impl Into<String>, text: String) {
+        self.vars.push((name.into(), text));
     }
 
-    // Status indicators in red (priority: ! > ⇔ > ? > ⇡)
-    if display.show_status {
-        let mut status = String::new();
-        if info.conflict {
-            status.push('!');
+    /// The line laid out by a format template instead: variables (with the
+    /// `custom.*` command output), then segments by name, with the suffix
+    /// appended unless the result is empty
+    fn finish_format(mut self, format: &str, config: &Config) -> String {
+        for (name, value) in &config.custom_values {
+            self.var(format!("custom.{name}"), value.clone());
         }
-        if info.divergent {
-            status.push('⇔');
+        let var = |name: &str| {
+            self.vars
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, text)| text)
+                .or_else(|| {
+                    let segment = self.segments.iter().find(|(s, _)| s.name() == name);
+                    segment.map(|(_, text)| text)
+                })
+                .cloned()
+        };
+        let mut out = template::expand(format, &var);
+        if !out.is_empty() {
+            out.push_str(&self.marker);
+            out.push_str(&config.suffix);
         }
-        if info.empty_desc {
-            status.push('?');
+        out
+    }
+
+    /// The rendered line, with `suffix` appended unless it is empty
+    /// In block mode the status follows on its own line, without a trailing newline
+    fn finish(mut self, suffix: &str) -> String {
+        if !self.details.is_empty() {
+            if !self.out.is_empty() {
+                self.out.push('\n');
+            }
+            self.out.push_str(&self.details);
         }
-        if info.has_remote && !info.is_synced {
-            status.push('⇡');
+        if !self.out.is_empty() {
+            self.out.push_str(suffix);
         }
+        self.out
+    }
+}
+
+/// Columns the widest line takes: the cells of the chars outside `ESC [ ... m`
+/// color sequences
+pub fn visible_width(out: &str) -> usize {
+    out.lines()
+        .map(|line| {
+            let mut width = 0;
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                if c == '\x1b' {
+                    chars.by_ref().find(|&c| c == 'm');
+                } else {
+                    width += char_width(c);
+                }
+            }
+            width
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Terminal cells a char takes: none for combining marks, variation selectors
+/// and zero-width spaces, two for East Asian wide chars and emoji
+/// Nerd Font icons (private use) take one, as patched fonts draw them
+const fn char_width(c: char) -> usize {
+    match c {
+        '\u{0300}'..='\u{036F}'
+        | '\u{200B}'..='\u{200F}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{E0100}'..='\u{E01EF}' => 0,
+        // East Asian wide and fullwidth
+        '\u{1100}'..='\u{115F}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{20000}'..='\u{3FFFD}'
+        // Emoji drawn as emoji by default
+        | '\u{231A}'..='\u{231B}'
+        | '\u{23E9}'..='\u{23EC}'
+        | '\u{23F0}'
+        | '\u{23F3}'
+        | '\u{25FD}'..='\u{25FE}'
+        | '\u{2614}'..='\u{2615}'
+        | '\u{2648}'..='\u{2653}'
+        | '\u{267F}'
+        | '\u{2693}'
+        | '\u{26A1}'
+        | '\u{26AA}'..='\u{26AB}'
+        | '\u{26BD}'..='\u{26BE}'
+        | '\u{26C4}'..='\u{26C5}'
+        | '\u{26CE}'
+        | '\u{26D4}'
+        | '\u{26EA}'
+        | '\u{26F2}'..='\u{26F3}'
+        | '\u{26F5}'
+        | '\u{26FA}'
+        | '\u{26FD}'
+        | '\u{2705}'
+        | '\u{270A}'..='\u{270B}'
+        | '\u{2728}'
+        | '\u{274C}'
+        | '\u{274E}'
+        | '\u{2753}'..='\u{2755}'
+        | '\u{2757}'
+        | '\u{2795}'..='\u{2797}'
+        | '\u{27B0}'
+        | '\u{27BF}'
+        | '\u{2B1B}'..='\u{2B1C}'
+        | '\u{2B50}'
+        | '\u{2B55}'
+        | '\u{1F004}'
+        | '\u{1F0CF}'
+        | '\u{1F18E}'
+        | '\u{1F191}'..='\u{1F19A}'
+        | '\u{1F200}'..='\u{1F2FF}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F680}'..='\u{1F6FF}'
+        | '\u{1F7E0}'..='\u{1F7EB}'
+        | '\u{1F90C}'..='\u{1F9FF}'
+        | '\u{1FA70}'..='\u{1FAFF}' => 2,
+        _ => 1,
+    }
+}
 
-        if !status.is_empty() {
-            if !out.is_empty() {
-                out.push(' ');
+/// Backslash before each character starship's format strings treat specially
+/// (`[`, `]`, `$`, `\`), so a custom module shows names like `fix[2]` as they
+/// are; color sequences pass through untouched
+pub fn escape_starship(out: &str) -> String {
+    let mut escaped = String::with_capacity(out.len());
+    let mut chars = out.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => {
+                escaped.push(c);
+                for c in chars.by_ref() {
+                    escaped.push(c);
+                    if c == 'm' {
+                        break;
+                    }
+                }
             }
-            let status_text = format!("[{}]", &status);
-            out.push_str(&format_segment(&status_text, RED, display.show_color));
+            '[' | ']' | '$' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `out` without whitespace at its end, looking past the color sequences
+/// that close it (`trailing_space = false`)
+pub fn trim_trailing_space(out: &str) -> String {
+    let mut rest = out.trim_end();
+    // Sequences peeled off the end, outermost first
+    let mut closing = Vec::new();
+    while let Some(start) = rest.rfind('\x1b') {
+        let code = &rest[start..];
+        let is_color = code
+            .strip_prefix("\x1b[")
+            .and_then(|code| code.strip_suffix('m'))
+            .is_some_and(|params| params.chars().all(|c| c.is_ascii_digit() || c == ';'));
+        if !is_color {
+            break;
         }
+        closing.push(code);
+        rest = rest[..start].trim_end();
     }
+    let mut trimmed = rest.to_string();
+    trimmed.extend(closing.into_iter().rev());
+    trimmed
+}
 
+/// Render with every configured segment, then drop segments in `drop_order`
+/// until the line fits in `max_width`
+fn fit(config: &Config, render: impl Fn(&[Segment]) -> String) -> String {
+    let mut segments = config.segments.clone();
+    let mut out = render(&segments);
+    for dropped in &config.drop_order {
+        if config.max_width == 0 || visible_width(&out) <= config.max_width {
+            break;
+        }
+        let before = segments.len();
+        segments.retain(|s| s != dropped);
+        if segments.len() < before {
+            log::debug!(
+                "dropped {} to fit {} columns",
+                dropped.name(),
+                config.max_width
+            );
+            out = render(&segments);
+        }
+    }
     out
 }
 
+/// `▣` before the symbol of a repo nested inside another (`nested`)
+fn nested_marker(nested: bool, palette: &Palette, show_color: bool) -> String {
+    if nested {
+        format_segment("▣", &palette.symbol, show_color)
+    } else {
+        String::new()
+    }
+}
+
+/// Format JJ info as prompt string
+/// Pattern: `on {symbol}{name} ({id}) [{status}]` (order set by `segments`)
+pub fn format_jj(info: &JjInfo, config: &Config) -> String {
+    fit(config, |segments| {
+        render_jj(info, config, segments, &|| jj_status(info, config))
+    })
+}
+
+/// Format a colocated repo as JJ, with Git's file status added to the
+/// status block (`colocated = "merged"`)
+#[cfg(feature = "git")]
+pub fn format_merged(info: &JjInfo, git: &GitInfo, config: &Config) -> String {
+    fit(config, |segments| {
+        render_jj(info, config, segments, &|| {
+            let mut status = jj_status(info, config);
+            if git.status_skipped && !info.status_skipped {
+                status.push(Indicator::Skipped, "≋");
+            }
+            git_file_status(git, config, &mut status);
+            status
+        })
+    })
+}
+
+fn render_jj<'a>(
+    info: &JjInfo,
+    config: &'a Config,
+    segments: &[Segment],
+    status: &dyn Fn() -> StatusLine<'a>,
+) -> String {
+    let display = &config.jj_display;
+    let palette = &config.jj_palette;
+    let rule = info
+        .bookmark
+        .as_deref()
+        .and_then(|bm| config.branch_rule(bm));
+    let name_color: &str = match rule.and_then(|r| r.color.as_deref()) {
+        Some(color) => color,
+        // Refs-only prompts know nothing about the working copy
+        None if config.state_colors && !config.refs_only && !info.status_skipped => {
+            state_color(info.conflict, info.has_changes)
+        }
+        None => &palette.name,
+    };
+
+    // Name (bookmark or change_id prefix)
+    let name: Cow<str> = info
+        .bookmark
+        .as_ref()
+        .map_or(Cow::Borrowed(&info.change_id), |bm| config.truncate(bm));
+
+    let symbol = format_segment(
+        rule.and_then(|r| r.symbol.as_deref())
+            .unwrap_or(&config.jj_symbol),
+        &palette.symbol,
+        display.show_color,
+    );
+
+    let mut line = Line::new(config.block);
+    for &segment in segments {
+        match segment {
+            // "on {symbol}" prefix
+            Segment::Prefix if display.show_prefix => {
+                let nested = nested_marker(info.nested, palette, display.show_color);
+                line.push(segment, &format!("{}{nested}{symbol}", config.prefix_text));
+            }
+            Segment::Name if display.show_name => {
+                let mut text = format_segment(&name, name_color, display.show_color);
+                if let Some(remote) = &info.remote {
+                    let remote_text = format!("→{remote}");
+                    text.push_str(&format_segment(
+                        &remote_text,
+                        name_color,
+                        display.show_color,
+                    ));
+                }
+                line.push(segment, &text);
+            }
+            // ID - skip if same as name (deduplicate)
+            Segment::Id if display.show_id && *name != info.change_id => {
+                let id_text = format!("({})", &info.change_id);
+                line.push(
+                    segment,
+                    &format_segment(&id_text, &palette.id, display.show_color),
+                );
+            }
+            Segment::Status if display.show_status => {
+                let status = status();
+                if !status.is_empty() {
+                    line.push(segment, &status.render(display.show_color));
+                    line.var("status", status.render_bare(display.show_color));
+                }
+            }
+            Segment::Type => push_type(&mut line, info.commit_type.as_deref(), config),
+            Segment::OpAge if info.op_time > 0 => {
+                let text = format!("op {}", format_age(age(info.op_time)));
+                line.push(
+                    segment,
+                    &format_segment(&text, &palette.id, display.show_color),
+                );
+            }
+            Segment::Action => {
+                let (action, time) = (info.last_action.as_deref(), info.action_time);
+                push_action(&mut line, action, time, &palette.id, display.show_color);
+            }
+            Segment::Description | Segment::Workspace | Segment::OpId => {
+                let text = jj_text(info, segment, config);
+                push_text(&mut line, segment, text, &palette.id, display.show_color);
+            }
+            _ => {}
+        }
+    }
+    if info.stale {
+        line.mark(&config.stale_marker);
+    }
+
+    if config.jj_format.is_empty() {
+        return line.finish(&config.suffix);
+    }
+    let bookmark = info.bookmark.as_deref().map_or_else(String::new, |bm| {
+        format_segment(&config.truncate(bm), name_color, display.show_color)
+    });
+    line.var("symbol", symbol);
+    line.var("bookmark", bookmark);
+    line.var(
+        "change_id",
+        format_segment(&info.change_id, &palette.id, display.show_color),
+    );
+    line.finish_format(&config.jj_format, config)
+}
+
+/// Icon for a conventional commit type, when one is configured
+fn push_type(line: &mut Line, commit_type: Option<&str>, config: &Config) {
+    if let Some(icon) = commit_type.and_then(|t| config.commit_icon(t)) {
+        line.push(Segment::Type, icon);
+    }
+}
+
+/// Last repo action and how long ago it finished: `rebase 5m`
+fn push_action(line: &mut Line, action: Option<&str>, time: usize, color: &str, show_color: bool) {
+    if let Some(action) = action {
+        let text = format!("{action} {}", format_age(age(time)));
+        line.push(Segment::Action, &format_segment(&text, color, show_color));
+    }
+}
+
+/// A segment that is just its text, when there is some
+fn push_text(
+    line: &mut Line,
+    segment: Segment,
+    text: Option<Cow<str>>,
+    color: &str,
+    show_color: bool,
+) {
+    if let Some(text) = text {
+        line.push(segment, &format_segment(&text, color, show_color));
+    }
+}
+
+/// Text of JJ's `description` (truncated like names), `workspace` and `op_id` segments
+fn jj_text<'a>(info: &'a JjInfo, segment: Segment, config: &Config) -> Option<Cow<'a, str>> {
+    match segment {
+        Segment::Description => info.description.as_deref().map(|d| config.truncate(d)),
+        Segment::Workspace => info.workspace.as_deref().map(Cow::Borrowed),
+        Segment::OpId => info.op_id.as_ref().map(|id| Cow::Owned(format!("op:{id}"))),
+        _ => None,
+    }
+}
+
+/// Text of Git's `description` (truncated like names) and `workspace` segments
+#[cfg(feature = "git")]
+fn git_text<'a>(info: &'a GitInfo, segment: Segment, config: &Config) -> Option<Cow<'a, str>> {
+    match segment {
+        Segment::Description => info.description.as_deref().map(|d| config.truncate(d)),
+        Segment::Workspace => info.workspace.as_deref().map(Cow::Borrowed),
+        _ => None,
+    }
+}
+
+/// Seconds from `time` (seconds since the Unix epoch) to now
+fn age(time: usize) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    now.saturating_sub(time as u64)
+}
+
+/// Compact age in the largest whole unit: `42s`, `5m`, `3h`, `2d`
+fn format_age(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// JJ status indicators (priority: ≋ > ! > ↯ > ⇔ > ? > ✎ > ≠ > ⚑ > ⊂ > ⚠ > ⇡)
+fn jj_status<'a>(info: &JjInfo, config: &'a Config) -> StatusLine<'a> {
+    let mut status = StatusLine::new(&config.jj_palette, config);
+    if info.status_skipped {
+        status.push(Indicator::Skipped, "≋");
+    }
+    if info.conflict {
+        status.push(Indicator::Conflict, "!");
+    }
+    if info.conflict_below {
+        status.push(Indicator::ConflictBelow, "↯");
+    }
+    if info.divergent {
+        status.push_count(Indicator::Divergent, "⇔", info.divergent_commits);
+    }
+    if info.empty_desc {
+        status.push(Indicator::Undescribed, "?");
+    }
+    if info.wip {
+        status.push(Indicator::Wip, "✎");
+    }
+    if info.foreign_author {
+        status.push(Indicator::ForeignAuthor, "≠");
+    }
+    if info.on_default {
+        status.push(Indicator::DefaultBranch, "⚑");
+    }
+    if info.sparse_patterns > 0 {
+        status.push_count(Indicator::Sparse, "⊂", info.sparse_patterns);
+    }
+    if info.large_files > 0 {
+        status.push_count(Indicator::LargeFile, "⚠", info.large_files);
+    }
+    if info.remote.is_some() {
+        // Per-remote counts against the tracked bookmark
+        let thresholds = &config.jj_thresholds;
+        if thresholds.shows(Indicator::Ahead, info.ahead) {
+            status.push_number(Indicator::Ahead, "⇡", info.ahead);
+        }
+        if thresholds.shows(Indicator::Behind, info.behind) {
+            status.push_number(Indicator::Behind, "⇣", info.behind);
+        }
+    } else if info.has_remote && !info.is_synced {
+        status.push_text(Indicator::Ahead, "⇡", "unsynced");
+    }
+    status
+}
+
+/// Marker for a cwd inside a repo's `.jj` or `.git` directory
+/// Pattern: `on {symbol}(inside {dir})`
+pub fn format_internals(dir: &str, config: &Config) -> String {
+    let (symbol, display, palette) = if dir == ".git" {
+        (&config.git_symbol, &config.git_display, &config.git_palette)
+    } else {
+        (&config.jj_symbol, &config.jj_display, &config.jj_palette)
+    };
+    let mut line = Line::new(config.block);
+    if display.show_prefix {
+        let symbol = format_segment(symbol, &palette.symbol, display.show_color);
+        line.push(Segment::Prefix, &format!("{}{symbol}", config.prefix_text));
+    }
+    let marker = format_segment(
+        &format!("(inside {dir})"),
+        &palette.status,
+        display.show_color,
+    );
+    line.push(Segment::Name, &marker);
+    line.finish(&config.suffix)
+}
+
 /// Format Git info as prompt string
-/// Pattern: `on {symbol}{name} ({id}) [{status}]`
+/// Pattern: `on {symbol}{name} ({id}) [{status}]` (order set by `segments`)
 #[cfg(feature = "git")]
 pub fn format_git(info: &GitInfo, config: &Config) -> String {
-    let mut out = String::with_capacity(128);
+    fit(config, |segments| render_git(info, config, segments))
+}
+
+#[cfg(feature = "git")]
+fn render_git(info: &GitInfo, config: &Config, segments: &[Segment]) -> String {
     let display = &config.git_display;
+    let palette = &config.git_palette;
+    let rule = info.branch.as_deref().and_then(|b| config.branch_rule(b));
+
+    // Nothing was read from a repo that failed the ownership check
+    if info.untrusted {
+        return format_untrusted(config);
+    }
+    let symbol = format_segment(
+        rule.and_then(|r| r.symbol.as_deref())
+            .unwrap_or(&config.git_symbol),
+        &palette.symbol,
+        display.show_color,
+    );
+    let dirty = info.dirty || info.changed() > info.conflicted;
+    let name_color: &str = match rule.and_then(|r| r.color.as_deref()) {
+        Some(color) => color,
+        // Refs-only prompts know nothing about the working copy
+        None if config.state_colors && !config.refs_only && !info.status_skipped => {
+            state_color(info.conflicted > 0, dirty)
+        }
+        None => &palette.name,
+    };
+    let mut line = Line::new(config.block);
+    for &segment in segments {
+        match segment {
+            // "on {symbol}" prefix
+            Segment::Prefix if display.show_prefix => {
+                let nested = nested_marker(info.nested, palette, display.show_color);
+                line.push(segment, &format!("{}{nested}{symbol}", config.prefix_text));
+            }
+            // Name (branch or HEAD)
+            Segment::Name if display.show_name => {
+                let name: Cow<str> = info
+                    .branch
+                    .as_ref()
+                    .map_or(Cow::Borrowed("HEAD"), |b| config.truncate(b));
+                let mut text = format_segment(&name, name_color, display.show_color);
+                if let Some(parent) = &info.superproject {
+                    let parent_text = format!("⊂{parent}");
+                    text.push_str(&format_segment(
+                        &parent_text,
+                        &palette.symbol,
+                        display.show_color,
+                    ));
+                }
+                line.push(segment, &text);
+            }
+            Segment::Id if display.show_id => {
+                let id_text = format!("({})", &info.head_short);
+                line.push(
+                    segment,
+                    &format_segment(&id_text, &palette.id, display.show_color),
+                );
+            }
+            Segment::State => {
+                if let Some(text) = operation_text(info) {
+                    line.push(segment, &format_segment(&text, YELLOW, display.show_color));
+                }
+            }
+            Segment::Status if display.show_status => {
+                let status = git_status(info, config);
+                if !status.is_empty() {
+                    line.push(segment, &status.render(display.show_color));
+                    line.var("status", status.render_bare(display.show_color));
+                }
+            }
+            Segment::Type => push_type(&mut line, info.commit_type.as_deref(), config),
+            Segment::Base if info.base_ahead > 0 => {
+                let base = info.base.as_deref().unwrap_or("default");
+                let text = format!("{base}+{}", info.base_ahead);
+                line.push(
+                    segment,
+                    &format_segment(&text, &palette.id, display.show_color),
+                );
+            }
+            Segment::Action => {
+                let (action, time) = (info.last_action.as_deref(), info.action_time);
+                push_action(&mut line, action, time, &palette.id, display.show_color);
+            }
+            Segment::Description | Segment::Workspace => {
+                let text = git_text(info, segment, config);
+                push_text(&mut line, segment, text, &palette.id, display.show_color);
+            }
+            _ => {}
+        }
+    }
+
+    if config.git_format.is_empty() {
+        return line.finish(&config.suffix);
+    }
+    let branch = info.branch.as_deref().map_or_else(String::new, |b| {
+        format_segment(&config.truncate(b), name_color, display.show_color)
+    });
+    line.var("symbol", symbol);
+    line.var("branch", branch);
+    line.var(
+        "commit",
+        format_segment(&info.head_short, &palette.id, display.show_color),
+    );
+    line.finish_format(&config.git_format, config)
+}
 
-    // "on {symbol}" prefix
+/// Marker for a repo that failed the ownership check, of which nothing was read
+#[cfg(feature = "git")]
+fn format_untrusted(config: &Config) -> String {
+    let display = &config.git_display;
+    let palette = &config.git_palette;
+    let mut line = Line::new(config.block);
     if display.show_prefix {
-        out.push_str("on ");
-        out.push_str(&format_segment(
-            &config.git_symbol,
-            BLUE,
-            display.show_color,
-        ));
+        let symbol = format_segment(&config.git_symbol, &palette.symbol, display.show_color);
+        line.push(Segment::Prefix, &format!("{}{symbol}", config.prefix_text));
     }
+    let marker = format_segment("(untrusted)", &palette.status, display.show_color);
+    line.push(Segment::Name, &marker);
+    line.finish(&config.suffix)
+}
+
+/// In-progress operation with its progress: `REBASING 2/5`, `BISECTING ~3 steps`
+#[cfg(feature = "git")]
+fn operation_text(info: &GitInfo) -> Option<String> {
+    let op = info.operation.as_ref()?;
+    Some(match (info.bisect_steps, info.step, info.total) {
+        (Some(1), ..) => format!("{op} ~1 step"),
+        (Some(steps), ..) => format!("{op} ~{steps} steps"),
+        (None, 0, 0) => op.clone(),
+        (None, 0, left) => format!("{op} {left} left"),
+        (None, step, total) => format!("{op} {step}/{total}"),
+    })
+}
+
+/// Git status indicators
+#[cfg(feature = "git")]
+fn git_status<'a>(info: &GitInfo, config: &'a Config) -> StatusLine<'a> {
+    let mut status = StatusLine::new(&config.git_palette, config);
 
-    // Name in purple (branch or HEAD)
-    if display.show_name {
-        let name: Cow<str> = info
-            .branch
-            .as_ref()
-            .map_or(Cow::Borrowed("HEAD"), |b| config.truncate(b));
-        out.push_str(&format_segment(&name, PURPLE, display.show_color));
+    // File status (order: ≋ > ⚠ > = > + > » > ~ > ! > ? > ✘)
+    if info.status_skipped {
+        status.push(Indicator::Skipped, "≋");
+    }
+    if info.unreadable > 0 {
+        status.push_count(Indicator::Unreadable, "⚠", info.unreadable);
+    }
+    git_file_status(info, config, &mut status);
+    if info.wip {
+        status.push(Indicator::Wip, "✎");
+    }
+    if info.foreign_author {
+        status.push(Indicator::ForeignAuthor, "≠");
+    }
+    if info.on_default {
+        status.push(Indicator::DefaultBranch, "⚑");
+    }
+    if info.lfs_locked > 0 {
+        status.push_count(Indicator::LfsLocked, "⊠", info.lfs_locked);
+    }
+    if info.lfs_unlocked > 0 {
+        status.push_count(Indicator::LfsUnlocked, "⊘", info.lfs_unlocked);
     }
 
-    // ID in green
-    if display.show_id {
-        if !out.is_empty() {
-            out.push(' ');
+    // Ahead/behind: per compared remote (`origin⇡2 upstream⇣14`), else the upstream
+    if info.remotes.is_empty() {
+        push_ahead_behind(&mut status, config, None, info.ahead, info.behind);
+    } else {
+        let mut separator = "";
+        for counts in &info.remotes {
+            let remote = format!("{separator}{}", counts.remote);
+            if push_ahead_behind(
+                &mut status,
+                config,
+                Some(&remote),
+                counts.ahead,
+                counts.behind,
+            ) {
+                separator = " ";
+            }
         }
-        let id_text = format!("({})", &info.head_short);
-        out.push_str(&format_segment(&id_text, GREEN, display.show_color));
     }
+    status
+}
 
-    // Status indicators in red
-    if display.show_status {
-        let mut status = String::new();
+/// Ahead/behind arrows, after `remote` when comparing with several, collapsed
+/// into one symbol for a diverged branch with `git_diverged`; false when
+/// neither count shows
+#[cfg(feature = "git")]
+fn push_ahead_behind(
+    status: &mut StatusLine<'_>,
+    config: &Config,
+    remote: Option<&str>,
+    ahead: usize,
+    behind: usize,
+) -> bool {
+    let thresholds = &config.git_thresholds;
+    let show_ahead = thresholds.shows(Indicator::Ahead, ahead);
+    let show_behind = thresholds.shows(Indicator::Behind, behind);
+    let label = remote.unwrap_or_default();
+    let name = label.trim_start();
+    if config.git_diverged && show_ahead && show_behind {
+        let word = match remote {
+            Some(_) => format!("diverged from {name}, {ahead} ahead, {behind} behind"),
+            None => format!("diverged, {ahead} ahead, {behind} behind"),
+        };
+        let glyph = format!("{label}{}{ahead}/{behind}", config.git_diverged_symbol);
+        status.push_text(Indicator::Ahead, glyph, word);
+        return true;
+    }
+    if show_ahead {
+        let word = match remote {
+            Some(_) => format!("{ahead} ahead of {name}"),
+            None => format!("{ahead} ahead"),
+        };
+        status.push_text(Indicator::Ahead, format!("{label}⇡{ahead}"), word);
+    }
+    if show_behind {
+        // The remote name is already there when ahead showed
+        let label = if show_ahead { "" } else { label };
+        let word = match remote {
+            Some(_) => format!("{behind} behind {name}"),
+            None => format!("{behind} behind"),
+        };
+        status.push_text(Indicator::Behind, format!("{label}⇣{behind}"), word);
+    }
+    show_ahead || show_behind
+}
 
-        // File status (order: = > + > ! > ? > ✘)
-        if info.conflicted > 0 {
-            status.push('=');
-        }
-        if info.staged > 0 {
-            status.push('+');
+/// Git file-status indicators, per category or as one `±N` total
+#[cfg(feature = "git")]
+fn git_file_status(info: &GitInfo, config: &Config, status: &mut StatusLine<'_>) {
+    let thresholds = &config.git_thresholds;
+    if info.dirty {
+        // `git_simple_dirty`: changed, but not counted by category
+        status.push_text(Indicator::Modified, "*", "dirty");
+    } else if config.git_dirty_count {
+        // One `±N` total in place of the per-category symbols
+        let changed = info.changed();
+        if changed > 0 {
+            let capped = info.untracked > config.max_untracked && config.max_untracked > 0;
+            let suffix = if capped { "+" } else { "" };
+            status.push_text(
+                Indicator::Modified,
+                format!("±{changed}{suffix}"),
+                format!("{changed}{suffix} changed"),
+            );
         }
-        if info.modified > 0 {
-            status.push('!');
+    } else {
+        if thresholds.shows(Indicator::Conflict, info.conflicted) {
+            status.push_count(Indicator::Conflict, "=", info.conflicted);
         }
-        if info.untracked > 0 {
-            status.push('?');
+        if thresholds.shows(Indicator::Staged, info.staged) {
+            status.push_count(Indicator::Staged, "+", info.staged);
         }
-        if info.deleted > 0 {
-            status.push('✘');
+        if thresholds.shows(Indicator::Renamed, info.renamed) {
+            status.push_count(Indicator::Renamed, "»", info.renamed);
         }
-
-        // Ahead/behind
-        if info.ahead > 0 {
-            let _ = write!(status, "⇡{}", info.ahead);
+        if thresholds.shows(Indicator::TypeChanged, info.typechanged) {
+            status.push_count(Indicator::TypeChanged, "~", info.typechanged);
         }
-        if info.behind > 0 {
-            let _ = write!(status, "⇣{}", info.behind);
+        if thresholds.shows(Indicator::Modified, info.modified) {
+            status.push_count(Indicator::Modified, "!", info.modified);
         }
-
-        if !status.is_empty() {
-            if !out.is_empty() {
-                out.push(' ');
+        if thresholds.shows(Indicator::Untracked, info.untracked) {
+            if info.untracked > config.max_untracked && config.max_untracked > 0 {
+                // Enumeration stopped at the cap
+                let cap = config.max_untracked;
+                status.push_text(
+                    Indicator::Untracked,
+                    format!("?{cap}+"),
+                    format!("{cap}+ untracked"),
+                );
+            } else {
+                status.push_count(Indicator::Untracked, "?", info.untracked);
             }
-            let status_text = format!("[{}]", &status);
-            out.push_str(&format_segment(&status_text, RED, display.show_color));
+        }
+        if thresholds.shows(Indicator::Deleted, info.deleted) {
+            status.push_count(Indicator::Deleted, "✘", info.deleted);
         }
     }
-
-    out
 }
 
 #[cfg(test)]
@@ -158,10 +988,13 @@ mod tests {
     use super::*;
     use std::borrow::Cow;
 
-    #[cfg(feature = "git")]
-    use crate::config::DEFAULT_GIT_SYMBOL;
+    use crate::color::{BLUE, GREEN, PURPLE, RED};
     use crate::config::DEFAULT_JJ_SYMBOL;
-    use crate::config::DisplayConfig;
+    use crate::config::{BranchRule, DisplayConfig, RepoAlias};
+    #[cfg(feature = "git")]
+    use crate::config::{DEFAULT_GIT_SYMBOL, Thresholds};
+    #[cfg(feature = "git")]
+    use crate::git::RemoteCounts;
 
     #[allow(dead_code)]
     fn default_config() -> Config {
@@ -177,6 +1010,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         }
     }
 
@@ -190,6 +1024,7 @@ mod tests {
             divergent: false,
             has_remote: true,
             is_synced: true,
+            ..Default::default()
         };
         assert_eq!(
             format_jj(&info, &no_symbol_config()),
@@ -208,6 +1043,7 @@ mod tests {
             divergent: false,
             has_remote: false,
             is_synced: true,
+            ..Default::default()
         };
         assert_eq!(
             format_jj(&info, &no_symbol_config()),
@@ -225,6 +1061,7 @@ mod tests {
             divergent: false,
             has_remote: true,
             is_synced: true,
+            ..Default::default()
         };
         assert_eq!(
             format_jj(&info, &default_config()),
@@ -243,6 +1080,7 @@ mod tests {
             git_symbol: Cow::Borrowed(""),
             jj_display: DisplayConfig::all_visible(),
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         let info = JjInfo {
             change_id: "yzxv1234".into(),
@@ -252,6 +1090,7 @@ mod tests {
             divergent: false,
             has_remote: false,
             is_synced: true,
+            ..Default::default()
         };
         assert_eq!(
             format_jj(&info, &config),
@@ -269,6 +1108,7 @@ mod tests {
             divergent: false,
             has_remote: true,
             is_synced: true,
+            ..Default::default()
         };
         let config = Config {
             truncate_name: 0,
@@ -283,65 +1123,605 @@ mod tests {
                 show_color: false,
             },
             git_display: DisplayConfig::all_visible(),
+            ..Config::default()
         };
         assert_eq!(format_jj(&info, &config), "on 󱗆 main (yzxv1234)");
     }
 
-    #[cfg(feature = "git")]
     #[test]
-    fn test_git_format_clean() {
-        let info = GitInfo {
-            branch: Some("main".into()),
-            head_short: "a3b4c5d".into(),
-            staged: 0,
-            modified: 0,
-            untracked: 0,
-            deleted: 0,
-            conflicted: 0,
-            ahead: 0,
-            behind: 0,
+    fn test_branch_rules() {
+        let config = Config {
+            branch_rules: vec![
+                BranchRule {
+                    pattern: "main|master".into(),
+                    color: Some(Cow::Borrowed(GREEN)),
+                    symbol: None,
+                },
+                BranchRule {
+                    pattern: "hotfix/*".into(),
+                    color: Some(Cow::Borrowed(RED)),
+                    symbol: Some("⚠ ".into()),
+                },
+            ],
+            ..no_symbol_config()
+        };
+        let info = |bookmark: &str| JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some(bookmark.into()),
+            ..Default::default()
         };
         assert_eq!(
-            format_git(&info, &no_symbol_config()),
-            format!("on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(a3b4c5d){RESET}")
+            format_jj(&info("master"), &config),
+            format!("on {BLUE}{RESET}{GREEN}master{RESET} {GREEN}(yzxv1234){RESET}")
+        );
+        assert_eq!(
+            format_jj(&info("hotfix/login"), &config),
+            format!("on {BLUE}⚠ {RESET}{RED}hotfix/login{RESET} {GREEN}(yzxv1234){RESET}")
+        );
+        assert_eq!(
+            format_jj(&info("feature"), &config),
+            format!("on {BLUE}{RESET}{PURPLE}feature{RESET} {GREEN}(yzxv1234){RESET}")
         );
     }
 
-    #[cfg(feature = "git")]
     #[test]
-    fn test_git_format_dirty() {
-        let info = GitInfo {
-            branch: Some("feature".into()),
-            head_short: "1234567".into(),
-            staged: 2,
-            modified: 3,
-            untracked: 1,
-            deleted: 0,
-            conflicted: 0,
-            ahead: 2,
-            behind: 1,
+    fn test_state_colors() {
+        let config = Config {
+            state_colors: true,
+            ..no_symbol_config()
         };
-        assert_eq!(
-            format_git(&info, &no_symbol_config()),
-            format!(
-                "on {BLUE}{RESET}{PURPLE}feature{RESET} {GREEN}(1234567){RESET} {RED}[+!?⇡2⇣1]{RESET}"
-            )
-        );
+        let info = |conflict: bool, has_changes: bool| JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict,
+            has_changes,
+            ..Default::default()
+        };
+        assert!(format_jj(&info(false, false), &config).contains(&format!("{GREEN}main")));
+        assert!(format_jj(&info(false, true), &config).contains(&format!("{YELLOW}main")));
+        assert!(format_jj(&info(true, true), &config).contains(&format!("{RED}main")));
+        let refs_only = Config {
+            refs_only: true,
+            ..config
+        };
+        let name = format!("{}main", refs_only.jj_palette.name);
+        assert!(format_jj(&info(false, false), &refs_only).contains(&name));
     }
 
-    #[cfg(feature = "git")]
     #[test]
-    fn test_git_format_with_symbol() {
-        let info = GitInfo {
-            branch: Some("main".into()),
-            head_short: "a3b4c5d".into(),
-            staged: 0,
+    fn test_jj_format_counts() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            divergent: true,
+            divergent_commits: 3,
+            ..Default::default()
+        };
+        let config = Config {
+            counts: true,
+            ..no_symbol_config()
+        };
+        assert!(format_jj(&info, &config).ends_with(&format!("{RED}[⇔3]{RESET}")));
+
+        let sparse = JjInfo {
+            sparse_patterns: 2,
+            ..info
+        };
+        assert!(format_jj(&sparse, &config).ends_with(&format!("{RED}[⇔3⊂2]{RESET}")));
+        assert!(format_jj(&sparse, &no_symbol_config()).ends_with(&format!("{RED}[⇔⊂]{RESET}")));
+    }
+
+    #[test]
+    fn test_commit_type() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            commit_type: Some("fix".into()),
+            ..Default::default()
+        };
+        let config = Config {
+            segments: vec![Segment::Type, Segment::Name],
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..default_config()
+        };
+        assert_eq!(format_jj(&info, &config), "🐛 yzxv1234");
+
+        let mut config = config;
+        config.commit_types = vec![("fix".into(), String::new())];
+        assert_eq!(format_jj(&info, &config), "yzxv1234");
+    }
+
+    #[test]
+    fn test_wip() {
+        let config = no_symbol_config();
+        assert!(config.is_wip("WIP: try the new parser"));
+        assert!(config.is_wip("  [draft] notes"));
+        assert!(!config.is_wip("fix wip: handling"));
+
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            wip: config.is_wip("private: local hacks"),
+            ..Default::default()
+        };
+        assert!(format_jj(&info, &config).ends_with(&format!("{RED}[✎]{RESET}")));
+    }
+
+    #[test]
+    fn test_op_age() {
+        assert_eq!(format_age(42), "42s");
+        assert_eq!(format_age(5 * 60 + 10), "5m");
+        assert_eq!(format_age(3 * 3600), "3h");
+        assert_eq!(format_age(2 * 86400 + 5), "2d");
+
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            op_time: 1,
+            ..Default::default()
+        };
+        let config = Config {
+            segments: vec![Segment::Name, Segment::OpAge],
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..default_config()
+        };
+        let rendered = format_jj(&info, &config);
+        assert!(rendered.starts_with("yzxv1234 op ") && rendered.ends_with('d'));
+
+        let unknown = JjInfo { op_time: 0, ..info };
+        assert_eq!(format_jj(&unknown, &config), "yzxv1234");
+    }
+
+    #[test]
+    fn test_last_action() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            last_action: Some("rebase".into()),
+            action_time: 1,
+            ..Default::default()
+        };
+        let config = Config {
+            segments: vec![Segment::Name, Segment::Action],
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            git_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..default_config()
+        };
+        let rendered = format_jj(&info, &config);
+        assert!(rendered.starts_with("yzxv1234 rebase ") && rendered.ends_with('d'));
+        let none = JjInfo {
+            last_action: None,
+            ..info
+        };
+        assert_eq!(format_jj(&none, &config), "yzxv1234");
+        #[cfg(feature = "git")]
+        {
+            let info = GitInfo {
+                branch: Some("main".into()),
+                head_short: "a3b4c5d".into(),
+                last_action: Some("checkout".into()),
+                action_time: 1,
+                ..Default::default()
+            };
+            let rendered = format_git(&info, &config);
+            assert!(rendered.starts_with("main checkout ") && rendered.ends_with('d'));
+        }
+    }
+
+    #[test]
+    fn test_text_segments() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            description: Some("fix: the parser".into()),
+            workspace: Some("review".into()),
+            op_id: Some("3b9f0e1a".into()),
+            ..Default::default()
+        };
+        let config = Config {
+            segments: vec![
+                Segment::Name,
+                Segment::Workspace,
+                Segment::OpId,
+                Segment::Description,
+            ],
+            truncate_name: 8,
+            jj_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            git_display: DisplayConfig {
+                show_color: false,
+                ..DisplayConfig::all_visible()
+            },
+            ..default_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            "yzxv1234 review op:3b9f0e1a fix: th…"
+        );
+        let bare = JjInfo {
+            change_id: "yzxv1234".into(),
+            ..Default::default()
+        };
+        assert_eq!(format_jj(&bare, &config), "yzxv1234");
+        #[cfg(feature = "git")]
+        {
+            let info = GitInfo {
+                branch: Some("main".into()),
+                head_short: "a3b4c5d".into(),
+                description: Some("wip".into()),
+                workspace: Some("hotfix".into()),
+                ..Default::default()
+            };
+            assert_eq!(format_git(&info, &config), "main hotfix wip");
+        }
+    }
+
+    #[test]
+    fn test_hide() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            conflict: true,
+            remote: Some("origin".into()),
+            ahead: 2,
+            ..Default::default()
+        };
+        let config = Config {
+            hide: vec![Indicator::Ahead],
+            ..no_symbol_config()
+        };
+        assert!(format_jj(&info, &config).ends_with(&format!("{RED}[!]{RESET}")));
+        #[cfg(feature = "git")]
+        {
+            let info = GitInfo {
+                head_short: "1234567".into(),
+                untracked: 4,
+                ..Default::default()
+            };
+            let config = Config {
+                hide: vec![Indicator::Untracked],
+                ..no_symbol_config()
+            };
+            // Nothing left to show: no empty brackets
+            assert_eq!(
+                format_git(&info, &config),
+                format!("on {BLUE}{RESET}{PURPLE}HEAD{RESET} {GREEN}(1234567){RESET}")
+            );
+        }
+    }
+
+    #[test]
+    fn test_stale_marker() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            empty_desc: true,
+            stale: true,
+            ..Default::default()
+        };
+        let config = Config {
+            suffix: " ".into(),
+            ..no_symbol_config()
+        };
+        assert!(format_jj(&info, &config).ends_with(&format!("{RED}[?]{RESET}~ ")));
+        let config = Config {
+            stale_marker: String::new(),
+            ..config
+        };
+        assert!(format_jj(&info, &config).ends_with(&format!("{RED}[?]{RESET} ")));
+    }
+
+    #[test]
+    fn test_conflict_below() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            conflict_below: true,
+            ..Default::default()
+        };
+        assert!(format_jj(&info, &no_symbol_config()).ends_with(&format!("{RED}[↯]{RESET}")));
+
+        let conflicted = JjInfo {
+            conflict: true,
+            ..info
+        };
+        assert!(
+            format_jj(&conflicted, &no_symbol_config()).ends_with(&format!("{RED}[!↯]{RESET}"))
+        );
+    }
+
+    #[test]
+    fn test_foreign_author() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            empty_desc: true,
+            foreign_author: true,
+            ..Default::default()
+        };
+        assert!(format_jj(&info, &no_symbol_config()).ends_with(&format!("{RED}[?≠]{RESET}")));
+        #[cfg(feature = "git")]
+        {
+            let info = GitInfo {
+                head_short: "1234567".into(),
+                modified: 1,
+                ahead: 1,
+                foreign_author: true,
+                ..Default::default()
+            };
+            assert!(
+                format_git(&info, &no_symbol_config()).ends_with(&format!("{RED}[!≠⇡1]{RESET}"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_branch() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            wip: true,
+            on_default: true,
+            ..Default::default()
+        };
+        assert!(format_jj(&info, &no_symbol_config()).ends_with(&format!("{RED}[✎⚑]{RESET}")));
+        #[cfg(feature = "git")]
+        {
+            let info = GitInfo {
+                branch: Some("main".into()),
+                head_short: "1234567".into(),
+                behind: 2,
+                on_default: true,
+                ..Default::default()
+            };
+            assert!(
+                format_git(&info, &no_symbol_config()).ends_with(&format!("{RED}[⚑⇣2]{RESET}"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_segments() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict: true,
+            ..Default::default()
+        };
+        let config = Config {
+            segments: vec![Segment::Status, Segment::Prefix, Segment::Name],
+            ..default_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            format!("{RED}[!]{RESET} on {BLUE}{DEFAULT_JJ_SYMBOL}{RESET}{PURPLE}main{RESET}")
+        );
+    }
+
+    #[test]
+    fn test_max_width() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict: true,
+            ..Default::default()
+        };
+        let format = |max_width| {
+            let config = Config {
+                max_width,
+                ..no_symbol_config()
+            };
+            format_jj(&info, &config)
+        };
+        assert_eq!(visible_width(&format(0)), "on main (yzxv1234) [!]".len());
+        assert_eq!(
+            format(20),
+            format!("on {BLUE}{RESET}{PURPLE}main{RESET} {RED}[!]{RESET}")
+        );
+        assert_eq!(format(8), format!("{PURPLE}main{RESET} {RED}[!]{RESET}"));
+        assert_eq!(format(4), format!("{PURPLE}main{RESET}"));
+    }
+
+    #[test]
+    fn test_visible_width() {
+        assert_eq!(visible_width(&format!("on {PURPLE}main{RESET}")), 7);
+        assert_eq!(visible_width("✨ feat 日本"), 12);
+        assert_eq!(visible_width("e\u{301}\u{fe0f}"), 1);
+        assert_eq!(visible_width(&format!("main\n{RED}[!]{RESET} [+2 ~1]")), 11);
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn test_transient() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict: true,
+            empty_desc: true,
+            ..Default::default()
+        };
+        let config = Config {
+            state_colors: true,
+            ..default_config()
+        }
+        .transient();
+        assert_eq!(
+            format_jj(&info, &config),
+            format!("{BLUE}{DEFAULT_JJ_SYMBOL}{RESET}{PURPLE}main{RESET}")
+        );
+    }
+
+    #[test]
+    fn test_block() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict: true,
+            ..Default::default()
+        };
+        let config = Config {
+            block: true,
+            suffix: " ".into(),
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(yzxv1234){RESET}\n{RED}[!]{RESET} "
+            )
+        );
+
+        // Clean repos stay on one line
+        let info = JjInfo {
+            conflict: false,
+            ..info
+        };
+        assert!(!format_jj(&info, &config).contains('\n'));
+    }
+
+    #[test]
+    fn test_prefix_text_and_suffix() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            ..Default::default()
+        };
+        let config = Config {
+            prefix_text: "in ".into(),
+            suffix: " ".into(),
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            format!("in {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(yzxv1234){RESET} ")
+        );
+    }
+
+    #[test]
+    fn test_plain_words() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            conflict: true,
+            empty_desc: true,
+            remote: Some("origin".into()),
+            ahead: 2,
+            ..Default::default()
+        };
+        let config = Config {
+            plain_words: true,
+            ..no_symbol_config()
+        };
+        assert!(
+            format_jj(&info, &config)
+                .ends_with(&format!("{RED}[conflicted, undescribed, 2 ahead]{RESET}"))
+        );
+    }
+
+    #[test]
+    fn test_indicator_colors() {
+        let mut config = no_symbol_config();
+        config.jj_palette.indicators = vec![(Indicator::Ahead, Cow::Borrowed(BLUE))];
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict: true,
+            empty_desc: true,
+            remote: Some("origin".into()),
+            ahead: 2,
+            ..Default::default()
+        };
+        assert!(
+            format_jj(&info, &config)
+                .ends_with(&format!("{RED}[!?{RESET}{BLUE}⇡2{RESET}{RED}]{RESET}"))
+        );
+    }
+
+    #[test]
+    fn test_jj_format_tracking_remote() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("feature".into()),
+            empty_desc: false,
+            conflict: false,
+            divergent: false,
+            has_remote: true,
+            is_synced: false,
+            remote: Some("origin".into()),
+            ahead: 2,
+            behind: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_jj(&info, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}feature{RESET}{PURPLE}→origin{RESET} {GREEN}(yzxv1234){RESET} {RED}[⇡2⇣1]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_clean() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 0,
+            deleted: 0,
+            conflicted: 0,
+            ahead: 0,
+            behind: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!("on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(a3b4c5d){RESET}")
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_dirty() {
+        let info = GitInfo {
+            branch: Some("feature".into()),
+            head_short: "1234567".into(),
+            staged: 2,
+            modified: 3,
+            untracked: 1,
+            deleted: 0,
+            conflicted: 0,
+            ahead: 2,
+            behind: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}feature{RESET} {GREEN}(1234567){RESET} {RED}[+!?⇡2⇣1]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_with_symbol() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
             modified: 0,
             untracked: 0,
             deleted: 0,
             conflicted: 0,
             ahead: 0,
             behind: 0,
+            ..Default::default()
         };
         assert_eq!(
             format_git(&info, &default_config()),
@@ -350,4 +1730,433 @@ mod tests {
             )
         );
     }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_merged_format() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict: true,
+            ..Default::default()
+        };
+        let git = GitInfo {
+            staged: 2,
+            untracked: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_merged(&info, &git, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(yzxv1234){RESET} {RED}[!+?]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_untracked_capped() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 0,
+            modified: 0,
+            untracked: 100,
+            deleted: 0,
+            conflicted: 0,
+            ahead: 0,
+            behind: 0,
+            ..Default::default()
+        };
+        let config = Config {
+            max_untracked: 99,
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_git(&info, &config),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(a3b4c5d){RESET} {RED}[?99+]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_thresholds() {
+        let mut config = no_symbol_config();
+        config.git_thresholds = Thresholds(vec![(Indicator::Untracked, 5), (Indicator::Behind, 3)]);
+        let info = |untracked: usize, behind: usize| GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            modified: 1,
+            untracked,
+            behind,
+            ..Default::default()
+        };
+        assert!(format_git(&info(4, 2), &config).ends_with(&format!("{RED}[!]{RESET}")));
+        assert!(format_git(&info(5, 3), &config).ends_with(&format!("{RED}[!?⇣3]{RESET}")));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_counts() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 2,
+            modified: 5,
+            untracked: 1,
+            renamed: 1,
+            ahead: 1,
+            ..Default::default()
+        };
+        let config = Config {
+            counts: true,
+            ..no_symbol_config()
+        };
+        assert!(format_git(&info, &config).ends_with(&format!("{RED}[+2»1!5?1⇡1]{RESET}")));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_dirty_count() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            staged: 2,
+            modified: 1,
+            untracked: 3,
+            ahead: 1,
+            ..Default::default()
+        };
+        let config = Config {
+            git_dirty_count: true,
+            ..no_symbol_config()
+        };
+        assert!(format_git(&info, &config).ends_with(&format!("{RED}[±6⇡1]{RESET}")));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_simple_dirty() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            dirty: true,
+            ahead: 1,
+            ..Default::default()
+        };
+        assert!(format_git(&info, &no_symbol_config()).ends_with(&format!("{RED}[*⇡1]{RESET}")));
+        assert!(info.is_dirty(false));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_submodule() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            superproject: Some("app".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!("on {BLUE}{RESET}{PURPLE}main{RESET}{BLUE}⊂app{RESET} {GREEN}(a3b4c5d){RESET}")
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_diverged() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            modified: 1,
+            ahead: 2,
+            behind: 1,
+            ..Default::default()
+        };
+        let config = Config {
+            git_diverged: true,
+            ..no_symbol_config()
+        };
+        assert!(format_git(&info, &config).ends_with(&format!("{RED}[!⇕2/1]{RESET}")));
+        let words = Config {
+            plain_words: true,
+            ..config
+        };
+        assert!(format_git(&info, &words).ends_with(&format!(
+            "{RED}[modified, diverged, 2 ahead, 1 behind]{RESET}"
+        )));
+        // Only ahead: the plain arrow
+        let info = GitInfo { behind: 0, ..info };
+        assert!(format_git(&info, &config).ends_with(&format!("{RED}[!⇡2]{RESET}")));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_compare_remotes() {
+        let counts = |remote: &str, ahead, behind| RemoteCounts {
+            remote: remote.into(),
+            ahead,
+            behind,
+        };
+        let info = GitInfo {
+            branch: Some("feature".into()),
+            head_short: "a3b4c5d".into(),
+            ahead: 2,
+            remotes: vec![
+                counts("origin", 2, 0),
+                counts("fork", 0, 0),
+                counts("upstream", 3, 14),
+            ],
+            ..Default::default()
+        };
+        let config = no_symbol_config();
+        assert!(
+            format_git(&info, &config).ends_with(&format!("{RED}[origin⇡2 upstream⇡3⇣14]{RESET}"))
+        );
+        let words = Config {
+            plain_words: true,
+            ..no_symbol_config()
+        };
+        assert!(format_git(&info, &words).ends_with(&format!(
+            "{RED}[2 ahead of origin, 3 ahead of upstream, 14 behind upstream]{RESET}"
+        )));
+        let diverged = Config {
+            git_diverged: true,
+            ..no_symbol_config()
+        };
+        assert!(
+            format_git(&info, &diverged)
+                .ends_with(&format!("{RED}[origin⇡2 upstream⇕3/14]{RESET}"))
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_lfs_locks() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            modified: 3,
+            lfs_locked: 2,
+            lfs_unlocked: 1,
+            ..Default::default()
+        };
+        assert!(format_git(&info, &no_symbol_config()).ends_with(&format!("{RED}[!⊠⊘]{RESET}")));
+        let config = Config {
+            counts: true,
+            ..no_symbol_config()
+        };
+        assert!(format_git(&info, &config).ends_with(&format!("{RED}[!3⊠2⊘1]{RESET}")));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_base() {
+        let info = GitInfo {
+            branch: Some("feature".into()),
+            head_short: "a3b4c5d".into(),
+            base: Some("main".into()),
+            base_ahead: 3,
+            ..Default::default()
+        };
+        let config = Config {
+            segments: vec![Segment::Name, Segment::Base],
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_git(&info, &config),
+            format!("{PURPLE}feature{RESET} {GREEN}main+3{RESET}")
+        );
+        let on_base = GitInfo {
+            base_ahead: 0,
+            ..info
+        };
+        assert_eq!(
+            format_git(&on_base, &config),
+            format!("{PURPLE}feature{RESET}")
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_status_skipped() {
+        let info = GitInfo {
+            branch: Some("main".into()),
+            head_short: "a3b4c5d".into(),
+            ahead: 1,
+            status_skipped: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(a3b4c5d){RESET} {RED}[≋⇡1]{RESET}"
+            )
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_unreadable() {
+        let info = GitInfo {
+            head_short: "a3b4c5d".into(),
+            modified: 1,
+            unreadable: 2,
+            ..Default::default()
+        };
+        assert!(format_git(&info, &no_symbol_config()).ends_with(&format!("{RED}[⚠!]{RESET}")));
+    }
+
+    #[test]
+    fn test_jj_format_template() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            conflict: true,
+            ..JjInfo::default()
+        };
+        let config = Config {
+            jj_format: "$symbol$bookmark@$change_id [$status]?".into(),
+            ..no_symbol_config()
+        };
+        assert_eq!(
+            format_jj(&info, &config),
+            format!("{BLUE}{RESET}{PURPLE}main{RESET}@{GREEN}yzxv1234{RESET} [{RED}!{RESET}]")
+        );
+        let clean = JjInfo {
+            conflict: false,
+            ..info
+        };
+        assert_eq!(
+            format_jj(&clean, &config),
+            format!("{BLUE}{RESET}{PURPLE}main{RESET}@{GREEN}yzxv1234{RESET}")
+        );
+        let custom = Config {
+            jj_format: "$bookmark [${custom.version}]?[${custom.missing}]?".into(),
+            custom_values: vec![("version".into(), "v1.2".into())],
+            ..config
+        };
+        assert_eq!(
+            format_jj(&clean, &custom),
+            format!("{PURPLE}main{RESET} [v1.2]")
+        );
+    }
+
+    #[test]
+    fn test_escape_starship() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("fix[2]$x\\y".into()),
+            ..JjInfo::default()
+        };
+        assert_eq!(
+            escape_starship(&format_jj(&info, &no_symbol_config())),
+            format!("on {BLUE}{RESET}{PURPLE}fix\\[2\\]\\$x\\\\y{RESET} {GREEN}(yzxv1234){RESET}")
+        );
+    }
+
+    #[test]
+    fn test_trim_trailing_space() {
+        assert_eq!(trim_trailing_space("on main "), "on main");
+        assert_eq!(
+            trim_trailing_space(&format!("{PURPLE}main {RESET} \n")),
+            format!("{PURPLE}main{RESET}")
+        );
+        assert_eq!(trim_trailing_space("  "), "");
+        assert_eq!(trim_trailing_space("on main"), "on main");
+    }
+
+    #[test]
+    fn test_repo_alias() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            ..JjInfo::default()
+        };
+        let alias = RepoAlias {
+            pattern: "~/src/mono*".into(),
+            alias: Some("mono".into()),
+            symbol: Some("M ".into()),
+        };
+        assert_eq!(
+            format_jj(&info, &no_symbol_config().aliased(&alias)),
+            format!("on {BLUE}M mono {RESET}{PURPLE}main{RESET} {GREEN}(yzxv1234){RESET}")
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        let info = JjInfo {
+            change_id: "yzxv1234".into(),
+            bookmark: Some("main".into()),
+            nested: true,
+            ..JjInfo::default()
+        };
+        assert_eq!(
+            format_jj(&info, &no_symbol_config()),
+            format!("on {BLUE}▣{RESET}{BLUE}{RESET}{PURPLE}main{RESET} {GREEN}(yzxv1234){RESET}")
+        );
+    }
+
+    #[test]
+    fn test_format_internals() {
+        assert_eq!(
+            format_internals(".git", &no_symbol_config()),
+            format!("on {BLUE}{RESET}{RED}(inside .git){RESET}")
+        );
+        assert_eq!(
+            format_internals(".jj", &no_symbol_config()),
+            format!("on {BLUE}{RESET}{RED}(inside .jj){RESET}")
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_untrusted() {
+        let info = GitInfo {
+            untrusted: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!("on {BLUE}{RESET}{RED}(untrusted){RESET}")
+        );
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_format_operation() {
+        let info = GitInfo {
+            head_short: "a3b4c5d".into(),
+            operation: Some("REBASING".into()),
+            step: 3,
+            total: 7,
+            ..Default::default()
+        };
+        assert_eq!(
+            format_git(&info, &no_symbol_config()),
+            format!(
+                "on {BLUE}{RESET}{PURPLE}HEAD{RESET} {GREEN}(a3b4c5d){RESET} {YELLOW}REBASING 3/7{RESET}"
+            )
+        );
+
+        let picking = GitInfo {
+            operation: Some("CHERRY-PICKING".into()),
+            step: 0,
+            total: 2,
+            ..info
+        };
+        assert!(format_git(&picking, &no_symbol_config()).contains("CHERRY-PICKING 2 left"));
+
+        let bisecting = GitInfo {
+            operation: Some("BISECTING".into()),
+            total: 0,
+            bisect_steps: Some(4),
+            ..picking
+        };
+        assert!(format_git(&bisecting, &no_symbol_config()).contains("BISECTING ~4 steps"));
+    }
 }
@@ -0,0 +1,185 @@
+//! `--output porcelain`: versioned `key<TAB>value` lines for scripts
+//! The prompt may change between releases; this format doesn't within a
+//! version. Keys are only ever added, so parsers must skip keys they don't know
+
+use crate::detect::RepoType;
+#[cfg(feature = "git")]
+use crate::git::GitInfo;
+use crate::jj::JjInfo;
+use crate::json::Value;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Bumped only for changes that could break a parser of the previous version
+pub const VERSION: usize = 1;
+
+/// A porcelain key and its value
+pub type Field = (&'static str, Value);
+
+/// JJ fields, listed here rather than taken from the info document so that
+/// renaming an internal or cache field can't change the format
+pub fn jj_fields(info: &JjInfo) -> Vec<Field> {
+    vec![
+        ("change_id", info.change_id.as_str().into()),
+        ("bookmark", info.bookmark.as_deref().into()),
+        ("empty_desc", info.empty_desc.into()),
+        ("conflict", info.conflict.into()),
+        ("conflict_below", info.conflict_below.into()),
+        ("divergent", info.divergent.into()),
+        ("divergent_commits", info.divergent_commits.into()),
+        ("divergent_ids", info.divergent_ids.clone().into()),
+        ("sparse_patterns", info.sparse_patterns.into()),
+        ("large_files", info.large_files.into()),
+        ("wip", info.wip.into()),
+        ("commit_type", info.commit_type.as_deref().into()),
+        ("foreign_author", info.foreign_author.into()),
+        ("op_time", info.op_time.into()),
+        ("last_action", info.last_action.as_deref().into()),
+        ("action_time", info.action_time.into()),
+        ("on_default", info.on_default.into()),
+        ("stale", info.stale.into()),
+        ("nested", info.nested.into()),
+        ("has_remote", info.has_remote.into()),
+        ("is_synced", info.is_synced.into()),
+        ("remote", info.remote.as_deref().into()),
+        ("ahead", info.ahead.into()),
+        ("behind", info.behind.into()),
+        ("status_skipped", info.status_skipped.into()),
+        ("has_changes", info.has_changes.into()),
+    ]
+}
+
+/// Git fields, listed like `jj_fields`; each compared remote is one
+/// `remotes` line of `{remote} {ahead} {behind}`
+#[cfg(feature = "git")]
+pub fn git_fields(info: &GitInfo) -> Vec<Field> {
+    let remotes: Vec<String> = info
+        .remotes
+        .iter()
+        .map(|r| format!("{} {} {}", r.remote, r.ahead, r.behind))
+        .collect();
+    vec![
+        ("branch", info.branch.as_deref().into()),
+        ("head_short", info.head_short.as_str().into()),
+        ("staged", info.staged.into()),
+        ("modified", info.modified.into()),
+        ("untracked", info.untracked.into()),
+        ("deleted", info.deleted.into()),
+        ("conflicted", info.conflicted.into()),
+        ("renamed", info.renamed.into()),
+        ("typechanged", info.typechanged.into()),
+        ("dirty", info.dirty.into()),
+        ("ahead", info.ahead.into()),
+        ("behind", info.behind.into()),
+        ("superproject", info.superproject.as_deref().into()),
+        ("nested", info.nested.into()),
+        ("remotes", remotes.into()),
+        ("base", info.base.as_deref().into()),
+        ("base_ahead", info.base_ahead.into()),
+        ("last_action", info.last_action.as_deref().into()),
+        ("action_time", info.action_time.into()),
+        ("on_default", info.on_default.into()),
+        ("status_skipped", info.status_skipped.into()),
+        ("wip", info.wip.into()),
+        ("commit_type", info.commit_type.as_deref().into()),
+        ("operation", info.operation.as_deref().into()),
+        ("step", info.step.into()),
+        ("total", info.total.into()),
+        ("bisect_steps", info.bisect_steps.into()),
+        ("foreign_author", info.foreign_author.into()),
+        ("untrusted", info.untrusted.into()),
+        ("unreadable", info.unreadable.into()),
+        ("lfs_locked", info.lfs_locked.into()),
+        ("lfs_unlocked", info.lfs_unlocked.into()),
+    ]
+}
+
+/// `version`, `repo_type` and `repo_root`, then the fields in order: bools as
+/// `true`/`false`, nulls as empty values and lists as one line per element
+pub fn format(repo_type: RepoType, repo_root: Option<&Path>, fields: Option<&[Field]>) -> String {
+    let mut out = format!("version\t{VERSION}\nrepo_type\t{}\n", repo_type.as_str());
+    if let Some(root) = repo_root {
+        line(&mut out, "repo_root", &root.to_string_lossy());
+    }
+    for (key, value) in fields.unwrap_or_default() {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    line(&mut out, key, &scalar(item));
+                }
+            }
+            value => line(&mut out, key, &scalar(value)),
+        }
+    }
+    out
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// One line; backslashes, tabs and line breaks in the value are escaped so
+/// splitting on the first tab and on newlines is always safe
+fn line(out: &mut String, key: &str, value: &str) {
+    let _ = write!(out, "{key}\t");
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let info = JjInfo {
+            change_id: "kxqpmtzv".into(),
+            conflict: true,
+            ahead: 2,
+            divergent_ids: vec!["3f2a".into(), "9c0".into()],
+            last_action: Some("a\tb\nc".into()),
+            ..JjInfo::default()
+        };
+        let out = format(
+            RepoType::Jj,
+            Some(Path::new("/repo")),
+            Some(&jj_fields(&info)),
+        );
+        assert!(out.starts_with(
+            "version\t1\nrepo_type\tjj\nrepo_root\t/repo\nchange_id\tkxqpmtzv\nbookmark\t\n"
+        ));
+        assert!(out.contains("\nconflict\ttrue\n"));
+        assert!(out.contains("\nahead\t2\n"));
+        assert!(out.contains("\nlast_action\ta\\tb\\nc\n"));
+        assert!(out.contains("\ndivergent_ids\t3f2a\ndivergent_ids\t9c0\n"));
+        assert_eq!(
+            format(RepoType::None, None, None),
+            "version\t1\nrepo_type\tnone\n"
+        );
+    }
+
+    #[test]
+    fn test_fields_cover_info() {
+        // A field added to the info document needs a porcelain key too
+        let info = JjInfo::default();
+        let Value::Object(document) = info.to_json() else {
+            unreachable!()
+        };
+        let keys: Vec<&str> = jj_fields(&info).iter().map(|(key, _)| *key).collect();
+        for (key, _) in &document {
+            assert!(keys.contains(&key.as_str()), "{key}");
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! JSON Schemas printed by `jj-starship schema`
+//! Both are derived from the code (info documents from `to_json`, the config
+//! file from `KEYS`), so they can't drift from what is emitted or accepted
+
+use crate::config::{Detail, Indicator, KEYS, Kind, Segment};
+#[cfg(feature = "git")]
+use crate::git::{GitInfo, RemoteCounts};
+use crate::jj::JjInfo;
+use crate::json::Value;
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+const STYLE_HELP: &str = "Colors (`red`, `bright red`, `ansi-color-N`, `#rrggbb`) and `bold`, `dim`, `italic`, `underline`, `on <bg>`";
+
+/// The `info` document (also the `render --stdin-json` input): JJ info or Git info
+pub fn info() -> Value {
+    let jj = JjInfo::default().to_json();
+    #[allow(unused_mut)]
+    let mut variants = vec![document("JJ info", &jj, &jj, "change_id")];
+    #[cfg(feature = "git")]
+    {
+        // Optional counts and list elements are set so their type shows;
+        // other optionals are strings
+        let typed = GitInfo {
+            bisect_steps: Some(0),
+            remotes: vec![RemoteCounts {
+                remote: String::new(),
+                ahead: 0,
+                behind: 0,
+            }],
+            ..GitInfo::default()
+        };
+        variants.push(document(
+            "Git info",
+            &GitInfo::default().to_json(),
+            &typed.to_json(),
+            "head_short",
+        ));
+    }
+    object(vec![
+        ("$schema", DRAFT.into()),
+        ("title", "jj-starship info".into()),
+        ("anyOf", Value::Array(variants)),
+    ])
+}
+
+/// One info document: property types come from `typed`, and fields that are
+/// null in `default` may be null
+fn document(title: &str, default: &Value, typed: &Value, required: &str) -> Value {
+    let Value::Object(fields) = typed else {
+        return Value::Null;
+    };
+    let properties = fields
+        .iter()
+        .map(|(key, value)| {
+            let nullable = default.get(key) == Some(&Value::Null);
+            let schema = schema_of(value);
+            (key.clone(), if nullable { or_null(schema) } else { schema })
+        })
+        .collect();
+    object(vec![
+        ("title", title.into()),
+        ("type", "object".into()),
+        ("required", vec![required].into()),
+        ("properties", Value::Object(properties)),
+    ])
+}
+
+/// Schema for a sample value: an array's items follow its first element (strings
+/// when empty), an object's properties its fields
+fn schema_of(value: &Value) -> Value {
+    match value {
+        Value::Bool(_) => type_of("boolean"),
+        Value::Number(_) => count(),
+        Value::Array(items) => array(items.first().map_or_else(|| type_of("string"), schema_of)),
+        Value::Object(fields) => object(vec![
+            ("type", "object".into()),
+            (
+                "properties",
+                Value::Object(
+                    fields
+                        .iter()
+                        .map(|(key, value)| (key.clone(), schema_of(value)))
+                        .collect(),
+                ),
+            ),
+        ]),
+        Value::String(_) | Value::Null => type_of("string"),
+    }
+}
+
+/// The config file, including `[presets.<name>]` tables of the same keys
+pub fn config() -> Value {
+    let keys = table(KEYS.iter().map(|&(key, kind)| (key, kind)).collect());
+    let Value::Object(mut fields) = keys.clone() else {
+        return Value::Null;
+    };
+    if let Some((_, Value::Object(properties))) = fields.iter_mut().find(|(k, _)| k == "properties")
+    {
+        properties.push((
+            "presets".into(),
+            object(vec![
+                ("type", "object".into()),
+                ("additionalProperties", keys),
+            ]),
+        ));
+    }
+    fields.insert(0, ("$schema".into(), DRAFT.into()));
+    fields.insert(1, ("title".into(), "jj-starship config".into()));
+    Value::Object(fields)
+}
+
+/// An object schema for dotted keys, nesting `a.b` under a table `a`
+fn table(keys: Vec<(&str, Kind)>) -> Value {
+    let mut properties: Vec<(String, Value)> = Vec::new();
+    let mut sections: Vec<(&str, Vec<(&str, Kind)>)> = Vec::new();
+    for (key, kind) in keys {
+        match key.split_once('.') {
+            Some((section, rest)) => match sections.iter_mut().find(|(s, _)| *s == section) {
+                Some((_, sub)) => sub.push((rest, kind)),
+                None => sections.push((section, vec![(rest, kind)])),
+            },
+            None => properties.push((key.into(), value(kind))),
+        }
+    }
+    properties.extend(
+        sections
+            .into_iter()
+            .map(|(section, sub)| (section.to_string(), table(sub))),
+    );
+    object(vec![
+        ("type", "object".into()),
+        ("properties", Value::Object(properties)),
+        ("additionalProperties", false.into()),
+    ])
+}
+
+fn value(kind: Kind) -> Value {
+    match kind {
+        Kind::Usize => count(),
+        Kind::Bool => type_of("boolean"),
+        Kind::String => type_of("string"),
+        Kind::Style => object(vec![
+            ("type", "string".into()),
+            ("description", STYLE_HELP.into()),
+        ]),
+        Kind::Paths | Kind::Strings => array(type_of("string")),
+        Kind::Segments => array(choice(&Segment::ALL.map(Segment::name))),
+        Kind::Details => array(choice(&Detail::ALL.map(Detail::name))),
+        Kind::Indicators => array(choice(&Indicator::ALL.map(Indicator::name))),
+        Kind::Choice(options) => choice(options),
+        Kind::Rules => object(vec![
+            ("type", "object".into()),
+            (
+                "additionalProperties",
+                object(vec![(
+                    "oneOf",
+                    Value::Array(vec![
+                        type_of("string"),
+                        object(vec![
+                            ("type", "object".into()),
+                            (
+                                "properties",
+                                object(vec![
+                                    ("style", type_of("string")),
+                                    ("symbol", type_of("string")),
+                                ]),
+                            ),
+                            ("additionalProperties", false.into()),
+                        ]),
+                    ]),
+                )]),
+            ),
+        ]),
+        Kind::Aliases => object(vec![
+            ("type", "object".into()),
+            (
+                "additionalProperties",
+                object(vec![(
+                    "oneOf",
+                    Value::Array(vec![
+                        type_of("string"),
+                        object(vec![
+                            ("type", "object".into()),
+                            (
+                                "properties",
+                                object(vec![
+                                    ("alias", type_of("string")),
+                                    ("symbol", type_of("string")),
+                                ]),
+                            ),
+                            ("additionalProperties", false.into()),
+                        ]),
+                    ]),
+                )]),
+            ),
+        ]),
+        Kind::Table => object(vec![
+            ("type", "object".into()),
+            ("additionalProperties", type_of("string")),
+        ]),
+    }
+}
+
+fn object(fields: Vec<(&str, Value)>) -> Value {
+    Value::Object(fields.into_iter().map(|(k, v)| (k.into(), v)).collect())
+}
+
+fn type_of(name: &str) -> Value {
+    object(vec![("type", name.into())])
+}
+
+fn count() -> Value {
+    object(vec![("type", "integer".into()), ("minimum", 0usize.into())])
+}
+
+fn array(items: Value) -> Value {
+    object(vec![("type", "array".into()), ("items", items)])
+}
+
+fn choice(options: &[&str]) -> Value {
+    object(vec![(
+        "enum",
+        Value::Array(options.iter().map(|&o| o.into()).collect()),
+    )])
+}
+
+fn or_null(schema: Value) -> Value {
+    object(vec![("anyOf", Value::Array(vec![schema, type_of("null")]))])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn test_info_schema() {
+        let schema = json::parse(&info().to_string()).unwrap();
+        let Some(Value::Array(variants)) = schema.get("anyOf") else {
+            panic!("no variants");
+        };
+        let jj = variants[0].get("properties").unwrap();
+        assert_eq!(jj.get("conflict"), Some(&type_of("boolean")));
+        assert_eq!(jj.get("bookmark"), Some(&or_null(type_of("string"))));
+        assert_eq!(jj.get("divergent_ids"), Some(&array(type_of("string"))));
+        #[cfg(feature = "git")]
+        {
+            let git = variants[1].get("properties").unwrap();
+            assert_eq!(git.get("bisect_steps"), Some(&or_null(count())));
+            let remote = git
+                .get("remotes")
+                .and_then(|remotes| remotes.get("items"))
+                .and_then(|items| items.get("properties"))
+                .unwrap();
+            assert_eq!(remote.get("remote"), Some(&type_of("string")));
+            assert_eq!(remote.get("ahead"), Some(&count()));
+        }
+    }
+
+    #[test]
+    fn test_config_schema() {
+        let schema = config();
+        let properties = schema.get("properties").unwrap();
+        assert_eq!(properties.get("no_cache"), Some(&type_of("boolean")));
+        let styles = properties
+            .get("jj")
+            .and_then(|jj| jj.get("properties"))
+            .and_then(|jj| jj.get("status_styles"))
+            .and_then(|styles| styles.get("properties"))
+            .unwrap();
+        assert!(styles.get("conflict_below").is_some());
+        let preset = properties
+            .get("presets")
+            .and_then(|p| p.get("additionalProperties"))
+            .and_then(|p| p.get("properties"))
+            .unwrap();
+        assert!(preset.get("segments").is_some() && preset.get("presets").is_none());
+    }
+}
@@ -0,0 +1,181 @@
+//! `serve --gitstatus`: gitstatusd-compatible request loop over stdin/stdout
+//! Lets powerlevel10k's gitstatus integration run on jj-starship and pick up JJ repos
+
+use crate::config::{self, Config};
+use crate::detect::{self, RepoType};
+#[cfg(feature = "git")]
+use crate::git;
+use crate::jj;
+use crate::log;
+use crate::timing::Timings;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Terminates requests and responses
+const RECORD_SEP: u8 = 0x1e;
+/// Separates fields within a record
+const UNIT_SEP: &str = "\x1f";
+
+/// Response fields we can fill; the rest of gitstatusd's fields are sent empty/zero
+#[derive(Default)]
+struct Status {
+    workdir: String,
+    commit: String,
+    branch: String,
+    action: &'static str,
+    staged: usize,
+    unstaged: usize,
+    conflicted: usize,
+    untracked: usize,
+    ahead: usize,
+    behind: usize,
+    unstaged_deleted: usize,
+}
+
+/// Answer requests (`id<US>dir<US>dont_compute_dirty<RS>`) until stdin closes
+/// The process lives as long as the shell, so a changed config file is
+/// picked up through `reload` before the next request
+pub fn gitstatus(mut config: Config, reload: impl Fn() -> Config) -> io::Result<()> {
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut buf = Vec::new();
+    let mut stamp = config_stamp();
+
+    loop {
+        buf.clear();
+        if input.read_until(RECORD_SEP, &mut buf)? == 0 || buf.pop() != Some(RECORD_SEP) {
+            // EOF (a trailing partial request is dropped)
+            return Ok(());
+        }
+        let current = config_stamp();
+        if current != stamp {
+            log::debug!("config file changed, reloading");
+            stamp = current;
+            config = reload();
+        }
+        let response = respond(&String::from_utf8_lossy(&buf), &config);
+        output.write_all(response.as_bytes())?;
+        output.write_all(&[RECORD_SEP])?;
+        output.flush()?;
+    }
+}
+
+/// Modification time of the config file (None while there is none)
+fn config_stamp() -> Option<SystemTime> {
+    fs::metadata(config::file_path()?).ok()?.modified().ok()
+}
+
+fn respond(request: &str, config: &Config) -> String {
+    let mut fields = request.split(UNIT_SEP);
+    let id = fields.next().unwrap_or_default();
+    let dir = Path::new(fields.next().unwrap_or_default());
+    let dont_compute_dirty = fields.next() == Some("1");
+
+    // gitstatusd only accepts absolute paths (p10k's handshake sends an empty one)
+    let status = if dir.is_absolute() {
+        let config = Config {
+            id_length: usize::MAX,
+            refs_only: dont_compute_dirty,
+            ..config.clone()
+        };
+        collect(dir, &config)
+    } else {
+        None
+    };
+
+    match status {
+        Some(status) => format_response(id, &status),
+        None => format!("{id}{UNIT_SEP}0"),
+    }
+}
+
+#[allow(unreachable_patterns)]
+fn collect(dir: &Path, config: &Config) -> Option<Status> {
+    let result = detect::detect_enabled(dir, config, None);
+    let repo_root = result.repo_root?;
+    let workdir = repo_root.to_string_lossy().into_owned();
+
+    match result.repo_type {
+        RepoType::Jj | RepoType::JjColocated => {
+            let info = jj::collect(&repo_root, config, None, &Timings::default()).ok()?;
+            let action = if info.conflict {
+                "conflict"
+            } else if info.divergent {
+                "divergent"
+            } else {
+                ""
+            };
+            Some(Status {
+                workdir,
+                // The change ID is what JJ users address commits by
+                commit: info.change_id,
+                branch: info.bookmark.unwrap_or_default(),
+                action,
+                conflicted: usize::from(info.conflict),
+                ahead: info.ahead,
+                behind: info.behind,
+                ..Status::default()
+            })
+        }
+        #[cfg(feature = "git")]
+        RepoType::Git => {
+            let info = git::collect(&repo_root, config, &Timings::default()).ok()?;
+            Some(Status {
+                workdir,
+                commit: if info.head_short == "empty" {
+                    String::new()
+                } else {
+                    info.head_short
+                },
+                branch: info.branch.unwrap_or_default(),
+                action: "",
+                staged: info.staged + info.renamed,
+                unstaged: info.modified + info.deleted + info.typechanged,
+                conflicted: info.conflicted,
+                untracked: info.untracked,
+                ahead: info.ahead,
+                behind: info.behind,
+                unstaged_deleted: info.deleted,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Positional response fields, in gitstatusd's order
+fn format_response(id: &str, s: &Status) -> String {
+    let fields = [
+        id.to_string(),
+        "1".into(),
+        s.workdir.clone(),
+        s.commit.clone(),
+        s.branch.clone(),
+        String::new(), // upstream branch
+        String::new(), // remote name
+        String::new(), // remote URL
+        s.action.into(),
+        "0".into(), // files in index
+        s.staged.to_string(),
+        s.unstaged.to_string(),
+        s.conflicted.to_string(),
+        s.untracked.to_string(),
+        s.ahead.to_string(),
+        s.behind.to_string(),
+        "0".into(),    // stashes
+        String::new(), // tag
+        s.unstaged_deleted.to_string(),
+        "0".into(),    // staged new
+        "0".into(),    // staged deleted
+        String::new(), // push remote name
+        String::new(), // push remote URL
+        "0".into(),    // ahead of push remote
+        "0".into(),    // behind push remote
+        "0".into(),    // skip-worktree files
+        "0".into(),    // assume-unchanged files
+        String::new(), // commit message encoding (UTF-8)
+        String::new(), // commit message summary
+    ];
+    fields.join(UNIT_SEP)
+}
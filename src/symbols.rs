@@ -0,0 +1,82 @@
+//! Repo symbols per Nerd Font generation
+//! v3 moved the Material Design icons to `U+F0001..`, so glyphs from that
+//! range render blank with fonts patched by v2
+
+/// Nerd Font generation the terminal font was patched with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NerdFont {
+    #[default]
+    V3,
+    V2,
+    /// No Nerd Font: plain-text symbols
+    None,
+}
+
+/// Default repo symbols for a font generation
+#[derive(Debug, Clone, Copy)]
+pub struct Symbols {
+    pub jj: &'static str,
+    pub git: &'static str,
+}
+
+impl NerdFont {
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    pub const NAMES: &[&str] = &["v3", "v2", "none"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "v3" => Some(Self::V3),
+            "v2" => Some(Self::V2),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::V3 => "v3",
+            Self::V2 => "v2",
+            Self::None => "none",
+        }
+    }
+
+    pub const fn symbols(self) -> Symbols {
+        match self {
+            // nf-oct-git_branch + nf-md-source_branch_sync, nf-dev-git_branch
+            Self::V3 => Symbols {
+                jj: "\u{f418}\u{f15c6} ",
+                git: "\u{e725} ",
+            },
+            // The md glyph has no v2 codepoint; the octicon and devicon are unchanged
+            Self::V2 => Symbols {
+                jj: "\u{f418} ",
+                git: "\u{e725} ",
+            },
+            Self::None => Symbols {
+                jj: "jj ",
+                git: "git ",
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for name in NerdFont::NAMES {
+            assert!(NerdFont::parse(name).is_some());
+        }
+        assert_eq!(NerdFont::parse("v1"), None);
+    }
+
+    #[test]
+    fn test_v2_avoids_v3_only_range() {
+        let symbols = NerdFont::V2.symbols();
+        for c in symbols.jj.chars().chain(symbols.git.chars()) {
+            assert!(u32::from(c) < 0xf0000, "{c:?} is v3-only");
+        }
+    }
+}
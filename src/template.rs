@@ -0,0 +1,235 @@
+//! Format templates (`jj.format`, `git.format`): literal text with `$name` or
+//! `${name}` variables; `\` makes the next character literal
+//! `(a|b)` shows the first alternative that has a value and `[...]?` drops the
+//! bracketed text when it has none, taking a neighboring space with it
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Parsed template piece
+#[derive(Debug, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Var(String),
+    /// `(a|b)`: the first alternative with a value, or one without variables
+    Group(Vec<Vec<Node>>),
+    /// `[...]?`: the text with its brackets, or nothing without a value
+    Optional(Vec<Node>),
+}
+
+/// Expand `template`, looking each variable up with `var`; unknown variables
+/// expand to nothing
+pub fn expand(template: &str, var: &dyn Fn(&str) -> Option<String>) -> String {
+    render(&parse(template), var).0
+}
+
+/// Names of the variables `template` refers to, in order
+pub fn variables(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    collect_variables(&parse(template), &mut names);
+    names
+}
+
+fn collect_variables(nodes: &[Node], names: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Var(name) => names.push(name.clone()),
+            Node::Group(alternatives) => {
+                for alternative in alternatives {
+                    collect_variables(alternative, names);
+                }
+            }
+            Node::Optional(nodes) => collect_variables(nodes, names),
+        }
+    }
+}
+
+fn parse(template: &str) -> Vec<Node> {
+    parse_until(&mut template.chars().peekable(), &[]).0
+}
+
+/// Nodes up to the first character of `end` (consumed and returned, None at
+/// the end of the template)
+fn parse_until(chars: &mut Peekable<Chars>, end: &[char]) -> (Vec<Node>, Option<char>) {
+    let mut nodes = Vec::new();
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        if end.contains(&c) {
+            flush(&mut nodes, &mut text);
+            return (nodes, Some(c));
+        }
+        match c {
+            '\\' => text.extend(chars.next()),
+            '$' => match variable(chars) {
+                Some(name) => {
+                    flush(&mut nodes, &mut text);
+                    nodes.push(Node::Var(name));
+                }
+                None => text.push('$'),
+            },
+            '(' => {
+                flush(&mut nodes, &mut text);
+                let mut alternatives = Vec::new();
+                loop {
+                    let (alternative, hit) = parse_until(chars, &['|', ')']);
+                    alternatives.push(alternative);
+                    if hit != Some('|') {
+                        break;
+                    }
+                }
+                nodes.push(Node::Group(alternatives));
+            }
+            '[' => {
+                flush(&mut nodes, &mut text);
+                let (inner, hit) = parse_until(chars, &[']']);
+                let mut bracketed = vec![Node::Text("[".into())];
+                bracketed.extend(inner);
+                if hit.is_none() {
+                    nodes.extend(bracketed);
+                    continue;
+                }
+                bracketed.push(Node::Text("]".into()));
+                if chars.next_if_eq(&'?').is_some() {
+                    nodes.push(Node::Optional(bracketed));
+                } else {
+                    nodes.extend(bracketed);
+                }
+            }
+            c => text.push(c),
+        }
+    }
+    flush(&mut nodes, &mut text);
+    (nodes, None)
+}
+
+fn flush(nodes: &mut Vec<Node>, text: &mut String) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(std::mem::take(text)));
+    }
+}
+
+/// Name after a `$`: `{name}` or a run of name characters starting with a
+/// letter, so `$5` stays literal
+fn variable(chars: &mut Peekable<Chars>) -> Option<String> {
+    let name: String = if chars.next_if_eq(&'{').is_some() {
+        chars.by_ref().take_while(|&c| c != '}').collect()
+    } else if chars
+        .peek()
+        .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_')
+    {
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|&c| is_name_char(c)) {
+            name.push(c);
+        }
+        name
+    } else {
+        String::new()
+    };
+    (!name.is_empty()).then_some(name)
+}
+
+/// Characters of a bare `$name`; dotted names need braces (`${custom.foo}`)
+const fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// Rendered text, and whether its variables had a value: None without
+/// variables, `Some(false)` when all of them were empty
+fn render(nodes: &[Node], var: &dyn Fn(&str) -> Option<String>) -> (String, Option<bool>) {
+    let mut out = String::new();
+    let mut valued = None;
+    // Something just expanded to nothing, so the space after it is dropped
+    let mut collapsed = false;
+    for node in nodes {
+        let (text, node_valued) = render_node(node, var);
+        valued = match (valued, node_valued) {
+            (None, v) | (v, None) => v,
+            (Some(a), Some(b)) => Some(a || b),
+        };
+        if text.is_empty() {
+            collapsed |= node_valued == Some(false);
+            continue;
+        }
+        let text = if collapsed && (out.is_empty() || out.ends_with(' ')) {
+            text.trim_start_matches(' ')
+        } else {
+            text.as_str()
+        };
+        if !text.is_empty() {
+            collapsed = false;
+            out.push_str(text);
+        }
+    }
+    if collapsed {
+        out.truncate(out.trim_end_matches(' ').len());
+    }
+    (out, valued)
+}
+
+fn render_node(node: &Node, var: &dyn Fn(&str) -> Option<String>) -> (String, Option<bool>) {
+    match node {
+        Node::Text(text) => (text.clone(), None),
+        Node::Var(name) => {
+            let value = var(name).unwrap_or_default();
+            let valued = !value.is_empty();
+            (value, Some(valued))
+        }
+        Node::Group(alternatives) => alternatives
+            .iter()
+            .map(|alternative| render(alternative, var))
+            .find(|(_, valued)| *valued != Some(false))
+            .unwrap_or((String::new(), Some(false))),
+        Node::Optional(nodes) => match render(nodes, var) {
+            (_, Some(false)) => (String::new(), Some(false)),
+            rendered => rendered,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(name: &str) -> Option<String> {
+        match name {
+            "name" => Some("main".into()),
+            "id" => Some("(yzxv1234)".into()),
+            "change_id" => Some("yzxv1234".into()),
+            "custom.foo" => Some("foo".into()),
+            "status" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(expand("on $name $id", &vars), "on main (yzxv1234)");
+        assert_eq!(expand("${name}:${custom.foo}", &vars), "main:foo");
+        assert_eq!(expand("$name.$missing!", &vars), "main.!");
+        assert_eq!(
+            expand("\\$name costs $5 \\(1\\) $", &vars),
+            "$name costs $5 (1) $"
+        );
+    }
+
+    #[test]
+    fn test_groups() {
+        assert_eq!(expand("(${bookmark}|${change_id})", &vars), "yzxv1234");
+        assert_eq!(expand("(${name}|${change_id})", &vars), "main");
+        assert_eq!(expand("(${bookmark}|detached)", &vars), "detached");
+        assert_eq!(expand("on (ref $bookmark)", &vars), "on");
+        assert_eq!(expand("$name [${status}]? $id", &vars), "main (yzxv1234)");
+        assert_eq!(expand("$name [$name]?", &vars), "main [main]");
+        assert_eq!(expand("[$id] x", &vars), "[(yzxv1234)] x");
+        assert_eq!(expand("$name $status", &vars), "main");
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(
+            variables("on $name (${bookmark}|\\$id) [${custom.foo}]?"),
+            ["name", "bookmark", "custom.foo"]
+        );
+    }
+}
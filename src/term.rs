@@ -0,0 +1,164 @@
+//! Terminal capability detection
+//! Read once from the environment at startup; `colors` and `nerd_font`
+//! override what is detected. Unknown terminals keep the full tier, except
+//! the plain Windows console, whose fonts have no Nerd Font glyphs
+
+use crate::symbols::NerdFont;
+use std::env;
+
+/// Colors a terminal can show, lowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorTier {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorTier {
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    pub const NAMES: &[&str] = &["truecolor", "256", "16"];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "truecolor" | "24bit" => Some(Self::TrueColor),
+            "256" => Some(Self::Ansi256),
+            "16" => Some(Self::Ansi16),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::TrueColor => "truecolor",
+            Self::Ansi256 => "256",
+            Self::Ansi16 => "16",
+        }
+    }
+}
+
+/// What the terminal can render
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Term {
+    pub colors: ColorTier,
+    pub nerd_font: NerdFont,
+}
+
+/// Turn on ANSI escape processing in the Windows console; a no-op elsewhere
+pub fn enable_vt() {
+    #[cfg(windows)]
+    let _ = anstyle_query::windows::enable_ansi_colors();
+}
+
+impl Term {
+    pub fn detect() -> Self {
+        Self::from_env(
+            |name| env::var(name).ok().filter(|v| !v.is_empty()),
+            cfg!(windows),
+        )
+    }
+
+    fn from_env(var: impl Fn(&str) -> Option<String>, windows: bool) -> Self {
+        let term = var("TERM").unwrap_or_default();
+
+        // COLORTERM is the only reliable truecolor signal; TERM names the rest
+        let colors = if var("COLORTERM").is_some_and(|v| v == "truecolor" || v == "24bit") {
+            ColorTier::TrueColor
+        } else if term.contains("256color") {
+            ColorTier::Ansi256
+        } else if matches!(
+            term.as_str(),
+            "linux" | "dumb" | "ansi" | "screen" | "vt100" | "vt220"
+        ) {
+            ColorTier::Ansi16
+        } else {
+            ColorTier::TrueColor
+        };
+
+        // First locale variable set decides; no locale at all is taken as UTF-8
+        let unicode = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|name| var(name))
+            .is_none_or(|locale| {
+                let locale = locale.to_ascii_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            });
+
+        // The Linux console has no way to show patched glyphs; on Windows only
+        // terminals that announce themselves (Windows Terminal, WezTerm,
+        // VS Code, ...) are likely to be set up with a patched font
+        let console = if windows {
+            var("WT_SESSION").is_none() && var("TERM_PROGRAM").is_none()
+        } else {
+            term == "linux"
+        };
+        let nerd_font = if unicode && !console {
+            NerdFont::V3
+        } else {
+            NerdFont::None
+        };
+
+        Self { colors, nerd_font }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_on(vars: &[(&str, &str)], windows: bool) -> Term {
+        Term::from_env(
+            |name| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| (*value).to_string())
+            },
+            windows,
+        )
+    }
+
+    fn detect(vars: &[(&str, &str)]) -> Term {
+        detect_on(vars, false)
+    }
+
+    #[test]
+    fn test_colors() {
+        let colors = |vars| detect(vars).colors;
+        assert_eq!(
+            colors(&[("COLORTERM", "truecolor"), ("TERM", "xterm-256color")]),
+            ColorTier::TrueColor
+        );
+        assert_eq!(colors(&[("TERM", "xterm-256color")]), ColorTier::Ansi256);
+        assert_eq!(colors(&[("TERM", "linux")]), ColorTier::Ansi16);
+        assert_eq!(colors(&[("TERM", "xterm-kitty")]), ColorTier::TrueColor);
+    }
+
+    #[test]
+    fn test_unicode_and_nerd_font() {
+        let nerd_font = |vars| detect(vars).nerd_font;
+        assert_eq!(nerd_font(&[("LANG", "en_US.UTF-8")]), NerdFont::V3);
+        // The first locale variable set decides
+        assert_eq!(
+            nerd_font(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")]),
+            NerdFont::None
+        );
+        // No locale at all is taken as UTF-8
+        assert_eq!(nerd_font(&[]), NerdFont::V3);
+        assert_eq!(
+            detect(&[("TERM", "linux"), ("LANG", "C.utf8")]).nerd_font,
+            NerdFont::None
+        );
+    }
+
+    #[test]
+    fn test_windows_console() {
+        assert_eq!(detect_on(&[], true).nerd_font, NerdFont::None);
+        assert_eq!(
+            detect_on(&[("WT_SESSION", "5f0b")], true).nerd_font,
+            NerdFont::V3
+        );
+        assert_eq!(
+            detect_on(&[("TERM_PROGRAM", "WezTerm")], true).nerd_font,
+            NerdFont::V3
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! Per-phase timing for `--timings`, reported on stderr, and the clock the
+//! `time_budget_ms` budget runs on
+
+use crate::config::{Config, Detail};
+use crate::log;
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+/// Records phase durations when enabled; a no-op otherwise
+#[derive(Debug)]
+pub struct Timings {
+    enabled: bool,
+    phases: RefCell<Vec<(&'static str, Duration)>>,
+    start: Instant,
+    /// A detail was dropped for time, so the result is incomplete
+    dropped: Cell<bool>,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            phases: RefCell::default(),
+            start: Instant::now(),
+            dropped: Cell::new(false),
+        }
+    }
+
+    /// Time since the run started, recorded or not
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Whether `detail` may still be collected within the time budget
+    pub fn allows(&self, config: &Config, detail: Detail) -> bool {
+        let allowed = config.within_budget(detail, self.elapsed());
+        if !allowed {
+            log::debug!("time budget spent, dropping {}", detail.name());
+            self.dropped.set(true);
+        }
+        allowed
+    }
+
+    /// Whether a detail was dropped for time this run
+    pub fn dropped(&self) -> bool {
+        self.dropped.get()
+    }
+
+    /// Run `f`, recording its duration under `phase`
+    pub fn time<T>(&self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+        let start = Instant::now();
+        let out = f();
+        self.phases.borrow_mut().push((phase, start.elapsed()));
+        out
+    }
+
+    /// Phases recorded so far, in order
+    pub fn phases(&self) -> Vec<(&'static str, Duration)> {
+        self.phases.borrow().clone()
+    }
+
+    /// Print each phase and the total to stderr
+    #[cfg(feature = "cli")]
+    pub fn report(&self) {
+        if !self.enabled {
+            return;
+        }
+        let phases = self.phases.borrow();
+        let total: Duration = phases.iter().map(|(_, d)| *d).sum();
+        eprintln!("jj-starship timings:");
+        for (phase, duration) in phases.iter() {
+            eprintln!("  {phase:<14}{:>9.3}ms", millis(*duration));
+        }
+        eprintln!("  {:<14}{:>9.3}ms", "total", millis(total));
+    }
+}
+
+#[cfg(feature = "cli")]
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_within_budget() {
+        let config = Config {
+            time_budget_ms: 100,
+            priority: vec![
+                Detail::Name,
+                Detail::Id,
+                Detail::Status,
+                Detail::AheadBehind,
+            ],
+            ..Config::default()
+        };
+        let at = Duration::from_millis;
+        assert!(config.within_budget(Detail::AheadBehind, at(20)));
+        assert!(!config.within_budget(Detail::AheadBehind, at(30)));
+        assert!(config.within_budget(Detail::Status, at(30)));
+        assert!(!config.within_budget(Detail::Id, at(80)));
+        // Names and details left out of `priority` always stay
+        assert!(config.within_budget(Detail::Name, at(500)));
+        assert!(config.within_budget(Detail::Conflict, at(500)));
+
+        let timings = Timings::new(false);
+        let unlimited = Config::default();
+        assert!(timings.allows(&unlimited, Detail::AheadBehind));
+        assert!(!timings.dropped());
+        let spent = Config {
+            time_budget_ms: 1,
+            ..Config::default()
+        };
+        std::thread::sleep(at(2));
+        assert!(!timings.allows(&spent, Detail::Status));
+        assert!(timings.dropped());
+        assert!(!spent.budgeted(&timings).unwrap().jj_display.show_id);
+
+        // No overflow with a budget near the limit
+        let huge = Config {
+            time_budget_ms: usize::MAX,
+            ..Config::default()
+        };
+        assert!(huge.within_budget(Detail::AheadBehind, at(500)));
+    }
+}
@@ -0,0 +1,493 @@
+//! Config file validation for `jj-starship config validate`
+
+use crate::color;
+use crate::config::{self, Detail, Indicator, KEYS, Kind, Segment};
+use crate::template;
+use crate::term::ColorTier;
+use std::fmt;
+use std::ops::Range;
+use toml_edit::{Document, Item, TableLike};
+
+/// A problem in the config file, located by 1-based line and column
+#[derive(Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Check config file text for syntax errors, unknown keys and bad values
+pub fn validate(text: &str) -> Vec<Diagnostic> {
+    let doc = match Document::parse(text) {
+        Ok(doc) => doc,
+        Err(e) => {
+            let offset = e.span().map_or(0, |span| span.start);
+            return vec![diagnostic(text, offset, e.message().trim().to_string())];
+        }
+    };
+    let mut out = Vec::new();
+    check_table(doc.as_table(), "", "", text, &mut out);
+    check_preset(&doc, text, &mut out);
+    check_formats(doc.as_table(), "", &[], text, &mut out);
+    let inherited = custom_names(doc.as_table());
+    let presets = doc.as_table().get("presets").and_then(Item::as_table_like);
+    for (preset, item) in presets.into_iter().flat_map(|presets| presets.iter()) {
+        if let Some(table) = item.as_table_like() {
+            let shown = format!("presets.{preset}.");
+            check_formats(table, &shown, &inherited, text, &mut out);
+        }
+    }
+    out
+}
+
+/// Variables the line of each repo type sets beyond the segment names
+const FORMAT_VARIABLES: [(&str, &[&str]); 2] = [
+    ("jj", &["symbol", "bookmark", "change_id"]),
+    ("git", &["symbol", "branch", "commit"]),
+];
+
+/// `jj.format` and `git.format` may only use variables that get a value: segment
+/// names, the repo type's own variables and `custom.<name>` for commands under
+/// `[custom]` (`inherited` names those of the top level, for presets)
+fn check_formats(
+    table: &dyn TableLike,
+    shown: &str,
+    inherited: &[String],
+    text: &str,
+    out: &mut Vec<Diagnostic>,
+) {
+    let mut customs = custom_names(table);
+    customs.extend_from_slice(inherited);
+    for (section, own) in FORMAT_VARIABLES {
+        let Some(item) = table
+            .get(section)
+            .and_then(Item::as_table_like)
+            .and_then(|t| t.get("format"))
+        else {
+            continue;
+        };
+        let Some(format) = item.as_str() else {
+            continue;
+        };
+        for name in template::variables(format) {
+            let known = Segment::ALL.iter().any(|s| s.name() == name)
+                || own.contains(&name.as_str())
+                || name
+                    .strip_prefix("custom.")
+                    .is_some_and(|custom| customs.iter().any(|c| c == custom));
+            if !known {
+                let message = format!("`{shown}{section}.format`: unknown variable `{name}`");
+                out.push(at(text, item.span(), message));
+            }
+        }
+    }
+}
+
+/// Names of the commands under `[custom]` in `table`
+fn custom_names(table: &dyn TableLike) -> Vec<String> {
+    table
+        .get("custom")
+        .and_then(Item::as_table_like)
+        .map(|custom| custom.iter().map(|(name, _)| name.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// `preset` must name a built-in or a `[presets.<name>]` table
+fn check_preset(doc: &Document<&str>, text: &str, out: &mut Vec<Diagnostic>) {
+    let Some(item) = doc.as_table().get("preset") else {
+        return;
+    };
+    let Some(name) = item.as_str() else {
+        return;
+    };
+    let user_defined = doc
+        .as_table()
+        .get("presets")
+        .and_then(Item::as_table_like)
+        .is_some_and(|presets| presets.contains_key(name));
+    if !user_defined && !config::preset_names().any(|preset| preset == name) {
+        out.push(at(text, item.span(), format!("unknown preset `{name}`")));
+    }
+}
+
+/// `prefix` is the key path within the schema; `shown` is prepended for messages
+/// (`presets.<name>` tables hold the same keys as the top level)
+fn check_table(
+    table: &dyn TableLike,
+    prefix: &str,
+    shown: &str,
+    text: &str,
+    out: &mut Vec<Diagnostic>,
+) {
+    for (name, item) in table.iter() {
+        let path = if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{prefix}.{name}")
+        };
+        let key_span = table.get_key_value(name).and_then(|(key, _)| key.span());
+
+        if let Some(sub) = item.as_table_like() {
+            if path == "presets" && shown.is_empty() {
+                for (preset, preset_item) in sub.iter() {
+                    if let Some(preset_table) = preset_item.as_table_like() {
+                        let shown = format!("presets.{preset}.");
+                        check_table(preset_table, "", &shown, text, out);
+                    }
+                }
+                continue;
+            }
+            if is_section(&path) {
+                check_table(sub, &path, shown, text, out);
+                continue;
+            }
+        }
+        let display = format!("{shown}{path}");
+        let Some(&(_, kind)) = KEYS.iter().find(|(key, _)| *key == path) else {
+            out.push(at(text, key_span, format!("unknown key `{display}`")));
+            continue;
+        };
+        if kind == Kind::Rules {
+            check_rules(item, &display, text, out);
+            continue;
+        }
+        if kind == Kind::Aliases {
+            check_aliases(item, &display, text, out);
+            continue;
+        }
+        if kind == Kind::Table {
+            check_string_table(item, &display, text, out);
+            continue;
+        }
+        if let Err(message) = check_value(kind, item) {
+            let span = item.span().or(key_span);
+            out.push(at(text, span, format!("`{display}`: {message}")));
+        }
+    }
+}
+
+/// Tables that group keys, e.g. `[jj]`
+fn is_section(path: &str) -> bool {
+    KEYS.iter().any(|(key, _)| {
+        key.strip_prefix(path)
+            .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+fn check_value(kind: Kind, item: &Item) -> Result<(), String> {
+    let value = item.as_value().ok_or("expected a value, not a table")?;
+    match kind {
+        Kind::Usize => match value.as_integer() {
+            Some(n) if n >= 0 => Ok(()),
+            Some(_) => Err("expected a non-negative integer".into()),
+            None => Err("expected an integer".into()),
+        },
+        Kind::Bool => value
+            .as_bool()
+            .map(|_| ())
+            .ok_or_else(|| "expected true or false".into()),
+        Kind::String => value
+            .as_str()
+            .map(|_| ())
+            .ok_or_else(|| "expected a string".into()),
+        Kind::Paths => {
+            let array = value.as_array().ok_or("expected an array of paths")?;
+            if array.iter().all(|v| v.as_str().is_some()) {
+                Ok(())
+            } else {
+                Err("expected an array of paths".into())
+            }
+        }
+        Kind::Segments => {
+            let array = value
+                .as_array()
+                .ok_or("expected an array of segment names")?;
+            array.iter().try_for_each(|v| match v.as_str() {
+                Some(name) if Segment::parse(name).is_some() => Ok(()),
+                Some(name) => Err(format!(
+                    "unknown segment `{name}` (expected {})",
+                    one_of(&Segment::ALL.map(Segment::name))
+                )),
+                None => Err("expected an array of segment names".into()),
+            })
+        }
+        Kind::Details => {
+            let array = value
+                .as_array()
+                .ok_or("expected an array of detail names")?;
+            array.iter().try_for_each(|v| match v.as_str() {
+                Some(name) if Detail::parse(name).is_some() => Ok(()),
+                Some(name) => Err(format!(
+                    "unknown detail `{name}` (expected {})",
+                    one_of(&Detail::ALL.map(Detail::name))
+                )),
+                None => Err("expected an array of detail names".into()),
+            })
+        }
+        Kind::Indicators => {
+            let array = value
+                .as_array()
+                .ok_or("expected an array of indicator names")?;
+            array.iter().try_for_each(|v| match v.as_str() {
+                Some(name) if Indicator::parse(name).is_some() => Ok(()),
+                Some(name) => Err(format!(
+                    "unknown indicator `{name}` (expected {})",
+                    one_of(&Indicator::ALL.map(Indicator::name))
+                )),
+                None => Err("expected an array of indicator names".into()),
+            })
+        }
+        Kind::Strings => {
+            let array = value.as_array().ok_or("expected an array of strings")?;
+            if array.iter().all(|v| v.as_str().is_some()) {
+                Ok(())
+            } else {
+                Err("expected an array of strings".into())
+            }
+        }
+        Kind::Choice(options) => match value.as_str() {
+            Some(v) if options.contains(&v) => Ok(()),
+            Some(v) => Err(format!(
+                "unknown value `{v}` (expected {})",
+                options.join(", ")
+            )),
+            None => Err("expected a string".into()),
+        },
+        Kind::Style => check_color(value.as_str().ok_or("expected a style")?),
+        // Checked entry by entry in `check_rules` / `check_aliases` / `check_string_table`
+        Kind::Rules | Kind::Aliases | Kind::Table => Ok(()),
+    }
+}
+
+/// `a, b or c`
+fn one_of(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => (*name).to_string(),
+        [rest @ .., last] => format!("{} or {last}", rest.join(", ")),
+    }
+}
+
+fn check_color(name: &str) -> Result<(), String> {
+    match color::parse_style(name, ColorTier::TrueColor) {
+        Some(_) => Ok(()),
+        None => Err(format!("invalid style `{name}`")),
+    }
+}
+
+/// Each rule is `"pattern" = "style"` or `"pattern" = { style = "...", symbol = "..." }`
+fn check_rules(item: &Item, path: &str, text: &str, out: &mut Vec<Diagnostic>) {
+    let Some(table) = item.as_table_like() else {
+        out.push(at(text, item.span(), format!("`{path}`: expected a table")));
+        return;
+    };
+    for (pattern, rule) in table.iter() {
+        let span = rule.span();
+        let result = match rule.as_str() {
+            Some(style) => check_color(style),
+            None => rule.as_table_like().map_or_else(
+                || Err("expected a style or { style, symbol }".into()),
+                |entry| {
+                    entry.iter().try_for_each(|(field, value)| match field {
+                        "style" => check_color(value.as_str().ok_or("expected a style")?),
+                        "symbol" => value
+                            .as_str()
+                            .map(|_| ())
+                            .ok_or_else(|| "`symbol`: expected a string".into()),
+                        _ => Err(format!("unknown rule field `{field}`")),
+                    })
+                },
+            ),
+        };
+        if let Err(message) = result {
+            out.push(at(text, span, format!("`{path}.{pattern}`: {message}")));
+        }
+    }
+}
+
+/// Each alias is `"pattern" = "alias"` or `"pattern" = { alias = "...", symbol = "..." }`
+fn check_aliases(item: &Item, path: &str, text: &str, out: &mut Vec<Diagnostic>) {
+    let Some(table) = item.as_table_like() else {
+        out.push(at(text, item.span(), format!("`{path}`: expected a table")));
+        return;
+    };
+    for (pattern, alias) in table.iter() {
+        if alias.as_str().is_some() {
+            continue;
+        }
+        let result = alias.as_table_like().map_or_else(
+            || Err("expected an alias or { alias, symbol }".into()),
+            |entry| {
+                entry.iter().try_for_each(|(field, value)| match field {
+                    "alias" | "symbol" => value
+                        .as_str()
+                        .map(|_| ())
+                        .ok_or_else(|| format!("`{field}`: expected a string")),
+                    _ => Err(format!("unknown alias field `{field}`")),
+                })
+            },
+        );
+        if let Err(message) = result {
+            out.push(at(
+                text,
+                alias.span(),
+                format!("`{path}.{pattern}`: {message}"),
+            ));
+        }
+    }
+}
+
+/// Each entry is `name = "string"`
+fn check_string_table(item: &Item, path: &str, text: &str, out: &mut Vec<Diagnostic>) {
+    let Some(table) = item.as_table_like() else {
+        out.push(at(text, item.span(), format!("`{path}`: expected a table")));
+        return;
+    };
+    for (name, value) in table.iter() {
+        if value.as_str().is_none() {
+            let message = format!("`{path}.{name}`: expected a string");
+            out.push(at(text, value.span(), message));
+        }
+    }
+}
+
+fn at(text: &str, span: Option<Range<usize>>, message: String) -> Diagnostic {
+    diagnostic(text, span.map_or(0, |span| span.start), message)
+}
+
+fn diagnostic(text: &str, offset: usize, message: String) -> Diagnostic {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Diagnostic {
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_config() {
+        let text = "id_length = 6\nslow_paths = [\"/mnt/big\"]\nsegments = [\"status\", \"name\"]\n\n[jj]\nsymbol = \"jj \"\nname_style = \"bright cyan\"\n";
+        assert_eq!(validate(text), Vec::new());
+    }
+
+    #[test]
+    fn test_reports_positions() {
+        let text = "id_lenght = 6\n[git]\nname_style = \"purpel\"\nid = 3\n";
+        let messages: Vec<String> = validate(text).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "1:1: unknown key `id_lenght`",
+                "3:14: `git.name_style`: invalid style `purpel`",
+                "4:6: `git.id`: expected true or false",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_presets() {
+        let text = "preset = \"work\"\n[presets.work]\nno_symbol = true\ncolour = 1\n";
+        let messages: Vec<String> = validate(text).iter().map(ToString::to_string).collect();
+        assert_eq!(messages, ["4:1: unknown key `presets.work.colour`"]);
+        assert_eq!(validate("preset = \"ascii\"\n"), Vec::new());
+        assert_eq!(validate("preset = \"fancy\"\n").len(), 1);
+    }
+
+    #[test]
+    fn test_segments() {
+        let messages: Vec<String> = validate("segments = [\"status\", \"branch\"]\n")
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "1:12: `segments`: unknown segment `branch` (expected prefix, name, id, state, status, type, op, base, action, description, workspace or op_id)"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hide() {
+        assert_eq!(validate("hide = [\"untracked\", \"ahead\"]\n"), Vec::new());
+        let messages: Vec<String> = validate("hide = [\"dirty\"]\n")
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(messages.len(), 1);
+        assert!(
+            messages[0].starts_with("1:8: `hide`: unknown indicator `dirty` (expected skipped, ")
+        );
+    }
+
+    #[test]
+    fn test_choice() {
+        assert_eq!(validate("nerd_font = \"v2\"\n"), Vec::new());
+        let messages: Vec<String> = validate("nerd_font = \"v4\"\n")
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            ["1:13: `nerd_font`: unknown value `v4` (expected v3, v2, none)"]
+        );
+    }
+
+    #[test]
+    fn test_branch_styles() {
+        let text = "[branch_styles]\n\"main|master\" = \"green\"\n\"hotfix/*\" = { style = \"red\", symbol = \"!\" }\n\"wip/*\" = { style = \"blurple\" }\n";
+        let messages: Vec<String> = validate(text).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            ["4:11: `branch_styles.wip/*`: invalid style `blurple`"]
+        );
+    }
+
+    #[test]
+    fn test_repo_aliases() {
+        let text = "[repo_aliases]\n\"~/src/mono*\" = { alias = \"mono\", symbol = \"M \" }\n\"*github.com*dotfiles*\" = \"dots\"\n\"/tmp/*\" = { name = \"tmp\" }\n";
+        let messages: Vec<String> = validate(text).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            ["4:12: `repo_aliases./tmp/*`: unknown alias field `name`"]
+        );
+    }
+
+    #[test]
+    fn test_format_variables() {
+        let text = "[custom]\nop = \"jj op log\"\n[jj]\nformat = \"$symbol$name ${custom.op} $branch\"\n[git]\nformat = \"$branch ${custom.nope}\"\n";
+        let messages: Vec<String> = validate(text).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "4:10: `jj.format`: unknown variable `branch`",
+                "6:10: `git.format`: unknown variable `custom.nope`",
+            ]
+        );
+        let text =
+            "[custom]\nop = \"jj op log\"\n[presets.work.jj]\nformat = \"${custom.op} $xyz\"\n";
+        let messages: Vec<String> = validate(text).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            ["4:10: `presets.work.jj.format`: unknown variable `xyz`"]
+        );
+    }
+
+    #[test]
+    fn test_syntax_error() {
+        let diagnostics = validate("[jj\nsymbol = 1\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, 1);
+    }
+}
@@ -0,0 +1,86 @@
+//! `--version` output; `--verbose` adds what bug reports need to tell builds
+//! apart: compiled-in features, the linked libgit2, the installed jj and the
+//! target triple
+
+use std::process::{Command, Stdio};
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `jj-starship <version>`
+pub fn short() -> String {
+    format!("jj-starship {VERSION}")
+}
+
+/// The short line plus one `name: value` line per build detail
+pub fn verbose() -> String {
+    let features = features();
+    let features = if features.is_empty() {
+        "none".to_string()
+    } else {
+        features.join(", ")
+    };
+    format!(
+        "{}\nfeatures: {features}\nlibgit2: {}\njj: {}\ntarget: {}\n",
+        short(),
+        libgit2(),
+        jj_cli().unwrap_or_else(|| "not found".into()),
+        env!("JJ_STARSHIP_TARGET"),
+    )
+}
+
+/// Enabled cargo features
+fn features() -> Vec<&'static str> {
+    [
+        ("git", cfg!(feature = "git")),
+        ("cli", cfg!(feature = "cli")),
+        ("color-tables", cfg!(feature = "color-tables")),
+        ("tracing", cfg!(feature = "tracing")),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect()
+}
+
+#[cfg(feature = "git")]
+fn libgit2() -> String {
+    let version = git2::Version::get();
+    let (major, minor, patch) = version.libgit2_version();
+    let linkage = if version.vendored() {
+        "vendored"
+    } else {
+        "system"
+    };
+    format!("{major}.{minor}.{patch} ({linkage})")
+}
+
+#[cfg(not(feature = "git"))]
+fn libgit2() -> String {
+    "not built in".into()
+}
+
+/// `jj --version` of the jj on `PATH`, without the leading `jj`; the prompt
+/// reads repos through jj-lib, so this only matters for repo format mismatches
+fn jj_cli() -> Option<String> {
+    let output = Command::new("jj")
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    let version = version.trim();
+    Some(version.strip_prefix("jj ").unwrap_or(version).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_features() {
+        let features = features();
+        assert_eq!(features.contains(&"git"), cfg!(feature = "git"));
+        assert_eq!(features.contains(&"cli"), cfg!(feature = "cli"));
+    }
+}